@@ -19,6 +19,11 @@ class Module(object):
     def challenge(self):
         return None
 
+    # noinspection PyMethodMayBeStatic
+    def read_strategy(self):
+        # None reads a single chunk; cf. "Reading Responses" in README.md
+        return None
+
     def check_response(self, **kwargs):
         raise NotImplementedError
 
@@ -0,0 +1,50 @@
+from dirac import *
+
+import dirac
+
+
+class Module(dirac.Module):
+    _module_protocol = "dialog/tcp"
+
+    @classmethod
+    def check_args(cls, port, steps):
+        is_valid_port_number(port)
+        if not isinstance(steps, list) or len(steps) == 0:
+            raise InvalidArgumentError('steps', steps, "is not a non-empty list of steps")
+        for step in steps:
+            Module._is_valid_step(step)
+
+        return True
+
+    @classmethod
+    def _is_valid_step(cls, step):
+        if not isinstance(step, dict) or not set(step.keys()) <= {"send", "expect", "timeout"}:
+            raise InvalidArgumentError('steps', step, "is not a hash of 'send', 'expect' and 'timeout'")
+        if "send" not in step and "expect" not in step:
+            raise InvalidArgumentError('steps', step, "neither sends nor expects anything")
+        if "send" in step and not isinstance(step["send"], str):
+            raise InvalidArgumentError('send', step["send"], "is not a string")
+        if "expect" in step:
+            is_valid_regex(step["expect"], "expect")
+        if "timeout" in step:
+            timeout = step["timeout"]
+            if isinstance(timeout, bool) or not isinstance(timeout, (int, float)) or timeout <= 0:
+                raise InvalidArgumentError('timeout', timeout, "is not a positive number of seconds")
+
+    def __init__(self, port, steps):
+        self.port = int(port)
+        self.steps = steps
+
+    def challenge(self):
+        return [{"send": step.get("send"), "expect": step.get("expect"), "timeout": step.get("timeout")}
+                for step in self.steps]
+
+    def check_response(self, transcript):
+        for number, step in enumerate(transcript, 1):
+            if step["matched"] is False:
+                raise ResponseCheckError("Step %d: expected '%s'; received '%s'." %
+                                         (number, step["expect"], step["received"]))
+        if len(transcript) != len(self.steps):
+            raise ResponseCheckError("Dialog ended after %d of %d steps." % (len(transcript), len(self.steps)))
+
+        return True
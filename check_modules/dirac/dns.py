@@ -0,0 +1,94 @@
+import dirac
+from dirac import *
+
+import re
+
+
+class Module(dirac.Module):
+    _record_types = ['A', 'AAAA', 'MX', 'TXT', 'SRV', 'CNAME', 'PTR', 'SOA', 'NS']
+    _rcodes = ['NOERROR', 'FORMERR', 'SERVFAIL', 'NXDOMAIN', 'NOTIMP', 'REFUSED']
+    _transports = ['udp', 'tcp']
+
+    @classmethod
+    def check_args(cls, port, name, type="A", transport="udp", recursion_desired=True, rcode="NOERROR",
+                   answers=None, min_ttl=None, max_ttl=None, authoritative=None):
+        is_valid_port_number(port)
+        if not isinstance(name, str) or not name:
+            raise InvalidArgumentError('name', name, "is not a domain name")
+        if not isinstance(type, str) or type.upper() not in Module._record_types:
+            raise InvalidArgumentError('type', type, "is not one of %s" % Module._record_types)
+        if transport not in Module._transports:
+            raise InvalidArgumentError('transport', transport, "is not one of %s" % Module._transports)
+        if not isinstance(recursion_desired, bool):
+            raise InvalidArgumentError('recursion_desired', recursion_desired, "is not a bool")
+        if rcode is not None and (not isinstance(rcode, str) or rcode.upper() not in Module._rcodes):
+            raise InvalidArgumentError('rcode', rcode, "is not one of %s" % Module._rcodes)
+        if answers is not None:
+            expected = answers if isinstance(answers, list) else [answers]
+            if not all(isinstance(answer, str) for answer in expected):
+                raise InvalidArgumentError('answers', answers, "is not a record or a list of records")
+        if min_ttl is not None:
+            is_valid_number(min_ttl, 0, 0x7FFFFFFF, "min_ttl", "is not a valid TTL")
+        if max_ttl is not None:
+            is_valid_number(max_ttl, 0, 0x7FFFFFFF, "max_ttl", "is not a valid TTL")
+        if min_ttl is not None and max_ttl is not None and int(min_ttl) > int(max_ttl):
+            raise InvalidArgumentError('min_ttl', min_ttl, "is greater than max_ttl")
+        if authoritative is not None and not isinstance(authoritative, bool):
+            raise InvalidArgumentError('authoritative', authoritative, "is not a bool")
+
+        return True
+
+    def __init__(self, port, name, type="A", transport="udp", recursion_desired=True, rcode="NOERROR",
+                 answers=None, min_ttl=None, max_ttl=None, authoritative=None):
+        self.port = int(port)
+        self.name = name
+        self.type = type.upper()
+        self.transport = transport
+        self.recursion_desired = recursion_desired
+        self.rcode = rcode.upper() if rcode is not None else None
+        if answers is None:
+            self.answers = []
+        else:
+            self.answers = answers if isinstance(answers, list) else [answers]
+        self.min_ttl = int(min_ttl) if min_ttl is not None else None
+        self.max_ttl = int(max_ttl) if max_ttl is not None else None
+        self.authoritative = authoritative
+
+    def protocol(self):
+        return "dns/%s" % self.transport
+
+    def challenge(self):
+        return {"name": self.name, "type": self.type, "recursion_desired": self.recursion_desired}
+
+    def check_response(self, rcode, authoritative, truncated, recursion_available, answers, authority):
+        if self.rcode is not None and rcode != self.rcode:
+            raise ResponseCheckError("Unexpected rcode '%s' for '%s'; expected '%s'." % (rcode, self.name, self.rcode))
+        if self.authoritative is not None and authoritative != self.authoritative:
+            raise ResponseCheckError("Answer for '%s' is %sauthoritative; expected the opposite." %
+                                     (self.name, "" if authoritative else "not "))
+
+        # Answers may also include the CNAME records leading to the records asked for.
+        records = [record for record in answers if record["type"] == self.type]
+        found = [_normalize(record) for record in records]
+        for answer in self.answers:
+            if _normalize({"type": self.type, "data": answer}) not in found:
+                raise ResponseCheckError("Missing %s record '%s' for '%s'; found '%s'." %
+                                         (self.type, answer, self.name,
+                                          "', '".join(record["data"] for record in records)))
+        for record in records:
+            if self.min_ttl is not None and record["ttl"] < self.min_ttl:
+                raise ResponseCheckError("TTL %d of %s record '%s' is less than %d." %
+                                         (record["ttl"], self.type, record["data"], self.min_ttl))
+            if self.max_ttl is not None and record["ttl"] > self.max_ttl:
+                raise ResponseCheckError("TTL %d of %s record '%s' is greater than %d." %
+                                         (record["ttl"], self.type, record["data"], self.max_ttl))
+
+        return True
+
+
+def _normalize(record):
+    """Makes record data comparable: names without the trailing dot and case, TXT strings without quotes."""
+    data = record["data"].strip()
+    if record["type"] == "TXT" and data.startswith('"'):
+        return "".join(re.findall(r'"((?:[^"\\]|\\.)*)"', data))
+    return data.rstrip(".").lower()
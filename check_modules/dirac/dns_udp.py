@@ -1,26 +1,23 @@
 from dirac import *
-import dirac
+from dirac import dns
 
 
-class Module(dirac.Module):
-    _module_protocol = "text/udp"
-
+# `dns` over UDP; without a `name`, it asks for the root's NS records and accepts any answer.
+class Module(dns.Module):
     @classmethod
-    def check_args(cls, port):
-        is_valid_port_number(port)
-
-        return True
-
-    def __init__(self, port):
-        self.port = port
+    def check_args(cls, port, name=None, **kwargs):
+        return super(Module, cls).check_args(port, **Module._query(name, kwargs))
 
-    def challenge(self):
-        return "Dirac"
+    def __init__(self, port, name=None, **kwargs):
+        super(Module, self).__init__(port, **Module._query(name, kwargs))
 
-    def check_response(self, response):
-        try:
-            return not response or len(response) > 0
-        except ValueError:
-            raise ResponseCheckError(
-                "Invalid identification string '%s' in response; cf. RFC4253, section 4.2." %
-                response)
+    @staticmethod
+    def _query(name, kwargs):
+        query = dict(kwargs, transport="udp")
+        if name is None:
+            query.update(name=".")
+            query.setdefault("type", "NS")
+            query.setdefault("rcode", None)
+        else:
+            query.update(name=name)
+        return query
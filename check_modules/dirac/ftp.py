@@ -16,7 +16,11 @@ class Module(text_tcp.Module):
         self.port = port
         self.response_code = int(response_code)
 
-    def check_response(self, response):
+    def read_strategy(self):
+        # Greetings may span several lines and segments.
+        return {"until": "idle"}
+
+    def check_response(self, response, tls=None):
         try:
             response_code_str = response.split(" ")[0]
             response_code = int(response_code_str)
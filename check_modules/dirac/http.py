@@ -6,19 +6,41 @@ import re
 
 class Module(text_tcp.Module):
     _module_protocol = "http/tcp"
+    _verbs = ['GET', 'HEAD', 'POST', 'PUT', 'DELETE', 'OPTIONS']
 
     @classmethod
-    def check_args(cls, port, verb, uri, response_code):
+    def check_args(cls, port, verb, uri, response_code, headers=None, body=None, follow_redirects=False,
+                   location=None, redirect_chain=None, expected_headers=None, body_regex=None, body_contains=None):
         is_valid_port_number(port)
         Module._is_valid_http_verb(verb)
         Module._is_valid_uri(uri)
-        is_valid_number(response_code, 1, 599, "response_code", "is not a valid HTTP response code")
+        for code in Module._response_codes(response_code):
+            is_valid_number(code, 1, 599, "response_code", "is not a valid HTTP response code")
+        if headers is not None:
+            Module._is_valid_header_map(headers, "headers")
+        if body is not None and not isinstance(body, str):
+            raise InvalidArgumentError('body', body, "is not a string")
+        if not isinstance(follow_redirects, bool):
+            raise InvalidArgumentError('follow_redirects', follow_redirects, "is not a bool")
+        if location is not None and not isinstance(location, str):
+            raise InvalidArgumentError('location', location, "is not a string")
+        if redirect_chain is not None:
+            if not follow_redirects:
+                raise InvalidArgumentError('redirect_chain', redirect_chain, "requires follow_redirects")
+            if not isinstance(redirect_chain, list) or not all(isinstance(l, str) for l in redirect_chain):
+                raise InvalidArgumentError('redirect_chain', redirect_chain, "is not a list of strings")
+        if expected_headers is not None:
+            Module._is_valid_header_map(expected_headers, "expected_headers")
+        if body_regex is not None:
+            is_valid_regex(body_regex, "body_regex")
+        if body_contains is not None and not isinstance(body_contains, str):
+            raise InvalidArgumentError('body_contains', body_contains, "is not a string")
 
         return True
 
     @classmethod
     def _is_valid_http_verb(cls, verb):
-        if verb.upper() not in ['GET', ]:
+        if verb.upper() not in Module._verbs:
             raise InvalidArgumentError('verb', verb, "is not a valid HTTP verb")
 
     @classmethod
@@ -26,22 +48,80 @@ class Module(text_tcp.Module):
         if uri == "":
             raise InvalidArgumentError('uri', uri, "is not a valid URI")
 
-    def __init__(self, port, verb, uri, response_code):
+    @classmethod
+    def _is_valid_header_map(cls, headers, name):
+        if not isinstance(headers, dict):
+            raise InvalidArgumentError(name, headers, "is not a hash of header names to values")
+        for value in headers.values():
+            if not isinstance(value, (str, int)):
+                raise InvalidArgumentError(name, headers, "is not a hash of header names to values")
+
+    @classmethod
+    def _response_codes(cls, response_code):
+        return response_code if isinstance(response_code, list) else [response_code]
+
+    def __init__(self, port, verb, uri, response_code, headers=None, body=None, follow_redirects=False,
+                 location=None, redirect_chain=None, expected_headers=None, body_regex=None, body_contains=None):
         self.port = port
         self.verb = verb.upper()
         self.uri = uri
-        self.response_code = int(response_code)
+        self.response_codes = [int(code) for code in Module._response_codes(response_code)]
+        self.headers = dict((k, str(v)) for k, v in (headers or {}).items())
+        self.body = body
+        self.follow_redirects = follow_redirects
+        self.location = location
+        self.redirect_chain = redirect_chain
+        self.expected_headers = dict((k.lower(), str(v)) for k, v in (expected_headers or {}).items())
+        self.body_regex = re.compile(body_regex) if body_regex is not None else None
+        self.body_contains = body_contains
 
     def challenge(self):
-        return "%s %s" % (self.verb, self.uri)
+        return {
+            "verb": self.verb,
+            "uri": self.uri,
+            "headers": self.headers,
+            "body": self.body,
+            "follow_redirects": self.follow_redirects,
+        }
 
-    def check_response(self, response_code, headers, body):
+    def check_response(self, response_code, headers, body, redirects=None, tls=None, body_truncated=False):
         try:
             response_code = int(response_code)
-            if response_code != self.response_code:
-                raise ResponseCheckError("Unexpected response code '%d'; expected '%d'." %
-                                         (response_code, self.response_code))
         except ValueError:
-            raise ResponseCheckError("Invalid response code '%d' in response." % response_code)
+            raise ResponseCheckError("Invalid response code '%s' in response." % response_code)
+        if response_code not in self.response_codes:
+            raise ResponseCheckError("Unexpected response code '%d'; expected '%s'." %
+                                     (response_code, "' or '".join(str(c) for c in self.response_codes)))
+
+        headers = Module._normalize_headers(headers)
+        if self.location is not None and headers.get("location") != self.location:
+            raise ResponseCheckError("Unexpected location '%s'; expected '%s'." %
+                                     (headers.get("location"), self.location))
+        if self.redirect_chain is not None:
+            chain = [redirect["location"] for redirect in redirects or []]
+            if chain != self.redirect_chain:
+                raise ResponseCheckError("Unexpected redirect chain '%s'; expected '%s'." %
+                                         (" -> ".join(chain), " -> ".join(self.redirect_chain)))
+        for name, value in self.expected_headers.items():
+            if headers.get(name) != value:
+                raise ResponseCheckError("Unexpected value '%s' of header '%s'; expected '%s'." %
+                                         (headers.get(name), name, value))
+        truncated = " (truncated after %d bytes)" % len(body.encode("utf-8")) if body_truncated else ""
+        if self.body_regex is not None and self.body_regex.search(body) is None:
+            raise ResponseCheckError("Body%s does not match '%s'." % (truncated, self.body_regex.pattern))
+        if self.body_contains is not None and self.body_contains not in body:
+            raise ResponseCheckError("Body%s does not contain '%s'." % (truncated, self.body_contains))
 
         return True
+
+    @classmethod
+    def _normalize_headers(cls, headers):
+        """Returns the headers as a dict with lower case names; accepts raw header text, too."""
+        if isinstance(headers, dict):
+            return dict((k.lower(), v) for k, v in headers.items())
+        normalized = {}
+        for line in (headers or "").splitlines():
+            if ":" in line:
+                name, value = line.split(":", 1)
+                normalized[name.strip().lower()] = value.strip()
+        return normalized
@@ -4,3 +4,53 @@ from dirac import http
 
 class Module(http.Module):
     _module_protocol = "https/tcp"
+    _tls_versions = ['SSLv3', 'TLSv1', 'TLSv1.1', 'TLSv1.2', 'TLSv1.3']
+
+    @classmethod
+    def check_args(cls, min_days_valid=None, expected_san=None, min_tls_version=None, **kwargs):
+        if min_days_valid is not None:
+            is_valid_number(min_days_valid, 0, 0x7FFFFFFF, "min_days_valid", "is not a valid number of days")
+        if expected_san is not None:
+            sans = expected_san if isinstance(expected_san, list) else [expected_san]
+            if not all(isinstance(san, str) for san in sans):
+                raise InvalidArgumentError('expected_san', expected_san, "is not a name or a list of names")
+        if min_tls_version is not None and min_tls_version not in Module._tls_versions:
+            raise InvalidArgumentError('min_tls_version', min_tls_version, "is not one of %s" % Module._tls_versions)
+
+        return super(Module, cls).check_args(**kwargs)
+
+    def __init__(self, min_days_valid=None, expected_san=None, min_tls_version=None, **kwargs):
+        super(Module, self).__init__(**kwargs)
+        self.min_days_valid = int(min_days_valid) if min_days_valid is not None else None
+        if expected_san is None:
+            self.expected_sans = []
+        else:
+            self.expected_sans = expected_san if isinstance(expected_san, list) else [expected_san]
+        self.min_tls_version = min_tls_version
+
+    def check_response(self, response_code, headers, body, redirects=None, tls=None, body_truncated=False):
+        super(Module, self).check_response(response_code, headers, body, redirects, tls, body_truncated)
+
+        checks_tls = self.min_days_valid is not None or self.expected_sans or self.min_tls_version is not None
+        if not checks_tls:
+            return True
+        if not tls or not tls.get("chain"):
+            raise ResponseCheckError("No TLS certificate in response.")
+
+        certificate = tls["chain"][0]
+        if self.min_days_valid is not None and certificate["days_valid"] < self.min_days_valid:
+            raise ResponseCheckError("Certificate '%s' expires in %d days on '%s'; expected at least %d days." %
+                                     (certificate["subject"], certificate["days_valid"], certificate["not_after"],
+                                      self.min_days_valid))
+        for san in self.expected_sans:
+            if san not in certificate["sans"]:
+                raise ResponseCheckError("Certificate '%s' does not include SAN '%s'; found '%s'." %
+                                         (certificate["subject"], san, "', '".join(certificate["sans"])))
+        if self.min_tls_version is not None:
+            version = tls["version"]
+            if version not in Module._tls_versions or \
+                    Module._tls_versions.index(version) < Module._tls_versions.index(self.min_tls_version):
+                raise ResponseCheckError("Unexpected TLS version '%s'; expected at least '%s'." %
+                                         (version, self.min_tls_version))
+
+        return True
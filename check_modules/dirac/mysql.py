@@ -16,7 +16,11 @@ class Module(dirac.Module):
     def challenge(self):
         return None
 
-    def check_response(self, response):
+    def read_strategy(self):
+        # The handshake packet may be split across segments.
+        return {"until": "idle"}
+
+    def check_response(self, response, tls=None):
         try:
             if not self._sanity_check(response):
                 return False
@@ -6,22 +6,28 @@ import re
 
 class Module(text_tcp.Module):
     @classmethod
-    def check_args(cls, port, software, proxy, return_code):
+    def check_args(cls, port, software, proxy, return_code, starttls=None):
         is_valid_port_number(port)
         is_valid_regex(software, "software")
-        try:
-            bool(proxy)
-        except ValueError:
+        if not isinstance(proxy, bool):
             raise InvalidArgumentError('proxy', proxy, "is not a bool")
         is_valid_number(return_code, 100, 600, "return_code", "is not a valid return code")
+        if starttls not in (None, "required"):
+            raise InvalidArgumentError('starttls', starttls, "is not 'required'")
+        if starttls and proxy:
+            raise InvalidArgumentError('starttls', starttls, "cannot be combined with proxy")
 
         return True
 
-    def __init__(self, port, software, proxy, return_code):
+    def __init__(self, port, software, proxy, return_code, starttls=None):
         self.port = int(port)
         self.software = re.compile(software)
         self.proxy = bool(proxy)
         self.return_code = int(return_code)
+        self.starttls = starttls == "required"
+
+    def protocol(self):
+        return "starttls/smtp" if self.starttls else super(Module, self).protocol()
 
     def challenge(self):
         challenge_str = ""
@@ -30,7 +36,16 @@ class Module(text_tcp.Module):
         challenge_str += "quit\n"
         return challenge_str
 
-    def check_response(self, response):
+    def read_strategy(self):
+        # Greetings may span several lines and segments.
+        return {"until": "idle"}
+
+    def check_response(self, response, tls=None, banner=None):
+        if self.starttls:
+            if tls is None or banner is None:
+                raise ResponseCheckError("Connection has not been upgraded via STARTTLS.")
+            # With STARTTLS, the greeting has been received before the upgrade.
+            response = banner
         try:
             return_code = int(re.split('-| ', response)[0])
             if self.return_code != return_code:
@@ -14,10 +14,15 @@ class Module(text_tcp.Module):
 
     def __init__(self, port, version, software):
         self.port = port
-        self.version = version
+        # YAML turns unquoted versions like 2.0 into floats
+        self.version = str(version)
         self.software = re.compile(software)
 
-    def check_response(self, response):
+    def read_strategy(self):
+        # The identification string is a single line; cf. RFC4253, section 4.2.
+        return {"until": "delimiter", "delimiter": "\n"}
+
+    def check_response(self, response, tls=None):
         try:
             # cf. https://tools.ietf.org/html/rfc4253#section-4.2
             (ssh, version, software) = response.strip().split(' ')[0].split('-', 2)
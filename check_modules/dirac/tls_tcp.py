@@ -0,0 +1,5 @@
+import dirac
+
+
+class Module(dirac.Module):
+    _module_protocol = "tls/tcp"
@@ -0,0 +1,57 @@
+import unittest
+
+from dirac import InvalidArgumentError, ResponseCheckError
+from dirac.dialog import Module
+
+EHLO_STEPS = [
+    {"expect": "^220 "},
+    {"send": "EHLO dirac\r\n", "expect": "(?m)^250[- ]STARTTLS", "timeout": 2},
+    {"send": "QUIT\r\n"},
+]
+
+
+def step(sent, received, expect, matched):
+    return {"sent": sent, "received": received, "expect": expect, "matched": matched}
+
+
+class UnitTests(unittest.TestCase):
+    def test_check_args(self):
+        res = Module.check_args(25, EHLO_STEPS)
+        self.assertTrue(res)
+
+    def test_check_args_no_steps(self):
+        with self.assertRaises(InvalidArgumentError):
+            Module.check_args(25, [])
+
+    def test_check_args_unknown_key(self):
+        with self.assertRaises(InvalidArgumentError):
+            Module.check_args(25, [{"sned": "EHLO dirac\r\n"}])
+
+    def test_check_args_invalid_regex(self):
+        with self.assertRaises(InvalidArgumentError):
+            Module.check_args(25, [{"expect": "(220"}])
+
+    def test_check_args_invalid_timeout(self):
+        with self.assertRaises(InvalidArgumentError):
+            Module.check_args(25, [{"expect": "^220", "timeout": 0}])
+
+    def test_challenge(self):
+        m = Module(25, EHLO_STEPS)
+        self.assertEqual(m.challenge()[2], {"send": "QUIT\r\n", "expect": None, "timeout": None})
+
+    def test_check_response(self):
+        m = Module(25, EHLO_STEPS)
+        res = m.check_response([
+            step(None, "220 mail.local ESMTP\r\n", "^220 ", True),
+            step("EHLO dirac\r\n", "250-mail.local\r\n250 STARTTLS\r\n", "(?m)^250[- ]STARTTLS", True),
+            step("QUIT\r\n", "", None, None),
+        ])
+        self.assertTrue(res)
+
+    def test_check_response_mismatch(self):
+        m = Module(25, EHLO_STEPS)
+        with self.assertRaises(ResponseCheckError):
+            m.check_response([
+                step(None, "220 mail.local ESMTP\r\n", "^220 ", True),
+                step("EHLO dirac\r\n", "250 mail.local\r\n", "(?m)^250[- ]STARTTLS", False),
+            ])
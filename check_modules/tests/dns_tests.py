@@ -0,0 +1,74 @@
+import unittest
+
+from dirac import InvalidArgumentError, ResponseCheckError
+from dirac.dns import Module
+
+
+def record(type, data, ttl=300, name="example.com."):
+    return {"name": name, "type": type, "ttl": ttl, "data": data}
+
+
+def response(rcode="NOERROR", authoritative=False, answers=None):
+    return {"rcode": rcode, "authoritative": authoritative, "truncated": False, "recursion_available": True,
+            "answers": answers or [], "authority": []}
+
+
+class UnitTests(unittest.TestCase):
+    def test_check_args(self):
+        res = Module.check_args(53, "example.com", type="MX", transport="tcp", answers=["10 mail.example.com"],
+                                min_ttl=60, max_ttl=86400, authoritative=True)
+        self.assertTrue(res)
+
+    def test_check_args_invalid(self):
+        with self.assertRaises(InvalidArgumentError):
+            Module.check_args(53, "example.com", type="ANY")
+        with self.assertRaises(InvalidArgumentError):
+            Module.check_args(53, "example.com", transport="tls")
+        with self.assertRaises(InvalidArgumentError):
+            Module.check_args(53, "example.com", rcode="OK")
+        with self.assertRaises(InvalidArgumentError):
+            Module.check_args(53, "example.com", min_ttl=600, max_ttl=60)
+        with self.assertRaises(InvalidArgumentError):
+            Module.check_args(53, "")
+
+    def test_protocol_and_challenge(self):
+        m = Module(53, "example.com", type="aaaa", transport="tcp", recursion_desired=False)
+        self.assertEqual(m.protocol(), "dns/tcp")
+        self.assertEqual(m.challenge(), {"name": "example.com", "type": "AAAA", "recursion_desired": False})
+
+    def test_check_response(self):
+        m = Module(53, "www.example.com", answers="192.0.2.1", min_ttl=60, authoritative=True)
+        res = m.check_response(**response(authoritative=True, answers=[
+            record("CNAME", "example.com.", name="www.example.com."),
+            record("A", "192.0.2.1"),
+            record("A", "192.0.2.2")]))
+        self.assertTrue(res)
+
+    def test_check_response_rcode(self):
+        m = Module(53, "missing.example.com")
+        with self.assertRaises(ResponseCheckError):
+            m.check_response(**response(rcode="NXDOMAIN"))
+        self.assertTrue(Module(53, "missing.example.com", rcode="NXDOMAIN").check_response(**response("NXDOMAIN")))
+
+    def test_check_response_missing_answer(self):
+        m = Module(53, "example.com", type="MX", answers=["10 mail.example.com", "20 backup.example.com"])
+        with self.assertRaises(ResponseCheckError):
+            m.check_response(**response(answers=[record("MX", "10 mail.example.com.")]))
+
+    def test_check_response_names_and_txt(self):
+        m = Module(53, "example.com", type="MX", answers="10 Mail.Example.com.")
+        self.assertTrue(m.check_response(**response(answers=[record("MX", "10 mail.example.com.")])))
+        m = Module(53, "example.com", type="TXT", answers="v=spf1 -all")
+        self.assertTrue(m.check_response(**response(answers=[record("TXT", '"v=spf1 " "-all"')])))
+
+    def test_check_response_ttl(self):
+        m = Module(53, "example.com", min_ttl=60, max_ttl=3600)
+        with self.assertRaises(ResponseCheckError):
+            m.check_response(**response(answers=[record("A", "192.0.2.1", ttl=30)]))
+        with self.assertRaises(ResponseCheckError):
+            m.check_response(**response(answers=[record("A", "192.0.2.1", ttl=86400)]))
+
+    def test_check_response_authoritative(self):
+        m = Module(53, "example.com", authoritative=True)
+        with self.assertRaises(ResponseCheckError):
+            m.check_response(**response(authoritative=False))
@@ -1,8 +1,14 @@
 import unittest
 
+from dirac import ResponseCheckError
 from dirac.dns_udp import Module
 
 
+def response(rcode, answers=None):
+    return {"rcode": rcode, "authoritative": False, "truncated": False, "recursion_available": True,
+            "answers": answers or [], "authority": []}
+
+
 class UnitTests(unittest.TestCase):
     def test_check_args(self):
         res = Module.check_args(53)
@@ -12,16 +18,30 @@ class UnitTests(unittest.TestCase):
     def test_init(self):
         Module(53)
 
+    def test_challenge(self):
+        m = Module(53)
+        self.assertEqual(m.protocol(), "dns/udp")
+        self.assertEqual(m.challenge(), {"name": ".", "type": "NS", "recursion_desired": True})
+
     def test_check_response(self):
         m = Module(53)
-        res = m.check_response(None)
+        res = m.check_response(**response("REFUSED"))
         self.assertTrue(res)
 
+    def test_check_response_with_name(self):
+        m = Module(53, "example.com", answers="192.0.2.1", transport="tcp")
+        self.assertEqual(m.protocol(), "dns/udp")
+        with self.assertRaises(ResponseCheckError):
+            m.check_response(**response("SERVFAIL"))
+        self.assertTrue(m.check_response(**response("NOERROR", [
+            {"name": "example.com.", "type": "A", "ttl": 300, "data": "192.0.2.1"}])))
+
 
 class ExampleTests(unittest.TestCase):
     # def test__software__version__os__version
 
     def test__dnsmasq__2_68__ubuntu__14_04(self):
         m = Module(53)
-        res = m.check_response(None)
+        res = m.check_response(**response("NOERROR", [
+            {"name": ".", "type": "NS", "ttl": 518400, "data": "a.root-servers.net."}]))
         self.assertTrue(res)
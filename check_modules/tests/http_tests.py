@@ -1,5 +1,6 @@
 import unittest
 
+from dirac import InvalidArgumentError, ResponseCheckError
 from dirac.http import Module
 
 
@@ -29,6 +30,71 @@ Content-Type: text/html
                                "")
         self.assertTrue(res)
 
+    def test_check_args_verbs(self):
+        for verb in ["HEAD", "post", "PUT", "DELETE", "OPTIONS"]:
+            self.assertTrue(Module.check_args(80, verb, "/", 200))
+
+    def test_check_args_invalid_verb(self):
+        with self.assertRaises(InvalidArgumentError):
+            Module.check_args(80, "TRACE", "/", 200)
+
+    def test_check_args_redirect_chain_requires_follow_redirects(self):
+        with self.assertRaises(InvalidArgumentError):
+            Module.check_args(80, "GET", "/", 200, redirect_chain=["/login"])
+
+    def test_check_args_invalid_body_regex(self):
+        with self.assertRaises(InvalidArgumentError):
+            Module.check_args(80, "GET", "/", 200, body_regex="(")
+
+    def test_challenge(self):
+        m = Module(80, "post", "/api", 201, headers={"Content-Type": "application/json"}, body="{}")
+        self.assertEqual(m.challenge(), {
+            "verb": "POST",
+            "uri": "/api",
+            "headers": {"Content-Type": "application/json"},
+            "body": "{}",
+            "follow_redirects": False,
+        })
+
+    def test_check_response_code_list(self):
+        m = Module(80, "GET", "/", [200, 204])
+        self.assertTrue(m.check_response(204, {}, ""))
+        with self.assertRaises(ResponseCheckError):
+            m.check_response(404, {}, "")
+
+    def test_check_response_location(self):
+        m = Module(80, "GET", "/", 301, location="https://server.local/")
+        self.assertTrue(m.check_response(301, {"Location": "https://server.local/"}, ""))
+        with self.assertRaises(ResponseCheckError):
+            m.check_response(301, {"Location": "http://server.local/"}, "")
+
+    def test_check_response_redirect_chain(self):
+        m = Module(80, "GET", "/", 200, follow_redirects=True, redirect_chain=["/login", "/login/"])
+        redirects = [{"response_code": 302, "location": "/login"}, {"response_code": 301, "location": "/login/"}]
+        self.assertTrue(m.check_response(200, {}, "", redirects))
+        with self.assertRaises(ResponseCheckError):
+            m.check_response(200, {}, "", redirects[:1])
+
+    def test_check_response_expected_headers(self):
+        m = Module(80, "GET", "/", 200, expected_headers={"content-type": "application/json"})
+        self.assertTrue(m.check_response(200, {"Content-Type": "application/json"}, ""))
+        with self.assertRaises(ResponseCheckError):
+            m.check_response(200, {"Content-Type": "text/html"}, "")
+
+    def test_check_response_body(self):
+        m = Module(80, "GET", "/health", 200, body_regex='"status":\\s*"ok"', body_contains="database")
+        self.assertTrue(m.check_response(200, {}, '{"status": "ok", "database": "up"}'))
+        with self.assertRaises(ResponseCheckError):
+            m.check_response(200, {}, '{"status": "failed", "database": "down"}')
+        with self.assertRaises(ResponseCheckError):
+            m.check_response(200, {}, '{"status": "ok"}')
+
+    def test_check_response_truncated_body(self):
+        m = Module(80, "GET", "/", 200, body_contains="database")
+        self.assertTrue(m.check_response(200, {}, "database", [], None, body_truncated=True))
+        with self.assertRaisesRegex(ResponseCheckError, r"Body \(truncated after 6 bytes\) does not contain"):
+            m.check_response(200, {}, "status", [], None, body_truncated=True)
+
 
 class ExampleTests(unittest.TestCase):
     # def test__software__version__os__version
@@ -0,0 +1,68 @@
+import unittest
+
+from dirac import InvalidArgumentError, ResponseCheckError
+from dirac.https import Module
+
+
+def tls(version="TLSv1.2", days_valid=90, sans=None):
+    return {
+        "version": version,
+        "cipher": "ECDHE-RSA-AES128-GCM-SHA256",
+        "chain": [{
+            "subject": "CN=server.local",
+            "issuer": "CN=Example CA",
+            "sans": sans if sans is not None else ["server.local", "www.server.local"],
+            "not_before": "Jan  1 00:00:00 2026 GMT",
+            "not_after": "Apr  1 00:00:00 2026 GMT",
+            "days_valid": days_valid,
+            "key_type": "RSA 2048",
+        }],
+        "verify_error": None,
+    }
+
+
+class UnitTests(unittest.TestCase):
+    def test_check_args(self):
+        res = Module.check_args(port=443, verb="GET", uri="/", response_code=200, min_days_valid=14,
+                                expected_san=["server.local"], min_tls_version="TLSv1.2")
+        self.assertTrue(res)
+
+    def test_check_args_invalid_tls_version(self):
+        with self.assertRaises(InvalidArgumentError):
+            Module.check_args(port=443, verb="GET", uri="/", response_code=200, min_tls_version="TLSv2")
+
+    def test_check_args_checks_http_args(self):
+        with self.assertRaises(InvalidArgumentError):
+            Module.check_args(port=443, verb="TRACE", uri="/", response_code=200)
+
+    def test_check_response_without_tls_assertions(self):
+        m = Module(port=443, verb="GET", uri="/", response_code=200)
+        self.assertTrue(m.check_response(200, {}, "", [], tls()))
+
+    def test_check_response_passes_body_truncated(self):
+        m = Module(port=443, verb="GET", uri="/", response_code=200, body_contains="database")
+        with self.assertRaisesRegex(ResponseCheckError, r"truncated after 6 bytes"):
+            m.check_response(200, {}, "status", [], tls(), body_truncated=True)
+
+    def test_check_response_min_days_valid(self):
+        m = Module(port=443, verb="GET", uri="/", response_code=200, min_days_valid=14)
+        self.assertTrue(m.check_response(200, {}, "", [], tls(days_valid=14)))
+        with self.assertRaises(ResponseCheckError):
+            m.check_response(200, {}, "", [], tls(days_valid=6))
+
+    def test_check_response_expected_san(self):
+        m = Module(port=443, verb="GET", uri="/", response_code=200, expected_san="www.server.local")
+        self.assertTrue(m.check_response(200, {}, "", [], tls()))
+        with self.assertRaises(ResponseCheckError):
+            m.check_response(200, {}, "", [], tls(sans=["server.local"]))
+
+    def test_check_response_min_tls_version(self):
+        m = Module(port=443, verb="GET", uri="/", response_code=200, min_tls_version="TLSv1.2")
+        self.assertTrue(m.check_response(200, {}, "", [], tls(version="TLSv1.3")))
+        with self.assertRaises(ResponseCheckError):
+            m.check_response(200, {}, "", [], tls(version="TLSv1"))
+
+    def test_check_response_missing_tls(self):
+        m = Module(port=443, verb="GET", uri="/", response_code=200, min_days_valid=14)
+        with self.assertRaises(ResponseCheckError):
+            m.check_response(200, {}, "", [], None)
@@ -1,5 +1,6 @@
 import unittest
 
+from dirac import InvalidArgumentError, ResponseCheckError
 from dirac.smtp import Module
 
 
@@ -8,6 +9,10 @@ class UnitTests(unittest.TestCase):
         res = Module.check_args(25, ".*Postfix.*", False, 220)
         self.assertTrue(res)
 
+    def test_check_args_proxy_not_a_bool(self):
+        with self.assertRaises(InvalidArgumentError):
+            Module.check_args(25, ".*Postfix.*", "False", 220)
+
     # noinspection PyMethodMayBeStatic
     def test_init(self):
         Module(25, ".*Postfix.*", False, 220)
@@ -17,6 +22,34 @@ class UnitTests(unittest.TestCase):
         res = m.check_response("220-smtp.server.local ESMTP Postfix (Ubuntu)")
         self.assertTrue(res)
 
+    def test_check_response_multiline_greeting(self):
+        m = Module(25, ".*Postfix.*", False, 220)
+        res = m.check_response("220-smtp.server.local ESMTP Postfix (Ubuntu)\r\n220 ready\r\n221 2.0.0 Bye\r\n")
+        self.assertTrue(res)
+
+    def test_read_strategy(self):
+        self.assertEqual(Module(25, ".*Postfix.*", False, 220).read_strategy(), {"until": "idle"})
+
+    def test_check_args_starttls(self):
+        self.assertTrue(Module.check_args(25, ".*Postfix.*", False, 220, starttls="required"))
+        with self.assertRaises(InvalidArgumentError):
+            Module.check_args(25, ".*Postfix.*", False, 220, starttls="optional")
+        with self.assertRaises(InvalidArgumentError):
+            Module.check_args(25, ".*Postfix.*", True, 220, starttls="required")
+
+    def test_protocol(self):
+        self.assertEqual(Module(25, ".*Postfix.*", False, 220).protocol(), "text/tcp")
+        self.assertEqual(Module(25, ".*Postfix.*", False, 220, starttls="required").protocol(), "starttls/smtp")
+
+    def test_check_response_starttls(self):
+        m = Module(25, ".*Postfix.*", False, 220, starttls="required")
+        tls = {"version": "TLSv1.2", "cipher": "ECDHE-RSA-AES256-GCM-SHA384", "chain": [], "verify_error": None}
+        res = m.check_response(response="221 2.0.0 Bye\r\n", tls=tls,
+                               banner="220-smtp.server.local ESMTP Postfix (Ubuntu)\r\n220 ready\r\n")
+        self.assertTrue(res)
+        with self.assertRaises(ResponseCheckError):
+            m.check_response("220-smtp.server.local ESMTP Postfix (Ubuntu)")
+
 
 class ExampleTests(unittest.TestCase):
     # def test__software__version__os__version
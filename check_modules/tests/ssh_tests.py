@@ -17,6 +17,21 @@ class UnitTests(unittest.TestCase):
         res = m.check_response("SSH-2.0-OpenSSH_6.6.1p1 Ubuntu-2ubuntu2")
         self.assertTrue(res)
 
+    def test_check_response_float_version(self):
+        m = Module(22, 2.0, "OpenSSH.*")
+        res = m.check_response("SSH-2.0-OpenSSH_6.6.1p1 Ubuntu-2ubuntu2")
+        self.assertTrue(res)
+
+    def test_read_strategy(self):
+        m = Module(22, "2.0", "OpenSSH.*")
+        self.assertEqual(m.read_strategy(), {"until": "delimiter", "delimiter": "\n"})
+
+    def test_check_response_over_tls(self):
+        m = Module(22, "2.0", "OpenSSH.*")
+        res = m.check_response(response="SSH-2.0-OpenSSH_6.6.1p1 Ubuntu-2ubuntu2",
+                               tls={"version": "TLSv1.2", "cipher": "AES256-SHA", "chain": [], "verify_error": None})
+        self.assertTrue(res)
+
 
 class ExampleTests(unittest.TestCase):
     # def test__software__version__os__version
@@ -0,0 +1,474 @@
+use std::cmp;
+use std::collections::{HashMap, HashSet};
+use std::fs::File;
+use std::io::prelude::*;
+use std::path::Path;
+
+use yaml_rust::Yaml;
+
+use super::expand::expand_hosts;
+use super::marked::{self, Node};
+use super::{parse_param_value, HostVars, ParamValue};
+
+/// The groups and host variables of an Ansible inventory.
+#[derive(Debug, Default)]
+pub struct AnsibleInventory {
+    /// The name, hosts and children of every group, in order of appearance.
+    pub groups: Vec<(String, Vec<String>, Vec<String>)>,
+    /// The variables of every host, including those inherited from its groups.
+    pub host_vars: HashMap<String, HostVars>,
+}
+
+/// Reads an Ansible inventory in YAML format if `path` ends with `.yml`, `.yaml` or `.json`, in
+/// INI format if it has another extension and in either format otherwise.
+pub fn read(path: &Path, max_hosts: usize) -> Result<AnsibleInventory, String> {
+    let mut content = String::new();
+    if let Err(err) = File::open(path).and_then(|mut f| f.read_to_string(&mut content)) {
+        return Err(format!("could not read file: {}", err));
+    }
+
+    let builder = match path.extension().and_then(|ext| ext.to_str()) {
+        Some("yml") | Some("yaml") | Some("json") => try!(parse_yaml(&content, max_hosts)),
+        Some(_) => try!(parse_ini(&content, max_hosts)),
+        None => {
+            match marked::load_from_str(&content) {
+                Ok(ref docs) if docs.first().map_or(false, |doc| doc.as_hash().is_some()) => {
+                    try!(parse_yaml(&content, max_hosts))
+                }
+                _ => try!(parse_ini(&content, max_hosts)),
+            }
+        }
+    };
+
+    builder.finish()
+}
+
+/// Parses the JSON a dynamic inventory script prints for `--list`.
+pub fn parse_list(content: &str, max_hosts: usize) -> Result<AnsibleInventory, String> {
+    try!(parse_yaml(content, max_hosts)).finish()
+}
+
+#[derive(Default)]
+struct Group {
+    hosts: Vec<String>,
+    children: Vec<String>,
+    vars: HostVars,
+}
+
+#[derive(Default)]
+struct Builder {
+    names: Vec<String>,
+    groups: HashMap<String, Group>,
+    hosts: Vec<String>,
+    host_vars: HashMap<String, HostVars>,
+}
+
+impl Builder {
+    fn group(&mut self, name: &str) -> &mut Group {
+        if !self.groups.contains_key(name) {
+            self.names.push(name.to_string());
+        }
+        self.groups.entry(name.to_string()).or_insert_with(Group::default)
+    }
+
+    fn add_hosts(&mut self,
+                 group: &str,
+                 pattern: &str,
+                 vars: HostVars,
+                 max_hosts: usize)
+                 -> Result<(), String> {
+        for host in try!(expand_hosts(pattern, max_hosts)) {
+            {
+                let group = self.group(group);
+                if !group.hosts.contains(&host) {
+                    group.hosts.push(host.clone());
+                }
+            }
+            if !self.hosts.contains(&host) {
+                self.hosts.push(host.clone());
+            }
+            self.host_vars.entry(host).or_insert_with(HostVars::new).extend(vars.clone());
+        }
+
+        Ok(())
+    }
+
+    fn add_child(&mut self, group: &str, child: &str) {
+        self.group(child);
+        let group = self.group(group);
+        if !group.children.iter().any(|c| c == child) {
+            group.children.push(child.to_string());
+        }
+    }
+
+    /// Adds the implicit groups `all` and `ungrouped` and applies group variables to the hosts;
+    /// variables of child groups take precedence over those of their parents and host variables
+    /// over all group variables.
+    fn finish(mut self) -> Result<AnsibleInventory, String> {
+        let ungrouped: Vec<String> = {
+            let grouped = |host: &String| {
+                self.groups.iter().any(|(name, group)| name != "all" && group.hosts.contains(host))
+            };
+            self.hosts.iter().filter(|host| !grouped(host)).cloned().collect()
+        };
+        self.group("ungrouped").hosts.extend(ungrouped);
+        self.group("all").hosts = self.hosts.clone();
+
+        let mut depths = HashMap::new();
+        let mut members = HashMap::new();
+        for name in &self.names {
+            try!(self.depth(name, &mut depths, &mut Vec::new()));
+            try!(self.members(name, &mut members, &mut Vec::new()));
+        }
+        let mut by_depth: Vec<(usize, usize, &String)> =
+            self.names.iter().enumerate().map(|(index, name)| (depths[name], index, name)).collect();
+        by_depth.sort();
+
+        let mut host_vars = HashMap::new();
+        for host in &self.hosts {
+            let mut vars = HostVars::new();
+            for &(_, _, name) in &by_depth {
+                if members[name].contains(host) {
+                    vars.extend(self.groups[name].vars.clone());
+                }
+            }
+            vars.extend(self.host_vars[host].clone());
+            host_vars.insert(host.clone(), vars);
+        }
+
+        let groups = self.names
+                         .iter()
+                         .map(|name| {
+                             let group = &self.groups[name];
+                             (name.clone(), group.hosts.clone(), group.children.clone())
+                         })
+                         .collect();
+        Ok(AnsibleInventory {
+            groups: groups,
+            host_vars: host_vars,
+        })
+    }
+
+    /// The length of the longest path from `all` to group `name`.
+    fn depth(&self,
+             name: &str,
+             depths: &mut HashMap<String, usize>,
+             path: &mut Vec<String>)
+             -> Result<usize, String> {
+        if let Some(&depth) = depths.get(name) {
+            return Ok(depth);
+        }
+        try!(check_cycle(name, path));
+
+        path.push(name.to_string());
+        let mut depth = if name == "all" { 0 } else { 1 };
+        for (parent, group) in &self.groups {
+            if group.children.iter().any(|c| c == name) {
+                depth = cmp::max(depth, try!(self.depth(parent, depths, path)) + 1);
+            }
+        }
+        path.pop();
+
+        depths.insert(name.to_string(), depth);
+        Ok(depth)
+    }
+
+    /// The hosts of group `name` and its children.
+    fn members(&self,
+               name: &str,
+               members: &mut HashMap<String, HashSet<String>>,
+               path: &mut Vec<String>)
+               -> Result<(), String> {
+        if members.contains_key(name) {
+            return Ok(());
+        }
+        try!(check_cycle(name, path));
+
+        path.push(name.to_string());
+        let group = &self.groups[name];
+        let mut hosts: HashSet<String> = group.hosts.iter().cloned().collect();
+        for child in &group.children {
+            try!(self.members(child, members, path));
+            hosts.extend(members[child].iter().cloned());
+        }
+        path.pop();
+
+        members.insert(name.to_string(), hosts);
+        Ok(())
+    }
+}
+
+fn check_cycle(name: &str, path: &[String]) -> Result<(), String> {
+    if path.iter().any(|n| n == name) {
+        return Err(format!("groups include each other: {} -> {}", path.join(" -> "), name));
+    }
+
+    Ok(())
+}
+
+fn parse_ini(content: &str, max_hosts: usize) -> Result<Builder, String> {
+    let mut builder = Builder::default();
+    let mut group = "ungrouped".to_string();
+    let mut section = "hosts";
+    for (index, line) in content.lines().enumerate() {
+        let at_line = |reason: String| format!("line {}: {}", index + 1, reason);
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') || line.starts_with(';') {
+            continue;
+        }
+
+        if line.starts_with('[') && line.ends_with(']') {
+            let header = &line[1..line.len() - 1];
+            let (name, kind) = match header.rfind(':') {
+                Some(i) if ["children", "vars", "hosts"].contains(&&header[i + 1..]) => {
+                    (&header[..i], &header[i + 1..])
+                }
+                _ => (header, "hosts"),
+            };
+            group = name.to_string();
+            section = match kind {
+                "children" => "children",
+                "vars" => "vars",
+                _ => "hosts",
+            };
+            builder.group(&group);
+            continue;
+        }
+
+        let args = try!(split_args(line).map_err(&at_line));
+        match section {
+            "children" => builder.add_child(&group, &args[0]),
+            "vars" => {
+                let (key, value) = try!(parse_assignment(line).map_err(&at_line));
+                builder.group(&group).vars.insert(key, value);
+            }
+            _ => {
+                let mut vars = HostVars::new();
+                for arg in &args[1..] {
+                    let (key, value) = try!(parse_assignment(arg).map_err(&at_line));
+                    vars.insert(key, value);
+                }
+                let mut pattern = &args[0][..];
+                if let Some(i) = pattern.rfind(':') {
+                    if pattern[..i].find(':').is_none() {
+                        if let Ok(port) = pattern[i + 1..].parse::<i64>() {
+                            vars.entry("ansible_port".to_string()).or_insert(ParamValue::Int(port));
+                            pattern = &pattern[..i];
+                        }
+                    }
+                }
+                try!(builder.add_hosts(&group, pattern, vars, max_hosts).map_err(&at_line));
+            }
+        }
+    }
+
+    Ok(builder)
+}
+
+/// Splits a line at whitespace outside of quotes and drops a trailing comment.
+fn split_args(line: &str) -> Result<Vec<String>, String> {
+    let mut args = Vec::new();
+    let mut arg = String::new();
+    let mut quote = None;
+    for c in line.chars() {
+        match quote {
+            Some(q) if c == q => quote = None,
+            Some(_) => {}
+            None if c == '"' || c == '\'' => quote = Some(c),
+            None if c == '#' && arg.is_empty() => break,
+            None if c.is_whitespace() => {
+                if !arg.is_empty() {
+                    args.push(arg);
+                    arg = String::new();
+                }
+                continue;
+            }
+            None => {}
+        }
+        arg.push(c);
+    }
+    if quote.is_some() {
+        return Err(format!("unclosed quote in '{}'", line));
+    }
+    if !arg.is_empty() {
+        args.push(arg);
+    }
+
+    Ok(args)
+}
+
+/// Parses `key=value`; unquoted numbers and booleans keep their type.
+fn parse_assignment(arg: &str) -> Result<(String, ParamValue), String> {
+    let i = match arg.find('=') {
+        Some(i) => i,
+        None => return Err(format!("expected 'key=value', found '{}'", arg)),
+    };
+    let key = arg[..i].trim().to_string();
+    let raw = arg[i + 1..].trim();
+
+    let value = if raw.len() >= 2 && (raw.starts_with('"') && raw.ends_with('"') ||
+                                      raw.starts_with('\'') && raw.ends_with('\'')) {
+        ParamValue::String(raw[1..raw.len() - 1].to_string())
+    } else if let Ok(i) = raw.parse::<i64>() {
+        ParamValue::Int(i)
+    } else if let Ok(x) = raw.parse::<f64>() {
+        ParamValue::Float(x)
+    } else {
+        match raw {
+            "True" | "true" => ParamValue::Bool(true),
+            "False" | "false" => ParamValue::Bool(false),
+            _ => ParamValue::String(raw.to_string()),
+        }
+    };
+
+    Ok((key, value))
+}
+
+/// Reads an inventory in Ansible's YAML format or, as printed by dynamic inventory scripts for
+/// `--list`, with lists of hosts and children and host variables in `_meta.hostvars`.
+fn parse_yaml(content: &str, max_hosts: usize) -> Result<Builder, String> {
+    let docs = try!(marked::load_from_str(content).map_err(|err| format!("invalid YAML: {}", err)));
+    let groups = match docs.first().and_then(|doc| doc.as_hash()) {
+        Some(groups) => groups,
+        None => return Err("expected a hash of groups".to_string()),
+    };
+
+    let mut builder = Builder::default();
+    let mut meta = None;
+    for &(ref name, ref group) in groups {
+        let name = try!(yaml_name(name));
+        if name == "_meta" {
+            meta = Some(group);
+            continue;
+        }
+        try!(parse_yaml_group(&mut builder, &name, group, max_hosts));
+    }
+    if let Some(hostvars) = meta.and_then(|meta| meta.get("hostvars")) {
+        for &(ref host, ref vars) in hostvars.as_hash().map_or(&[][..], |hosts| &hosts[..]) {
+            let host = try!(yaml_name(host));
+            let vars = try!(yaml_vars(vars));
+            builder.host_vars.entry(host).or_insert_with(HostVars::new).extend(vars);
+        }
+    }
+
+    Ok(builder)
+}
+
+fn parse_yaml_group(builder: &mut Builder,
+                    name: &str,
+                    node: &Node,
+                    max_hosts: usize)
+                    -> Result<(), String> {
+    builder.group(name);
+    if is_null(node) {
+        return Ok(());
+    }
+    if let Some(hosts) = node.as_vec() {
+        for pattern in hosts {
+            let pattern = try!(yaml_name(pattern));
+            try!(builder.add_hosts(name, &pattern, HostVars::new(), max_hosts).map_err(|err| at(node, err)));
+        }
+        return Ok(());
+    }
+    let elems = match node.as_hash() {
+        Some(elems) => elems,
+        None => {
+            return Err(at(node,
+                          format!("group '{}' must be a hash or a list of hosts, found {}",
+                                  name,
+                                  node.kind())))
+        }
+    };
+
+    for &(ref key, ref value) in elems {
+        let key_str = key.as_str().unwrap_or("?");
+        match (key_str, value.as_vec()) {
+            ("hosts", Some(hosts)) => {
+                for pattern in hosts {
+                    let pattern = try!(yaml_name(pattern));
+                    try!(builder.add_hosts(name, &pattern, HostVars::new(), max_hosts)
+                                .map_err(|err| at(value, err)));
+                }
+                continue;
+            }
+            ("children", Some(children)) => {
+                for child in children {
+                    builder.add_child(name, &try!(yaml_name(child)));
+                }
+                continue;
+            }
+            _ => {}
+        }
+        let entries = match value.as_hash() {
+            Some(entries) => &entries[..],
+            None if is_null(value) => &[],
+            None => {
+                return Err(at(value,
+                              format!("'{}' of group '{}' must be a hash, found {}",
+                                      key_str,
+                                      name,
+                                      value.kind())))
+            }
+        };
+        match key_str {
+            "hosts" => {
+                for &(ref pattern, ref vars) in entries {
+                    let pattern = try!(yaml_name(pattern));
+                    let vars = try!(yaml_vars(vars));
+                    try!(builder.add_hosts(name, &pattern, vars, max_hosts)
+                                .map_err(|err| at(value, err)));
+                }
+            }
+            "children" => {
+                for &(ref child, ref group) in entries {
+                    let child = try!(yaml_name(child));
+                    builder.add_child(name, &child);
+                    try!(parse_yaml_group(builder, &child, group, max_hosts));
+                }
+            }
+            "vars" => {
+                let vars = try!(yaml_vars(value));
+                builder.group(name).vars.extend(vars);
+            }
+            _ => {
+                return Err(at(key,
+                              format!("unknown key '{}' in group '{}'; expected 'hosts', 'children' or \
+                                       'vars'",
+                                      key_str,
+                                      name)))
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn yaml_name(node: &Node) -> Result<String, String> {
+    match node.to_yaml() {
+        Yaml::String(s) | Yaml::Real(s) => Ok(s),
+        Yaml::Integer(i) => Ok(i.to_string()),
+        _ => Err(at(node, format!("expected a name, found {}", node.kind()))),
+    }
+}
+
+fn yaml_vars(node: &Node) -> Result<HostVars, String> {
+    if is_null(node) {
+        return Ok(HostVars::new());
+    }
+    match try!(parse_param_value(node).map_err(|err| at(node, err.reason))) {
+        ParamValue::Map(vars) => Ok(vars),
+        _ => Err(at(node, format!("variables must be a hash, found {}", node.kind()))),
+    }
+}
+
+fn is_null(node: &Node) -> bool {
+    if let marked::Value::Scalar(Yaml::Null) = node.value {
+        true
+    } else {
+        false
+    }
+}
+
+fn at(node: &Node, reason: String) -> String {
+    format!("line {}: {}", node.line, reason)
+}
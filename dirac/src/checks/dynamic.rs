@@ -0,0 +1,168 @@
+use libc;
+use std::collections::hash_map::DefaultHasher;
+use std::env;
+use std::fs::{self, DirBuilder, File, OpenOptions};
+use std::hash::{Hash, Hasher};
+use std::io;
+use std::io::prelude::*;
+use std::os::unix::fs::{DirBuilderExt, MetadataExt, OpenOptionsExt};
+use std::path::{Path, PathBuf};
+use std::process::{self, Command};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use super::ansible::{self, AnsibleInventory};
+
+/// An executable printing an inventory following Ansible's dynamic inventory contract, i.e. the
+/// groups and host variables as JSON when called with `--list`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct InventoryCommand {
+    pub program: String,
+    pub args: Vec<String>,
+    /// How long the output may be reused by later runs, in milliseconds; by default the command is
+    /// run every time.
+    pub cache_ms: Option<u64>,
+}
+
+impl InventoryCommand {
+    /// The command line as shown in errors, e.g. `inventories/openstack.py --cloud prod --list`.
+    pub fn command_line(&self) -> String {
+        let mut words = vec![self.program.clone()];
+        words.extend(self.args.iter().cloned());
+        words.push("--list".to_string());
+        words.join(" ")
+    }
+
+    /// Runs the command, or reads its cached output, and parses the inventory; a relative program
+    /// path containing a `/` is relative to `dir`.
+    pub fn read(&self, dir: Option<&Path>, max_hosts: usize) -> Result<AnsibleInventory, String> {
+        let program = match dir {
+            Some(dir) if self.program.contains('/') => dir.join(&self.program),
+            _ => PathBuf::from(&self.program),
+        };
+        let cache_file = self.cache_ms.and_then(|_| cache_file(&program, &self.args));
+
+        let cached = match (self.cache_ms, cache_file.as_ref()) {
+            (Some(cache_ms), Some(cache_file)) => read_cache(cache_file, Duration::from_millis(cache_ms)),
+            _ => None,
+        };
+        let output = match cached {
+            Some(output) => {
+                debug!("Using cached output of inventory command '{}'", self.command_line());
+                output
+            }
+            None => {
+                let output = try!(run(&program, &self.args));
+                if let Some(ref cache_file) = cache_file {
+                    if let Err(err) = write_cache(cache_file, &output) {
+                        warn!("Could not cache inventory in '{}': {}", cache_file.display(), err);
+                    }
+                }
+                output
+            }
+        };
+
+        ansible::parse_list(&output, max_hosts).map_err(|err| format!("invalid output: {}", err))
+    }
+}
+
+fn run(program: &Path, args: &[String]) -> Result<String, String> {
+    let output = try!(Command::new(program)
+                          .args(args)
+                          .arg("--list")
+                          .output()
+                          .map_err(|err| format!("could not be run: {}", err)));
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(format!("failed with {}: {}", output.status, stderr.trim()));
+    }
+
+    String::from_utf8(output.stdout).map_err(|_| "output is not valid UTF-8".to_string())
+}
+
+/// The per-user cache directory, `$XDG_CACHE_HOME/dirac` or `~/.cache/dirac`.
+fn cache_dir() -> Option<PathBuf> {
+    let base = match env::var_os("XDG_CACHE_HOME").map(PathBuf::from) {
+        // Relative paths are to be ignored according to the XDG Base Directory Specification.
+        Some(ref dir) if dir.is_absolute() => dir.clone(),
+        _ => {
+            match env::var_os("HOME").map(PathBuf::from) {
+                Some(ref home) if home.is_absolute() => home.join(".cache"),
+                _ => return None,
+            }
+        }
+    };
+
+    Some(base.join("dirac"))
+}
+
+/// The file caching the output of `program` with `args`, or `None` without a cache directory.
+///
+/// A program given as a path is canonicalized, so different paths to the same program share the
+/// cache; a bare name is looked up in `PATH` and hashed as it is.
+fn cache_file(program: &Path, args: &[String]) -> Option<PathBuf> {
+    let dir = match cache_dir() {
+        Some(dir) => dir,
+        None => {
+            warn!("Not caching inventory: neither XDG_CACHE_HOME nor HOME is set");
+            return None;
+        }
+    };
+    let program = if program.components().count() > 1 {
+        fs::canonicalize(program).unwrap_or_else(|_| program.to_path_buf())
+    } else {
+        program.to_path_buf()
+    };
+
+    let mut hasher = DefaultHasher::new();
+    program.hash(&mut hasher);
+    args.hash(&mut hasher);
+    Some(dir.join(format!("inventory-{:016x}.json", hasher.finish())))
+}
+
+/// Writes `output` to a new file only the current user may read and renames it to `cache_file`,
+/// so that readers never see a partially written file.
+fn write_cache(cache_file: &Path, output: &str) -> io::Result<()> {
+    let dir = cache_file.parent().unwrap_or_else(|| Path::new("."));
+    try!(DirBuilder::new().recursive(true).mode(0o700).create(dir));
+
+    let nanos = SystemTime::now().duration_since(UNIX_EPOCH).map(|now| now.subsec_nanos()).unwrap_or(0);
+    let temp_file = dir.join(format!(".inventory-{}-{}.tmp", process::id(), nanos));
+    let written = OpenOptions::new()
+                      .write(true)
+                      .create_new(true)
+                      .mode(0o600)
+                      .open(&temp_file)
+                      .and_then(|mut f| f.write_all(output.as_bytes()))
+                      .and_then(|_| fs::rename(&temp_file, cache_file));
+    if written.is_err() {
+        let _ = fs::remove_file(&temp_file);
+    }
+
+    written
+}
+
+/// Reads the cached output if it is owned by the current user and younger than `max_age`.
+fn read_cache(cache_file: &Path, max_age: Duration) -> Option<String> {
+    let mut file = match File::open(cache_file) {
+        Ok(file) => file,
+        Err(_) => return None,
+    };
+    let metadata = match file.metadata() {
+        Ok(metadata) => metadata,
+        Err(_) => return None,
+    };
+    if metadata.uid() != unsafe { libc::geteuid() } {
+        warn!("Ignoring cached inventory '{}' owned by another user", cache_file.display());
+        return None;
+    }
+    match metadata.modified().map(|modified| SystemTime::now().duration_since(modified)) {
+        Ok(Ok(age)) if age < max_age => {}
+        _ => return None,
+    }
+
+    let mut output = String::new();
+    match file.read_to_string(&mut output) {
+        Ok(_) => Some(output),
+        Err(_) => None,
+    }
+}
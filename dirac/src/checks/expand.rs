@@ -0,0 +1,171 @@
+use std::net::Ipv4Addr;
+
+/// Expands an inventory entry into host names.
+///
+/// `[01:10]` and `[a:f]` are replaced by every number or letter of the range, optionally with a
+/// step like `[0:20:5]`; a leading zero pads all numbers to the width of the start. `{a,b,c}` is
+/// replaced by each alternative, and an IPv4 CIDR block like `10.0.4.0/28` becomes its host
+/// addresses, i.e. without network and broadcast address for prefixes up to /30. Fails if the
+/// entry would yield more than `max_hosts` hosts.
+pub fn expand_hosts(pattern: &str, max_hosts: usize) -> Result<Vec<String>, String> {
+    if pattern.contains('/') {
+        return expand_cidr(pattern, max_hosts);
+    }
+
+    let segments = try!(segments(pattern, max_hosts));
+    let count = segments.iter()
+                        .fold(Some(1usize), |count, s| count.and_then(|c| c.checked_mul(s.len())));
+    match count {
+        Some(count) if count <= max_hosts => {}
+        _ => return Err(too_many(pattern, max_hosts)),
+    }
+
+    let mut hosts = vec![String::new()];
+    for segment in segments {
+        hosts = hosts.iter()
+                     .flat_map(|prefix| segment.iter().map(move |s| format!("{}{}", prefix, s)))
+                     .collect();
+    }
+
+    Ok(hosts)
+}
+
+fn too_many(pattern: &str, max_hosts: usize) -> String {
+    format!("'{}' expands to more than {} hosts; raise the setting 'max_hosts_per_pattern' if this is \
+             intended",
+            pattern,
+            max_hosts)
+}
+
+/// Splits a pattern into the alternatives for each of its parts; literal parts have one.
+fn segments(pattern: &str, max_hosts: usize) -> Result<Vec<Vec<String>>, String> {
+    let mut segments = Vec::new();
+    let mut literal = String::new();
+    let mut chars = pattern.chars();
+    while let Some(c) = chars.next() {
+        let close = match c {
+            '[' => ']',
+            '{' => '}',
+            _ => {
+                literal.push(c);
+                continue;
+            }
+        };
+        let mut inner = String::new();
+        let mut closed = false;
+        while let Some(c) = chars.next() {
+            if c == close {
+                closed = true;
+                break;
+            }
+            inner.push(c);
+        }
+        if !closed {
+            return Err(format!("'{}' has an unclosed '{}'", pattern, c));
+        }
+
+        segments.push(vec![literal.clone()]);
+        literal.clear();
+        segments.push(if c == '[' {
+            let invalid = || format!("'{}' has an invalid range '[{}]'", pattern, inner);
+            let range = try!(Range::parse(&inner).ok_or_else(invalid));
+            if range.len() > max_hosts as u64 {
+                return Err(too_many(pattern, max_hosts));
+            }
+            range.values()
+        } else {
+            inner.split(',').map(|s| s.to_string()).collect()
+        });
+    }
+    segments.push(vec![literal]);
+
+    Ok(segments)
+}
+
+/// A range `start:end` or `start:end:step` of numbers or letters.
+struct Range {
+    from: u64,
+    to: u64,
+    step: u64,
+    /// The width numbers are padded to with zeros.
+    width: usize,
+    letters: bool,
+}
+
+impl Range {
+    fn parse(inner: &str) -> Option<Range> {
+        let parts: Vec<&str> = inner.split(':').collect();
+        let (start, end) = match parts.len() {
+            2 | 3 => (parts[0], parts[1]),
+            _ => return None,
+        };
+        let step = match parts.get(2).map(|step| step.parse::<u64>()) {
+            Some(Ok(step)) if step > 0 => step,
+            Some(_) => return None,
+            None => 1,
+        };
+
+        let mut range = Range {
+            from: 0,
+            to: 0,
+            step: step,
+            width: if start.len() > 1 && start.starts_with('0') { start.len() } else { 0 },
+            letters: false,
+        };
+        if let (Ok(from), Ok(to)) = (start.parse::<u64>(), end.parse::<u64>()) {
+            range.from = from;
+            range.to = to;
+        } else {
+            match (start.as_bytes(), end.as_bytes()) {
+                (&[from], &[to]) if from.is_ascii_alphabetic() && to.is_ascii_alphabetic() => {
+                    range.from = from as u64;
+                    range.to = to as u64;
+                    range.letters = true;
+                }
+                _ => return None,
+            }
+        }
+
+        if range.from <= range.to { Some(range) } else { None }
+    }
+
+    fn len(&self) -> u64 {
+        (self.to - self.from) / self.step + 1
+    }
+
+    fn values(&self) -> Vec<String> {
+        (0..self.len())
+            .map(|i| self.from + i * self.step)
+            .map(|n| {
+                if self.letters {
+                    (n as u8 as char).to_string()
+                } else {
+                    format!("{:0width$}", n, width = self.width)
+                }
+            })
+            .collect()
+    }
+}
+
+fn expand_cidr(pattern: &str, max_hosts: usize) -> Result<Vec<String>, String> {
+    let invalid = || format!("'{}' is not an IPv4 CIDR block", pattern);
+    let mut parts = pattern.splitn(2, '/');
+    let address = try!(parts.next().unwrap().parse::<Ipv4Addr>().map_err(|_| invalid()));
+    let prefix = match parts.next().unwrap().parse::<u32>() {
+        Ok(prefix) if prefix <= 32 => prefix,
+        _ => return Err(invalid()),
+    };
+
+    let size = 1u64 << (32 - prefix);
+    let network = u64::from(u32::from(address)) & !(size - 1);
+    let (first, last) = if prefix <= 30 {
+        (network + 1, network + size - 2)
+    } else {
+        (network, network + size - 1)
+    };
+    if last + 1 - first > max_hosts as u64 {
+        return Err(too_many(pattern, max_hosts));
+    }
+
+    Ok((first..last + 1).map(|a| Ipv4Addr::from(a as u32).to_string()).collect())
+}
@@ -0,0 +1,117 @@
+use regex::{self, Regex};
+
+use super::{Check, Inventory, Property};
+
+/// What a filter pattern is matched against.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Selector {
+    Host,
+    /// The inventory groups a host belongs to.
+    Group,
+    /// The property's name.
+    Property,
+    Module,
+}
+
+/// Selects the hosts and properties of a run by glob patterns such as `node0[5-9]*`.
+///
+/// A host and property are selected if, for every selector, they match at least one of its
+/// included patterns, if there are any, and none of its excluded patterns. Patterns support `*`,
+/// `?` and character classes like `[0-9]` or `[!a]` and must match the whole name.
+#[derive(Debug, Default)]
+pub struct Filter {
+    include: Vec<(Selector, Regex)>,
+    exclude: Vec<(Selector, Regex)>,
+}
+
+impl Filter {
+    pub fn new() -> Filter {
+        Filter::default()
+    }
+
+    pub fn include(&mut self, selector: Selector, pattern: &str) -> Result<&mut Filter, String> {
+        self.include.push((selector, try!(glob(pattern))));
+        Ok(self)
+    }
+
+    pub fn exclude(&mut self, selector: Selector, pattern: &str) -> Result<&mut Filter, String> {
+        self.exclude.push((selector, try!(glob(pattern))));
+        Ok(self)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.include.is_empty() && self.exclude.is_empty()
+    }
+
+    /// Whether `property` of `check` is to be checked against `host`.
+    pub fn selects(&self,
+                   inventory: &Inventory,
+                   check: &Check,
+                   property: &Property,
+                   host: &str)
+                   -> bool {
+        let member_of = |group: &str| {
+            inventory.get(group).map_or(false, |hosts| hosts.iter().any(|h| h == host))
+        };
+        let matches = |selector: Selector, regex: &Regex| {
+            match selector {
+                Selector::Host => regex.is_match(host),
+                Selector::Group => {
+                    regex.is_match(&check.inventory_name) ||
+                    inventory.keys().any(|group| regex.is_match(group) && member_of(group))
+                }
+                Selector::Property => regex.is_match(&property.name),
+                Selector::Module => regex.is_match(&property.module),
+            }
+        };
+
+        for &selector in &[Selector::Host, Selector::Group, Selector::Property, Selector::Module] {
+            let mut included = self.include.iter().filter(|&&(s, _)| s == selector).peekable();
+            if included.peek().is_some() && !included.any(|&(_, ref regex)| matches(selector, regex)) {
+                return false;
+            }
+        }
+
+        !self.exclude.iter().any(|&(selector, ref regex)| matches(selector, regex))
+    }
+}
+
+/// Translates a glob pattern into an anchored regular expression.
+fn glob(pattern: &str) -> Result<Regex, String> {
+    let mut re = String::from("^");
+    let mut chars = pattern.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '*' => re.push_str(".*"),
+            '?' => re.push('.'),
+            '[' => {
+                let mut class = String::new();
+                let mut closed = false;
+                while let Some(c) = chars.next() {
+                    match c {
+                        ']' if !class.is_empty() => {
+                            closed = true;
+                            break;
+                        }
+                        '!' if class.is_empty() => class.push('^'),
+                        '\\' | '[' | '&' | '~' => {
+                            class.push('\\');
+                            class.push(c);
+                        }
+                        _ => class.push(c),
+                    }
+                }
+                if !closed {
+                    return Err(format!("invalid pattern '{}': unclosed '['", pattern));
+                }
+                re.push('[');
+                re.push_str(&class);
+                re.push(']');
+            }
+            _ => re.push_str(&regex::quote(&c.to_string())),
+        }
+    }
+    re.push('$');
+
+    Regex::new(&re).map_err(|err| format!("invalid pattern '{}': {}", pattern, err))
+}
@@ -0,0 +1,371 @@
+use std::cmp;
+use std::collections::HashMap;
+
+use super::expand::expand_hosts;
+use super::marked::Node;
+use super::{parse_param_value, HostVars, Inventory, ParamValue, SuiteError, SuiteResult};
+
+/// An inventory group as written in the suite, before its children have been resolved.
+struct Group<'a> {
+    /// Where the group has been defined, unless in an inventory file.
+    node: Option<&'a Node>,
+    hosts: Vec<String>,
+    children: Vec<(String, Option<&'a Node>)>,
+    /// Variables of all hosts of the group, including those of its children.
+    vars: HostVars,
+    /// Variables given with single hosts or host patterns.
+    host_vars: Vec<(String, HostVars)>,
+}
+
+/// The inventory groups of all `- inventory:` entries of a suite.
+pub struct Groups<'a> {
+    groups: HashMap<String, Group<'a>>,
+    /// The maximum number of hosts a single host pattern may expand to.
+    max_hosts: usize,
+}
+
+impl<'a> Groups<'a> {
+    pub fn new(max_hosts: usize) -> Groups<'a> {
+        Groups {
+            groups: HashMap::new(),
+            max_hosts: max_hosts,
+        }
+    }
+
+    /// Adds the groups of an `- inventory:` entry; a group replaces an earlier one of the same name.
+    pub fn parse(&mut self, inventory_yaml: &'a Node) -> SuiteResult<()> {
+        let groups = match inventory_yaml.as_hash() {
+            Some(groups) => groups,
+            None => {
+                return Err(SuiteError::at(inventory_yaml,
+                                          format!("inventory must be a hash of host groups, found {}",
+                                                  inventory_yaml.kind())))
+            }
+        };
+
+        for &(ref hosts_name_yaml, ref hosts_yaml) in groups {
+            let hosts_name = match hosts_name_yaml.as_str() {
+                Some(name) => name.to_string(),
+                None => {
+                    return Err(SuiteError::at(hosts_name_yaml,
+                                              format!("inventory group name must be a string, found {}",
+                                                      hosts_name_yaml.kind())))
+                }
+            };
+            let group = try!(parse_group(&hosts_name, hosts_yaml, self.max_hosts));
+            debug!("- - Inventory name: '{:?}'", hosts_name);
+            debug!("- - Inventory hosts: '{:?}'", group.hosts);
+            self.groups.insert(hosts_name, group);
+        }
+
+        Ok(())
+    }
+
+    /// Adds a group read from an inventory file; it replaces an earlier one of the same name.
+    pub fn add(&mut self, name: String, hosts: Vec<String>, children: Vec<String>) {
+        let group = Group {
+            node: None,
+            hosts: hosts,
+            children: children.into_iter().map(|child| (child, None)).collect(),
+            vars: HostVars::new(),
+            host_vars: Vec::new(),
+        };
+        self.groups.insert(name, group);
+    }
+
+    /// Resolves the children of all groups, so every group lists its own hosts followed by those of
+    /// its children, each host once.
+    pub fn resolve(&self) -> SuiteResult<Inventory> {
+        let mut inventory = Inventory::new();
+        let mut names: Vec<&String> = self.groups.keys().collect();
+        names.sort();
+        for name in names {
+            try!(self.resolve_group(name, &mut Vec::new(), &mut inventory));
+        }
+
+        Ok(inventory)
+    }
+
+    fn resolve_group(&self,
+                     name: &str,
+                     path: &mut Vec<String>,
+                     inventory: &mut Inventory)
+                     -> SuiteResult<()> {
+        if inventory.contains_key(name) {
+            return Ok(());
+        }
+        let group = &self.groups[name];
+        if path.iter().any(|n| n == name) {
+            path.push(name.to_string());
+            return Err(at(group.node,
+                          format!("inventory groups include each other: {}", path.join(" -> "))));
+        }
+
+        path.push(name.to_string());
+        let mut hosts = group.hosts.clone();
+        for &(ref child, node) in &group.children {
+            if !self.groups.contains_key(child) {
+                return Err(at(node,
+                              format!("child '{}' of inventory group '{}' does not name an \
+                                       inventory group",
+                                      child,
+                                      name)));
+            }
+            try!(self.resolve_group(child, path, inventory));
+            for host in &inventory[child] {
+                if !hosts.contains(host) {
+                    hosts.push(host.clone());
+                }
+            }
+        }
+        path.pop();
+
+        inventory.insert(name.to_string(), hosts);
+        Ok(())
+    }
+
+    /// Returns the variables of the hosts of `inventory`, as resolved from these groups.
+    ///
+    /// Variables of a group apply to all its hosts, with those of child groups taking precedence
+    /// over those of their parents; variables given with a host take precedence over all of them.
+    pub fn host_vars(&self, inventory: &Inventory) -> HashMap<String, HostVars> {
+        let mut depths = HashMap::new();
+        let mut names: Vec<&String> = self.groups.keys().collect();
+        names.sort();
+        for name in &names {
+            self.depth(name, &mut depths);
+        }
+        names.sort_by_key(|name| depths[&name[..]]);
+
+        let mut host_vars: HashMap<String, HostVars> = HashMap::new();
+        for name in &names {
+            let vars = &self.groups[&name[..]].vars;
+            if vars.is_empty() {
+                continue;
+            }
+            for host in &inventory[&name[..]] {
+                host_vars.entry(host.clone()).or_insert_with(HostVars::new).extend(vars.clone());
+            }
+        }
+        for name in &names {
+            for &(ref host, ref vars) in &self.groups[&name[..]].host_vars {
+                host_vars.entry(host.clone()).or_insert_with(HostVars::new).extend(vars.clone());
+            }
+        }
+
+        host_vars
+    }
+
+    /// The length of the longest path to group `name` from a group without parents; the groups
+    /// must not include each other, as checked by `resolve`.
+    fn depth<'b>(&'b self, name: &'b str, depths: &mut HashMap<&'b str, usize>) -> usize {
+        if let Some(&depth) = depths.get(name) {
+            return depth;
+        }
+        let mut depth = 0;
+        for (parent, group) in &self.groups {
+            if group.children.iter().any(|&(ref child, _)| child == name) {
+                depth = cmp::max(depth, self.depth(parent, depths) + 1);
+            }
+        }
+
+        depths.insert(name, depth);
+        depth
+    }
+}
+
+fn at(node: Option<&Node>, reason: String) -> SuiteError {
+    match node {
+        Some(node) => SuiteError::at(node, reason),
+        None => SuiteError::new(reason),
+    }
+}
+
+/// Reads a group given either as a list of hosts or as a hash of `hosts`, `children` and `vars`.
+fn parse_group<'a>(name: &str, group_yaml: &'a Node, max_hosts: usize) -> SuiteResult<Group<'a>> {
+    let mut group = Group {
+        node: Some(group_yaml),
+        hosts: Vec::new(),
+        children: Vec::new(),
+        vars: HostVars::new(),
+        host_vars: Vec::new(),
+    };
+    if group_yaml.as_vec().is_some() {
+        try!(parse_hosts(&mut group, name, group_yaml, max_hosts));
+        return Ok(group);
+    }
+    let elems = match group_yaml.as_hash() {
+        Some(elems) => elems,
+        None => {
+            return Err(SuiteError::at(group_yaml,
+                                      format!("inventory group '{}' must be a list of hosts or a hash, \
+                                               found {}",
+                                              name,
+                                              group_yaml.kind())))
+        }
+    };
+
+    for &(ref key, ref value) in elems {
+        match key.as_str() {
+            Some("hosts") => try!(parse_hosts(&mut group, name, value, max_hosts)),
+            Some("children") => {
+                let children = try!(parse_names(name, "children", "child", value));
+                let nodes = value.as_vec().unwrap().iter().map(Some);
+                group.children = children.into_iter().zip(nodes).collect();
+            }
+            Some("vars") => group.vars = try!(parse_vars(name, value)),
+            _ => {
+                return Err(SuiteError::at(key,
+                                          format!("unknown key '{}' in inventory group '{}'; expected \
+                                                   'hosts', 'children' or 'vars'",
+                                                  key.as_str().unwrap_or("?"),
+                                                  name)))
+            }
+        }
+    }
+
+    Ok(group)
+}
+
+/// Reads the hosts of group `name`, expanding host patterns. A host is a string or a hash of a
+/// single host and its variables, e.g. `- node05: { address: 10.1.0.5 }`.
+fn parse_hosts(group: &mut Group, name: &str, hosts_yaml: &Node, max_hosts: usize) -> SuiteResult<()> {
+    let items = match hosts_yaml.as_vec() {
+        Some(items) => items,
+        None => {
+            return Err(SuiteError::at(hosts_yaml,
+                                      format!("'hosts' of inventory group '{}' must be a list, found {}",
+                                              name,
+                                              hosts_yaml.kind())))
+        }
+    };
+
+    group.hosts.clear();
+    group.host_vars.clear();
+    for item in items {
+        let (pattern, vars) = match (item.as_str(), item.as_hash()) {
+            (Some(pattern), _) => (pattern, None),
+            (_, Some(elems)) if elems.len() == 1 && elems[0].0.as_str().is_some() => {
+                let vars = try!(parse_vars(name, &elems[0].1));
+                (elems[0].0.as_str().unwrap(), Some(vars))
+            }
+            _ => {
+                return Err(SuiteError::at(item,
+                                          format!("host in inventory group '{}' must be a string or a \
+                                                   hash of a host and its variables, found {}",
+                                                  name,
+                                                  item.kind())))
+            }
+        };
+        let hosts = try!(expand_hosts(pattern, max_hosts).map_err(|reason| {
+            SuiteError::at(item, format!("host in inventory group '{}': {}", name, reason))
+        }));
+        if let Some(vars) = vars {
+            group.host_vars.extend(hosts.iter().map(|host| (host.clone(), vars.clone())));
+        }
+        group.hosts.extend(hosts);
+    }
+
+    Ok(())
+}
+
+/// Reads the hash of variables of group `name` or one of its hosts.
+fn parse_vars(name: &str, vars_yaml: &Node) -> SuiteResult<HostVars> {
+    match try!(parse_param_value(vars_yaml)) {
+        ParamValue::Map(vars) => Ok(vars),
+        ParamValue::Null => Ok(HostVars::new()),
+        _ => {
+            Err(SuiteError::at(vars_yaml,
+                               format!("variables in inventory group '{}' must be a hash, found {}",
+                                       name,
+                                       vars_yaml.kind())))
+        }
+    }
+}
+
+/// Reads the list of strings `key`, each a `what`, of group `name`.
+fn parse_names(name: &str, key: &str, what: &str, list_yaml: &Node) -> SuiteResult<Vec<String>> {
+    let list = match list_yaml.as_vec() {
+        Some(list) => list,
+        None => {
+            return Err(SuiteError::at(list_yaml,
+                                      format!("'{}' of inventory group '{}' must be a list, found {}",
+                                              key,
+                                              name,
+                                              list_yaml.kind())))
+        }
+    };
+
+    let mut names = Vec::with_capacity(list.len());
+    for item in list {
+        match item.as_str() {
+            Some(s) => names.push(s.to_string()),
+            None => {
+                return Err(SuiteError::at(item,
+                                          format!("{} in inventory group '{}' must be a string, found {}",
+                                                  what,
+                                                  name,
+                                                  item.kind())))
+            }
+        }
+    }
+
+    Ok(names)
+}
+
+/// How a group of a host pattern like `storage-nodes:&galera-nodes:!controllers` is combined.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Op {
+    Union,
+    Intersection,
+    Exclusion,
+}
+
+fn terms(pattern: &str) -> Vec<(Op, &str)> {
+    pattern.split(|c| c == ':' || c == ',')
+           .map(|term| term.trim())
+           .filter(|term| !term.is_empty())
+           .map(|term| {
+               if term.starts_with('&') {
+                   (Op::Intersection, term[1..].trim())
+               } else if term.starts_with('!') {
+                   (Op::Exclusion, term[1..].trim())
+               } else {
+                   (Op::Union, term)
+               }
+           })
+           .collect()
+}
+
+/// Returns the inventory groups a host pattern refers to.
+pub fn group_names(pattern: &str) -> Vec<&str> {
+    terms(pattern).into_iter().map(|(_, name)| name).collect()
+}
+
+/// Resolves a host pattern into its hosts, in inventory order.
+///
+/// Groups separated by `:` or `,` are united; then the hosts not in groups prefixed by `&` and
+/// those in groups prefixed by `!` are removed, regardless of where these groups appear. Unknown
+/// groups have no hosts.
+pub fn resolve_hosts(inventory: &Inventory, pattern: &str) -> Vec<String> {
+    let terms = terms(pattern);
+    let hosts_of = |name: &str| inventory.get(name).map_or(&[][..], |hosts| &hosts[..]);
+
+    let mut hosts: Vec<String> = Vec::new();
+    for &(_, name) in terms.iter().filter(|t| t.0 == Op::Union) {
+        for host in hosts_of(name) {
+            if !hosts.contains(host) {
+                hosts.push(host.clone());
+            }
+        }
+    }
+    for &(op, name) in &terms {
+        match op {
+            Op::Union => {}
+            Op::Intersection => hosts.retain(|host| hosts_of(name).contains(host)),
+            Op::Exclusion => hosts.retain(|host| !hosts_of(name).contains(host)),
+        }
+    }
+
+    hosts
+}
@@ -0,0 +1,215 @@
+use std::collections::BTreeMap;
+use std::mem;
+use yaml_rust::{Event, ScanError, Yaml};
+use yaml_rust::parser::{MarkedEventReceiver, Parser};
+use yaml_rust::scanner::{Marker, TokenType, TScalarStyle};
+
+/// A YAML node that remembers where it has been found in the source.
+#[derive(Clone, Debug)]
+pub struct Node {
+    pub value: Value,
+    pub line: usize,
+    pub col: usize,
+}
+
+#[derive(Clone, Debug)]
+pub enum Value {
+    Scalar(Yaml),
+    Array(Vec<Node>),
+    Hash(Vec<(Node, Node)>),
+}
+
+impl Node {
+    fn new(value: Value, mark: Marker) -> Node {
+        Node {
+            value: value,
+            line: mark.line(),
+            col: mark.col() + 1,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self.value {
+            Value::Scalar(Yaml::String(ref s)) => Some(s),
+            _ => None,
+        }
+    }
+
+    pub fn as_i64(&self) -> Option<i64> {
+        match self.value {
+            Value::Scalar(Yaml::Integer(i)) => Some(i),
+            _ => None,
+        }
+    }
+
+    pub fn as_vec(&self) -> Option<&Vec<Node>> {
+        match self.value {
+            Value::Array(ref v) => Some(v),
+            _ => None,
+        }
+    }
+
+    pub fn as_hash(&self) -> Option<&Vec<(Node, Node)>> {
+        match self.value {
+            Value::Hash(ref h) => Some(h),
+            _ => None,
+        }
+    }
+
+    /// Looks up `key` if this node is a hash.
+    pub fn get(&self, key: &str) -> Option<&Node> {
+        self.as_hash().and_then(|h| h.iter().find(|kv| kv.0.as_str() == Some(key)).map(|kv| &kv.1))
+    }
+
+    /// Converts this node back into a plain `Yaml` value, dropping all positions.
+    pub fn to_yaml(&self) -> Yaml {
+        match self.value {
+            Value::Scalar(ref yaml) => yaml.clone(),
+            Value::Array(ref v) => Yaml::Array(v.iter().map(|n| n.to_yaml()).collect()),
+            Value::Hash(ref h) => {
+                Yaml::Hash(h.iter().map(|kv| (kv.0.to_yaml(), kv.1.to_yaml())).collect())
+            }
+        }
+    }
+
+    /// Describes the kind of this node for error messages.
+    pub fn kind(&self) -> &'static str {
+        match self.value {
+            Value::Scalar(Yaml::String(_)) => "a string",
+            Value::Scalar(Yaml::Integer(_)) => "an integer",
+            Value::Scalar(Yaml::Real(_)) => "a real",
+            Value::Scalar(Yaml::Boolean(_)) => "a boolean",
+            Value::Scalar(Yaml::Null) => "empty",
+            Value::Scalar(_) => "an unknown value",
+            Value::Array(_) => "a list",
+            Value::Hash(_) => "a hash",
+        }
+    }
+}
+
+pub fn load_from_str(source: &str) -> Result<Vec<Node>, ScanError> {
+    let mut loader = NodeLoader {
+        docs: Vec::new(),
+        doc_stack: Vec::new(),
+        key_stack: Vec::new(),
+        anchor_map: BTreeMap::new(),
+    };
+    let mut parser = Parser::new(source.chars());
+    try!(parser.load(&mut loader, true));
+    Ok(loader.docs)
+}
+
+/// Mirrors `yaml_rust::YamlLoader`, but keeps the marker of each node.
+struct NodeLoader {
+    docs: Vec<Node>,
+    doc_stack: Vec<(Node, usize)>,
+    key_stack: Vec<Option<Node>>,
+    anchor_map: BTreeMap<usize, Node>,
+}
+
+impl MarkedEventReceiver for NodeLoader {
+    fn on_event(&mut self, ev: Event, mark: Marker) {
+        match ev {
+            Event::DocumentEnd => {
+                match self.doc_stack.pop() {
+                    Some((node, _)) => self.docs.push(node),
+                    None => self.docs.push(Node::new(Value::Scalar(Yaml::Null), mark)),
+                }
+            }
+            Event::SequenceStart(aid) => {
+                self.doc_stack.push((Node::new(Value::Array(Vec::new()), mark), aid));
+            }
+            Event::MappingStart(aid) => {
+                self.doc_stack.push((Node::new(Value::Hash(Vec::new()), mark), aid));
+                self.key_stack.push(None);
+            }
+            Event::SequenceEnd => {
+                let node = self.doc_stack.pop().unwrap();
+                self.insert_new_node(node);
+            }
+            Event::MappingEnd => {
+                self.key_stack.pop();
+                let node = self.doc_stack.pop().unwrap();
+                self.insert_new_node(node);
+            }
+            Event::Scalar(v, style, aid, tag) => {
+                let yaml = if style != TScalarStyle::Plain {
+                    Yaml::String(v)
+                } else if let Some(TokenType::Tag(ref handle, ref suffix)) = tag {
+                    if handle == "!!" {
+                        match suffix.as_ref() {
+                            "bool" => v.parse::<bool>().map(Yaml::Boolean).unwrap_or(Yaml::BadValue),
+                            "int" => v.parse::<i64>().map(Yaml::Integer).unwrap_or(Yaml::BadValue),
+                            "float" => {
+                                match v.parse::<f64>() {
+                                    Ok(_) => Yaml::Real(v.clone()),
+                                    Err(_) => Yaml::BadValue,
+                                }
+                            }
+                            "null" => {
+                                match v.as_ref() {
+                                    "~" | "null" => Yaml::Null,
+                                    _ => Yaml::BadValue,
+                                }
+                            }
+                            _ => Yaml::String(v.clone()),
+                        }
+                    } else {
+                        Yaml::String(v.clone())
+                    }
+                } else {
+                    plain_scalar(&v)
+                };
+                self.insert_new_node((Node::new(Value::Scalar(yaml), mark), aid));
+            }
+            Event::Alias(id) => {
+                let node = match self.anchor_map.get(&id) {
+                    Some(node) => node.clone(),
+                    None => Node::new(Value::Scalar(Yaml::BadValue), mark),
+                };
+                self.insert_new_node((node, 0));
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Resolves an untagged plain scalar following the YAML 1.2 core schema.
+///
+/// Unlike `Yaml::from_str`, this also accepts the capitalized spellings `True`, `FALSE`, `Null`
+/// etc. our suites have always used.
+fn plain_scalar(v: &str) -> Yaml {
+    match v {
+        "true" | "True" | "TRUE" => Yaml::Boolean(true),
+        "false" | "False" | "FALSE" => Yaml::Boolean(false),
+        "null" | "Null" | "NULL" | "~" => Yaml::Null,
+        _ => Yaml::from_str(v),
+    }
+}
+
+impl NodeLoader {
+    fn insert_new_node(&mut self, node: (Node, usize)) {
+        // Valid anchor ids start from 1
+        if node.1 > 0 {
+            self.anchor_map.insert(node.1, node.0.clone());
+        }
+        if self.doc_stack.is_empty() {
+            self.doc_stack.push(node);
+            return;
+        }
+        let parent = self.doc_stack.last_mut().unwrap();
+        match (parent.0).value {
+            Value::Array(ref mut v) => v.push(node.0),
+            Value::Hash(ref mut h) => {
+                let cur_key = self.key_stack.last_mut().unwrap();
+                if cur_key.is_none() {
+                    *cur_key = Some(node.0);
+                } else {
+                    let key = mem::replace(cur_key, None).unwrap();
+                    h.push((key, node.0));
+                }
+            }
+            Value::Scalar(_) => unreachable!(),
+        }
+    }
+}
@@ -1,46 +1,270 @@
-use std::collections::HashMap;
+mod ansible;
+mod dynamic;
+mod expand;
+mod filter;
+mod inventory;
+mod marked;
+mod template;
+
+use std::collections::{BTreeMap, HashMap};
+use std::error::Error;
+use std::fmt;
 use std::io::prelude::*;
 use std::fs::File;
-use yaml_rust::{YamlLoader, Yaml};
+use std::path::Path;
+use yaml_rust::{ScanError, Yaml};
+
+use self::dynamic::InventoryCommand;
+use self::inventory::Groups;
+use self::marked::Node;
+use self::template::HostContext;
+
+pub use self::filter::{Filter, Selector};
 
 pub type Inventory = HashMap<String, Vec<String>>;
 
-#[derive(Debug, RustcEncodable)]
+/// The variables of a host, e.g. `ansible_host`, by name.
+pub type HostVars = BTreeMap<String, ParamValue>;
+
+/// How many hosts a single inventory entry like `node[01:10]` may expand to unless the suite's
+/// settings say otherwise.
+pub const DEFAULT_MAX_HOSTS_PER_PATTERN: usize = 4096;
+
+/// A module parameter as written in the suite's YAML.
+#[derive(Debug, Clone, PartialEq, RustcEncodable)]
+pub enum ParamValue {
+    Null,
+    Bool(bool),
+    Int(i64),
+    Float(f64),
+    String(String),
+    List(Vec<ParamValue>),
+    Map(BTreeMap<String, ParamValue>),
+}
+
+impl ParamValue {
+    pub fn as_bool(&self) -> Option<bool> {
+        match *self {
+            ParamValue::Bool(b) => Some(b),
+            _ => None,
+        }
+    }
+
+    pub fn as_i64(&self) -> Option<i64> {
+        match *self {
+            ParamValue::Int(i) => Some(i),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match *self {
+            ParamValue::String(ref s) => Some(s),
+            _ => None,
+        }
+    }
+
+    pub fn as_list(&self) -> Option<&Vec<ParamValue>> {
+        match *self {
+            ParamValue::List(ref l) => Some(l),
+            _ => None,
+        }
+    }
+
+    pub fn as_map(&self) -> Option<&BTreeMap<String, ParamValue>> {
+        match *self {
+            ParamValue::Map(ref m) => Some(m),
+            _ => None,
+        }
+    }
+
+    /// Interprets this value as a port number; quoted numbers are accepted as well.
+    pub fn as_port(&self) -> Option<u16> {
+        match *self {
+            ParamValue::Int(i) if i > 0 && i <= 0xFFFF => Some(i as u16),
+            ParamValue::String(ref s) => {
+                match s.parse::<u16>() {
+                    Ok(p) if p > 0 => Some(p),
+                    _ => None,
+                }
+            }
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for ParamValue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ParamValue::Null => write!(f, "null"),
+            ParamValue::Bool(b) => write!(f, "{}", b),
+            ParamValue::Int(i) => write!(f, "{}", i),
+            ParamValue::Float(x) => write!(f, "{:?}", x),
+            ParamValue::String(ref s) => write!(f, "{}", s),
+            ParamValue::List(ref l) => {
+                let items: Vec<String> = l.iter().map(|v| v.to_string()).collect();
+                write!(f, "[{}]", items.join(", "))
+            }
+            ParamValue::Map(ref m) => {
+                let items: Vec<String> = m.iter().map(|(k, v)| format!("{}: {}", k, v)).collect();
+                write!(f, "{{{}}}", items.join(", "))
+            }
+        }
+    }
+}
+
+pub type Params = HashMap<String, ParamValue>;
+
+#[derive(Debug, Clone, RustcEncodable)]
 pub struct Property {
     pub name: String,
     pub module: String,
-    pub params: HashMap<String, String>,
+    pub params: Params,
+    /// Overrides the suite's read timeout for this property, in milliseconds.
+    pub timeout_ms: Option<u64>,
+    /// Overrides the suite's connect timeout for this property, in milliseconds.
+    pub connect_timeout_ms: Option<u64>,
+    /// Overrides the suite's number of UDP retransmissions for this property.
+    pub retries: Option<u32>,
+    /// Runs a `text/tcp` or `raw/tcp` module over TLS.
+    pub tls: Option<TlsOptions>,
+    /// Free-form labels, e.g. `warning` to let `dirac-check` treat failures as non-fatal.
+    pub tags: Vec<String>,
+    /// Hosts this property is not checked against but reported as skipped; cf. `apply_filter`.
+    pub skipped_hosts: Vec<String>,
+    /// Line and column of the property in the suite, for errors found after reading it.
+    pub position: (usize, usize),
+}
+
+#[derive(Debug, Clone, Default, PartialEq, RustcEncodable)]
+pub struct TlsOptions {
+    /// Name sent via SNI and verified against the certificate; defaults to the host.
+    pub server_name: Option<String>,
 }
 
 #[derive(Debug, RustcEncodable)]
 pub struct Check {
+    /// The host pattern as written in `hosts:`, e.g. `storage-nodes:&galera-nodes`.
     pub inventory_name: String,
+    /// The hosts `inventory_name` resolves to.
+    pub hosts: Vec<String>,
     pub properties: Vec<Property>,
+    /// Line and column of `hosts:` in the suite, for errors found after reading it.
+    pub hosts_position: (usize, usize),
 }
 
+#[derive(Debug, Default, RustcEncodable)]
+pub struct Settings {
+    /// Number of worker threads running properties concurrently; defaults to 1.
+    pub jobs: Option<usize>,
+    /// Read timeout for all properties, in milliseconds.
+    pub timeout_ms: Option<u64>,
+    /// Connect timeout for all properties, in milliseconds.
+    pub connect_timeout_ms: Option<u64>,
+    /// Number of UDP retransmissions for all properties; defaults to 0.
+    pub retries: Option<u32>,
+    /// How many hosts a single inventory entry may expand to; defaults to
+    /// `DEFAULT_MAX_HOSTS_PER_PATTERN`.
+    pub max_hosts_per_pattern: Option<usize>,
+}
 
 #[derive(Debug, RustcEncodable)]
 pub struct CheckSuite {
+    pub settings: Settings,
     pub inventory: Inventory,
+    /// The variables of the hosts that have any.
+    pub host_vars: HashMap<String, HostVars>,
     pub checks: Vec<Check>,
 }
 
+/// Describes why a check suite could not be read and where the problem has been found.
+#[derive(Debug)]
+pub struct SuiteError {
+    pub filename: Option<String>,
+    pub line: Option<usize>,
+    pub col: Option<usize>,
+    pub reason: String,
+}
+
+impl SuiteError {
+    pub fn new(reason: String) -> SuiteError {
+        SuiteError {
+            filename: None,
+            line: None,
+            col: None,
+            reason: reason,
+        }
+    }
+
+    fn at(node: &Node, reason: String) -> SuiteError {
+        SuiteError::at_position((node.line, node.col), reason)
+    }
+
+    /// Creates an error at a position kept from reading the suite, e.g. `Property::position`.
+    pub fn at_position((line, col): (usize, usize), reason: String) -> SuiteError {
+        SuiteError {
+            filename: None,
+            line: Some(line),
+            col: Some(col),
+            reason: reason,
+        }
+    }
+
+    pub fn with_filename(mut self, filename: &str) -> SuiteError {
+        self.filename = Some(filename.to_string());
+        self
+    }
+}
+
+impl fmt::Display for SuiteError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let filename = self.filename.as_ref().map_or("<string>", |s| s);
+        match self.line {
+            Some(line) => write!(f, "{}:{}: {}", filename, line, self.reason),
+            None => write!(f, "{}: {}", filename, self.reason),
+        }
+    }
+}
+
+impl Error for SuiteError {
+    fn description(&self) -> &str {
+        &self.reason
+    }
+}
+
+impl From<ScanError> for SuiteError {
+    fn from(err: ScanError) -> SuiteError {
+        SuiteError {
+            filename: None,
+            line: Some(err.marker().line()),
+            col: Some(err.marker().col() + 1),
+            reason: format!("invalid YAML: {}", err.description()),
+        }
+    }
+}
+
+pub type SuiteResult<T> = Result<T, SuiteError>;
+
 impl CheckSuite {
-    pub fn read_from_file(filename: &str) -> Option<CheckSuite> {
-        let mut f = File::open(filename).unwrap();
+    pub fn read_from_file(filename: &str) -> SuiteResult<CheckSuite> {
         let mut yaml_str = String::new();
-        let _ = f.read_to_string(&mut yaml_str);
+        if let Err(err) = File::open(filename).and_then(|mut f| f.read_to_string(&mut yaml_str)) {
+            return Err(SuiteError::new(format!("could not read file: {}", err)).with_filename(filename));
+        }
 
-        CheckSuite::read_from_string(&yaml_str)
+        let dir = Path::new(filename).parent();
+        CheckSuite::read(&yaml_str, dir).map_err(|err| err.with_filename(filename))
     }
 
-    pub fn read_from_string(yaml_str: &str) -> Option<CheckSuite> {
-        let key_inventory: Yaml = Yaml::from_str("inventory");
-        let key_host = Yaml::from_str("hosts");
-        let key_properties = Yaml::from_str("properties");
-        let key_name = Yaml::from_str("name");
+    /// Reads a suite from `yaml_str`; inventory files and commands are relative to the working
+    /// directory.
+    pub fn read_from_string(yaml_str: &str) -> SuiteResult<CheckSuite> {
+        CheckSuite::read(yaml_str, None)
+    }
 
-        let docs = YamlLoader::load_from_str(&yaml_str).unwrap();
+    /// Reads a suite whose inventory files and commands are relative to `dir`.
+    fn read(yaml_str: &str, dir: Option<&Path>) -> SuiteResult<CheckSuite> {
+        let docs = try!(marked::load_from_str(&yaml_str));
 
     /*
     [Array([
@@ -60,92 +284,617 @@ impl CheckSuite {
     */
 
         // We assume only one document in the file which consists of a list of hashes
-        let mut inventory = Inventory::new();
+        let entries = match docs.first().and_then(|doc| doc.as_vec()) {
+            Some(entries) => entries,
+            None => {
+                let reason = "expected a list of settings, inventory and check entries".to_string();
+                return Err(match docs.first() {
+                    Some(doc) => SuiteError::at(doc, reason),
+                    None => SuiteError::new(reason),
+                });
+            }
+        };
+
+        let mut settings = Settings::default();
         let mut checks: Vec<Check> = Vec::new();
 
-        for hash in docs[0].as_vec().unwrap() {
-            let map = hash.as_hash().unwrap();
-            if map.contains_key(&key_inventory) {
-                debug!("Found inventory: {:?}", hash);
-                let inventory_yaml = map.get(&key_inventory).unwrap().as_hash().unwrap();
-                for hosts_name_yaml in inventory_yaml.keys() {
-                    let hosts_name = hosts_name_yaml.as_str().unwrap().to_string();
-                    let mut hosts: Vec<String> = Vec::new();
-                    for host in inventory_yaml.get(hosts_name_yaml).unwrap().as_vec().unwrap() {
-                        let h = host.as_str().unwrap().to_string();
-                        debug!("Host: {}", h);
-                        hosts.push(h);
-                    }
-                    debug!("- - Inventory name: '{:?}'", hosts_name);
-                    debug!("- - Inventory hosts: '{:?}'", hosts);
-                    inventory.insert(hosts_name.to_string(), hosts);
-                }
+        for entry in entries {
+            if entry.as_hash().is_none() {
+                return Err(SuiteError::at(entry,
+                                          format!("expected a settings, inventory or check entry, \
+                                                   found {}",
+                                                  entry.kind())));
+            }
+            if let Some(settings_yaml) = entry.get("settings") {
+                debug!("Found settings: {:?}", settings_yaml);
+                settings = try!(parse_settings(settings_yaml));
             }
         }
 
-        for hash in docs[0].as_vec().unwrap() {
-            let map = hash.as_hash().unwrap();
-            if map.contains_key(&key_inventory) {
-            } else {
-                if map.contains_key(&key_host) && map.contains_key(&key_properties) {
-                    debug!("- Found check: {:?}", hash);
-                    let inventory_name = map.get(&key_host).unwrap().as_str().unwrap();
-                    let mut properties = Vec::new();
-
-                    let properties_yaml = map.get(&key_properties).unwrap().as_vec().unwrap();
-                    for property_yml in properties_yaml {
-                        let mut name: Option<String> = None;
-                        let mut params = HashMap::new();
-                        let mut module: Option<String> = None;
-                        for elem in property_yml.as_hash().unwrap() {
-                            if elem.0 == &key_name {
-                                name = Some(elem.1.as_str().unwrap().to_string());
-                            } else {
-                                module = Some(elem.0.as_str().unwrap().to_string());
-                            }
-                        }
-                        if module.is_some() {
-                            let params_yaml = property_yml.as_hash()
-                                                          .unwrap()
-                                                          .get(&Yaml::from_str(module.as_ref()
-                                                                                     .unwrap()))
-                                                          .unwrap()
-                                                          .as_hash()
-                                                          .unwrap();
-                            for kv in params_yaml {
-                                let value: String = match *kv.1 {
-                                    Yaml::Integer(i) => i.to_string(),
-                                    Yaml::Real(ref r) => r.to_string(),
-                                    Yaml::String(ref string) => string.to_string(),
-                                    _ => "<could not translate YAML value>".to_string(),
-                                };
-                                params.insert(kv.0.as_str().unwrap().to_string(), value);
-                            }
-                        }
-                        properties.push(Property {
-                            name: name.unwrap(),
-                            module: module.unwrap(),
-                            params: params,
-                        });
+        // Groups of inventories, inventory files and commands replace those of the same name read before.
+        let max_hosts = settings.max_hosts_per_pattern.unwrap_or(DEFAULT_MAX_HOSTS_PER_PATTERN);
+        let mut groups = Groups::new(max_hosts);
+        let mut host_vars = HashMap::new();
+        for entry in entries {
+            if let Some(inventory_yaml) = entry.get("inventory") {
+                debug!("Found inventory: {:?}", inventory_yaml);
+                try!(groups.parse(inventory_yaml));
+            } else if let Some(files_yaml) = entry.get("inventory_file") {
+                debug!("Found inventory file: {:?}", files_yaml);
+                for (path, node) in try!(parse_inventory_files(files_yaml)) {
+                    let path = dir.map_or(Path::new(&path).to_path_buf(), |dir| dir.join(&path));
+                    let ansible_inventory = try!(ansible::read(&path, max_hosts).map_err(|reason| {
+                        SuiteError::at(node,
+                                       format!("inventory file '{}': {}", path.display(), reason))
+                    }));
+                    for (name, hosts, children) in ansible_inventory.groups {
+                        groups.add(name, hosts, children);
                     }
-
-                    let check = Check {
-                        inventory_name: inventory_name.to_string(),
-                        properties: properties,
-                    };
-                    debug!("- Created a check: {:?}", check);
-                    checks.push(check);
+                    host_vars.extend(ansible_inventory.host_vars);
+                }
+            } else if let Some(command_yaml) = entry.get("inventory_command") {
+                debug!("Found inventory command: {:?}", command_yaml);
+                let command = try!(parse_inventory_command(command_yaml));
+                let ansible_inventory = try!(command.read(dir, max_hosts).map_err(|reason| {
+                    SuiteError::at(command_yaml,
+                                   format!("inventory command '{}': {}", command.command_line(), reason))
+                }));
+                for (name, hosts, children) in ansible_inventory.groups {
+                    groups.add(name, hosts, children);
                 }
+                host_vars.extend(ansible_inventory.host_vars);
+            }
+        }
+        let inventory = try!(groups.resolve());
+        // Variables from `- inventory:` entries take precedence over those of inventory files.
+        for (host, vars) in groups.host_vars(&inventory) {
+            host_vars.entry(host).or_insert_with(HostVars::new).extend(vars);
+        }
 
+        for entry in entries {
+            if entry.get("settings").is_some() || entry.get("inventory").is_some() ||
+               entry.get("inventory_file").is_some() || entry.get("inventory_command").is_some() {
+                continue;
             }
+            let check = try!(parse_check(entry, &inventory));
+            debug!("- Created a check: {:?}", check);
+            checks.push(check);
         }
+        info!("* Settings: {:?}", settings);
         info!("* Inventory: {:?}", inventory);
         info!("* Checks: {:?}", checks);
 
         let suite = CheckSuite {
+            settings: settings,
             inventory: inventory,
+            host_vars: host_vars,
             checks: checks,
         };
-        return Some(suite);
+        Ok(suite)
+    }
+
+    /// Marks every property as skipped for the hosts `filter` does not select. The inventory and all
+    /// properties are kept, so reports still show what has not been checked.
+    pub fn apply_filter(&mut self, filter: &Filter) {
+        let inventory = &self.inventory;
+        for check in &mut self.checks {
+            for index in 0..check.properties.len() {
+                let skipped: Vec<String> = check.hosts
+                                                .iter()
+                                                .filter(|host| {
+                                                    !filter.selects(inventory,
+                                                                    check,
+                                                                    &check.properties[index],
+                                                                    host)
+                                                })
+                                                .cloned()
+                                                .collect();
+                check.properties[index].skipped_hosts = skipped;
+            }
+        }
+    }
+
+    /// The address `host` is connected to: its variable `address` or `ansible_host`, if set, and the
+    /// host itself otherwise.
+    pub fn address_of<'a>(&'a self, host: &'a str) -> &'a str {
+        let vars = self.host_vars.get(host);
+        vars.and_then(|vars| vars.get("address").or_else(|| vars.get("ansible_host")))
+            .and_then(|address| address.as_str())
+            .unwrap_or(host)
+    }
+
+    /// Returns `property` as checked against `host`: templates like `{{ host.ssh_port }}` in its
+    /// params are replaced by the host's variables and the variable `port_offset` is added to the
+    /// param `port`. Unless given, the TLS server name is the host's name rather than its address.
+    pub fn property_for(&self, property: &Property, host: &str) -> Result<Property, String> {
+        let vars = self.host_vars.get(host);
+        let context = HostContext {
+            name: host,
+            address: self.address_of(host),
+            vars: vars,
+        };
+
+        let mut params = Params::new();
+        for (key, value) in &property.params {
+            let value = try!(template::render(value, &context)
+                                 .map_err(|err| format!("param '{}': {}", key, err)));
+            params.insert(key.clone(), value);
+        }
+        if let Some(offset) = vars.and_then(|vars| vars.get("port_offset")) {
+            let offset = try!(offset.as_i64().ok_or_else(|| {
+                format!("variable 'port_offset' of host '{}' must be an integer, found '{}'", host, offset)
+            }));
+            if let Some(port) = params.get("port").and_then(|port| port.as_port()) {
+                let shifted = (port as i64).saturating_add(offset);
+                if shifted < 1 || shifted > 65535 {
+                    return Err(format!("param 'port': {} plus 'port_offset' {} of host '{}' is out of range",
+                                       port,
+                                       offset,
+                                       host));
+                }
+                params.insert("port".to_string(), ParamValue::Int(shifted));
+            }
+        }
+
+        let mut tls = property.tls.clone();
+        if let Some(ref mut tls) = tls {
+            if tls.server_name.is_none() && context.address != host {
+                tls.server_name = Some(host.to_string());
+            }
+        }
+
+        Ok(Property {
+            params: params,
+            tls: tls,
+            ..property.clone()
+        })
+    }
+
+    /// Returns an error for every group in the checks' `hosts` that is not in the inventory.
+    pub fn check_inventory_names(&self) -> Vec<SuiteError> {
+        let mut errors = Vec::new();
+        for check in &self.checks {
+            let pattern = &check.inventory_name;
+            for name in inventory::group_names(pattern) {
+                if self.inventory.contains_key(name) {
+                    continue;
+                }
+                let reason = if name == pattern {
+                    format!("hosts '{}' does not name an inventory group", name)
+                } else {
+                    format!("group '{}' in hosts '{}' does not name an inventory group", name, pattern)
+                };
+                errors.push(SuiteError::at_position(check.hosts_position, reason));
+            }
+        }
+
+        errors
+    }
+}
+
+fn parse_settings(settings_yaml: &Node) -> SuiteResult<Settings> {
+    let mut settings = Settings::default();
+
+    if settings_yaml.as_hash().is_none() {
+        return Err(SuiteError::at(settings_yaml,
+                                  format!("settings must be a hash, found {}", settings_yaml.kind())));
+    }
+    if let Some(jobs) = settings_yaml.get("jobs") {
+        settings.jobs = match jobs.as_i64() {
+            Some(n) if n > 0 => Some(n as usize),
+            _ => return Err(SuiteError::at(jobs, "setting 'jobs' must be a positive integer".to_string())),
+        };
+    }
+    if let Some(timeout) = settings_yaml.get("timeout") {
+        settings.timeout_ms = Some(try!(parse_seconds(timeout, "timeout")));
+    }
+    if let Some(connect_timeout) = settings_yaml.get("connect_timeout") {
+        settings.connect_timeout_ms = Some(try!(parse_seconds(connect_timeout, "connect_timeout")));
+    }
+    if let Some(retries) = settings_yaml.get("retries") {
+        settings.retries = Some(try!(parse_retries(retries)));
+    }
+    if let Some(max_hosts) = settings_yaml.get("max_hosts_per_pattern") {
+        settings.max_hosts_per_pattern = match max_hosts.as_i64() {
+            Some(n) if n > 0 => Some(n as usize),
+            _ => {
+                return Err(SuiteError::at(max_hosts,
+                                          "setting 'max_hosts_per_pattern' must be a positive integer"
+                                              .to_string()))
+            }
+        };
+    }
+
+    Ok(settings)
+}
+
+/// Parses a positive number of seconds, e.g. `5` or `0.5`, into milliseconds.
+///
+/// Fails for less than a millisecond, which would otherwise turn into no time at all.
+fn parse_seconds(node: &Node, name: &str) -> SuiteResult<u64> {
+    let seconds = match node.to_yaml() {
+        Yaml::Integer(i) => i as f64,
+        Yaml::Real(ref r) => r.parse::<f64>().unwrap_or(0.0),
+        _ => 0.0,
+    };
+    if seconds > 0.0 && seconds.is_finite() {
+        let ms = (seconds * 1000.0) as u64;
+        if ms == 0 {
+            return Err(SuiteError::at(node,
+                                      format!("'{}' must be at least 0.001 seconds, found {}", name, seconds)));
+        }
+        Ok(ms)
+    } else {
+        Err(SuiteError::at(node,
+                           format!("'{}' must be a positive number of seconds, found {}",
+                                   name,
+                                   node.kind())))
+    }
+}
+
+/// Parses a non-negative number of retransmissions.
+fn parse_retries(node: &Node) -> SuiteResult<u32> {
+    let found = match node.as_i64() {
+        Some(n) if n >= 0 && n <= u32::max_value() as i64 => return Ok(n as u32),
+        Some(n) => n.to_string(),
+        None => node.kind().to_string(),
+    };
+
+    Err(SuiteError::at(node, format!("'retries' must be a non-negative integer, found {}", found)))
+}
+
+/// Reads `inventory_file: <path>` or `inventory_file: [<path>, ...]`.
+fn parse_inventory_files(node: &Node) -> SuiteResult<Vec<(String, &Node)>> {
+    if let Some(path) = node.as_str() {
+        return Ok(vec![(path.to_string(), node)]);
+    }
+    let items = match node.as_vec() {
+        Some(items) => items,
+        None => {
+            return Err(SuiteError::at(node,
+                                      format!("'inventory_file' must be a path or a list of paths, \
+                                               found {}",
+                                              node.kind())))
+        }
+    };
+
+    let mut paths = Vec::with_capacity(items.len());
+    for item in items {
+        match item.as_str() {
+            Some(path) => paths.push((path.to_string(), item)),
+            None => {
+                return Err(SuiteError::at(item,
+                                          format!("'inventory_file' must be a path or a list of paths, \
+                                                   found {}",
+                                                  item.kind())))
+            }
+        }
+    }
+
+    Ok(paths)
+}
+
+/// Reads `inventory_command: <command line>`, `inventory_command: [<program>, <arg>, ...]` or a hash
+/// of such a `command` and the seconds to `cache` its output for.
+fn parse_inventory_command(node: &Node) -> SuiteResult<InventoryCommand> {
+    let (command_yaml, cache_yaml) = if node.as_hash().is_some() {
+        for &(ref key, _) in node.as_hash().unwrap() {
+            match key.as_str() {
+                Some("command") | Some("cache") => {}
+                _ => {
+                    return Err(SuiteError::at(key,
+                                              format!("unknown key '{}' in 'inventory_command'; expected \
+                                                       'command' or 'cache'",
+                                                      key.as_str().unwrap_or("?"))))
+                }
+            }
+        }
+        match node.get("command") {
+            Some(command_yaml) => (command_yaml, node.get("cache")),
+            None => return Err(SuiteError::at(node, "'inventory_command' lacks a 'command'".to_string())),
+        }
+    } else {
+        (node, None)
+    };
+
+    let invalid = |node: &Node| {
+        SuiteError::at(node,
+                       format!("'inventory_command' must be a command line or a list of a program and \
+                                its arguments, found {}",
+                               node.kind()))
+    };
+    let mut words: Vec<String> = match (command_yaml.as_str(), command_yaml.as_vec()) {
+        (Some(line), _) => line.split_whitespace().map(|w| w.to_string()).collect(),
+        (_, Some(items)) => {
+            let mut words = Vec::with_capacity(items.len());
+            for item in items {
+                words.push(try!(item.as_str().ok_or_else(|| invalid(item))).to_string());
+            }
+            words
+        }
+        _ => return Err(invalid(command_yaml)),
+    };
+    if words.is_empty() {
+        return Err(SuiteError::at(command_yaml, "'inventory_command' must not be empty".to_string()));
+    }
+
+    let cache_ms = match cache_yaml {
+        Some(cache_yaml) => Some(try!(parse_seconds(cache_yaml, "cache"))),
+        None => None,
+    };
+    Ok(InventoryCommand {
+        program: words.remove(0),
+        args: words,
+        cache_ms: cache_ms,
+    })
+}
+
+fn parse_check(check_yaml: &Node, inventory: &Inventory) -> SuiteResult<Check> {
+    debug!("- Found check: {:?}", check_yaml);
+    let (inventory_name, hosts_position) = match check_yaml.get("hosts") {
+        Some(hosts) => {
+            match hosts.as_str() {
+                Some(name) => (name.to_string(), (hosts.line, hosts.col)),
+                None => {
+                    return Err(SuiteError::at(hosts,
+                                              format!("'hosts' must name an inventory group, \
+                                                       found {}",
+                                                      hosts.kind())))
+                }
+            }
+        }
+        None => return Err(SuiteError::at(check_yaml, "check has no 'hosts'".to_string())),
+    };
+    let properties_yaml = match check_yaml.get("properties") {
+        Some(properties) => {
+            match properties.as_vec() {
+                Some(list) => list,
+                None => {
+                    return Err(SuiteError::at(properties,
+                                              format!("properties of check for '{}' must be a list, \
+                                                       found {}",
+                                                      inventory_name,
+                                                      properties.kind())))
+                }
+            }
+        }
+        None => {
+            return Err(SuiteError::at(check_yaml,
+                                      format!("check for '{}' has no 'properties'", inventory_name)))
+        }
+    };
+
+    let mut properties = Vec::new();
+    for property_yaml in properties_yaml {
+        properties.push(try!(parse_property(property_yaml)));
+    }
+
+    Ok(Check {
+        hosts: inventory::resolve_hosts(inventory, &inventory_name),
+        inventory_name: inventory_name,
+        properties: properties,
+        hosts_position: hosts_position,
+    })
+}
+
+fn parse_property(property_yaml: &Node) -> SuiteResult<Property> {
+    let elems = match property_yaml.as_hash() {
+        Some(elems) => elems,
+        None => {
+            return Err(SuiteError::at(property_yaml,
+                                      format!("property must be a hash, found {}", property_yaml.kind())))
+        }
+    };
+
+    let mut name: Option<String> = None;
+    let mut timeout_ms: Option<u64> = None;
+    let mut connect_timeout_ms: Option<u64> = None;
+    let mut retries: Option<u32> = None;
+    let mut tls: Option<TlsOptions> = None;
+    let mut tags: Vec<String> = Vec::new();
+    let mut module: Option<(String, &Node)> = None;
+    for &(ref key, ref value) in elems {
+        let key_str = match key.as_str() {
+            Some(key_str) => key_str,
+            None => {
+                return Err(SuiteError::at(key,
+                                          format!("property key must be a string, found {}", key.kind())))
+            }
+        };
+        if key_str == "name" {
+            match value.as_str() {
+                Some(n) => name = Some(n.to_string()),
+                None => {
+                    return Err(SuiteError::at(value,
+                                              format!("property name must be a string, found {}",
+                                                      value.kind())))
+                }
+            }
+        } else if key_str == "timeout" {
+            timeout_ms = Some(try!(parse_seconds(value, key_str)));
+        } else if key_str == "connect_timeout" {
+            connect_timeout_ms = Some(try!(parse_seconds(value, key_str)));
+        } else if key_str == "retries" {
+            retries = Some(try!(parse_retries(value)));
+        } else if key_str == "tls" {
+            tls = try!(parse_tls(value));
+        } else if key_str == "tags" {
+            tags = try!(parse_tags(value));
+        } else if let Some((ref other, _)) = module {
+            return Err(SuiteError::at(key,
+                                      format!("property has more than one module: '{}' and '{}'",
+                                              other,
+                                              key_str)));
+        } else {
+            module = Some((key_str.to_string(), value));
+        }
+    }
+
+    let name = match name {
+        Some(name) => name,
+        None => return Err(SuiteError::at(property_yaml, "property has no name".to_string())),
+    };
+    let (module, params_yaml) = match module {
+        Some(module) => module,
+        None => {
+            return Err(SuiteError::at(property_yaml,
+                                      format!("property '{}' has no module", name)))
+        }
+    };
+    let params_hash = match params_yaml.as_hash() {
+        Some(params_hash) => params_hash,
+        None => {
+            return Err(SuiteError::at(params_yaml,
+                                      format!("params of module '{}' in property '{}' must be a \
+                                               hash, found {}",
+                                              module,
+                                              name,
+                                              params_yaml.kind())))
+        }
+    };
+
+    let mut params = Params::new();
+    for &(ref key, ref value_yaml) in params_hash {
+        let key_str = match key.as_str() {
+            Some(key_str) => key_str.to_string(),
+            None => {
+                return Err(SuiteError::at(key,
+                                          format!("param name in property '{}' must be a string, \
+                                                   found {}",
+                                                  name,
+                                                  key.kind())))
+            }
+        };
+        let value = try!(parse_param_value(value_yaml));
+        try!(template::check(&value).map_err(|reason| {
+            SuiteError::at(value_yaml,
+                           format!("param '{}' of property '{}': {}", key_str, name, reason))
+        }));
+        params.insert(key_str, value);
+    }
+
+    Ok(Property {
+        name: name,
+        module: module,
+        params: params,
+        timeout_ms: timeout_ms,
+        connect_timeout_ms: connect_timeout_ms,
+        retries: retries,
+        tls: tls,
+        tags: tags,
+        skipped_hosts: Vec::new(),
+        position: (property_yaml.line, property_yaml.col),
+    })
+}
+
+/// Reads `tags: <tag>` or `tags: [<tag>, ...]`.
+fn parse_tags(node: &Node) -> SuiteResult<Vec<String>> {
+    if let Some(tag) = node.as_str() {
+        return Ok(vec![tag.to_string()]);
+    }
+    let items = match node.as_vec() {
+        Some(items) => items,
+        None => {
+            return Err(SuiteError::at(node,
+                                      format!("'tags' must be a string or a list of strings, found {}",
+                                              node.kind())))
+        }
+    };
+
+    let mut tags = Vec::with_capacity(items.len());
+    for item in items {
+        match item.as_str() {
+            Some(tag) => tags.push(tag.to_string()),
+            None => {
+                return Err(SuiteError::at(item,
+                                          format!("'tags' must be a string or a list of strings, found {}",
+                                                  item.kind())))
+            }
+        }
     }
+
+    Ok(tags)
+}
+
+/// Reads `tls: true` or `tls: { server_name: <name> }`.
+fn parse_tls(node: &Node) -> SuiteResult<Option<TlsOptions>> {
+    if let marked::Value::Scalar(Yaml::Boolean(enabled)) = node.value {
+        return Ok(if enabled { Some(TlsOptions::default()) } else { None });
+    }
+    let elems = match node.as_hash() {
+        Some(elems) => elems,
+        None => {
+            return Err(SuiteError::at(node,
+                                      format!("'tls' must be a boolean or a hash, found {}", node.kind())))
+        }
+    };
+
+    let mut options = TlsOptions::default();
+    for &(ref key, ref value) in elems {
+        match key.as_str() {
+            Some("server_name") => {
+                match value.as_str() {
+                    Some(server_name) => options.server_name = Some(server_name.to_string()),
+                    None => {
+                        return Err(SuiteError::at(value,
+                                                  format!("'server_name' must be a string, found {}",
+                                                          value.kind())))
+                    }
+                }
+            }
+            _ => {
+                return Err(SuiteError::at(key,
+                                          format!("unknown TLS option '{}'",
+                                                  key.as_str().unwrap_or("?"))))
+            }
+        }
+    }
+
+    Ok(Some(options))
+}
+
+fn parse_param_value(node: &Node) -> SuiteResult<ParamValue> {
+    let value = match node.value {
+        marked::Value::Scalar(ref yaml) => {
+            match *yaml {
+                Yaml::Null => ParamValue::Null,
+                Yaml::Boolean(b) => ParamValue::Bool(b),
+                Yaml::Integer(i) => ParamValue::Int(i),
+                Yaml::Real(ref r) => {
+                    match r.parse::<f64>() {
+                        Ok(x) => ParamValue::Float(x),
+                        Err(_) => ParamValue::String(r.clone()),
+                    }
+                }
+                Yaml::String(ref s) => ParamValue::String(s.clone()),
+                _ => return Err(SuiteError::at(node, "unsupported param value".to_string())),
+            }
+        }
+        marked::Value::Array(ref items) => {
+            let mut list = Vec::with_capacity(items.len());
+            for item in items {
+                list.push(try!(parse_param_value(item)));
+            }
+            ParamValue::List(list)
+        }
+        marked::Value::Hash(ref kvs) => {
+            let mut map = BTreeMap::new();
+            for &(ref key, ref value) in kvs {
+                let key_str = match key.to_yaml() {
+                    Yaml::String(s) | Yaml::Real(s) => s,
+                    Yaml::Integer(i) => i.to_string(),
+                    Yaml::Boolean(b) => b.to_string(),
+                    _ => {
+                        return Err(SuiteError::at(key,
+                                                  format!("param key must be a scalar, found {}",
+                                                          key.kind())))
+                    }
+                };
+                map.insert(key_str, try!(parse_param_value(value)));
+            }
+            ParamValue::Map(map)
+        }
+    };
+
+    Ok(value)
 }
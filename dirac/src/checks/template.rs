@@ -0,0 +1,208 @@
+use std::collections::BTreeMap;
+
+use super::{HostVars, ParamValue};
+
+/// The host a property is checked against, as seen by templates.
+pub struct HostContext<'a> {
+    /// The host's name in the inventory.
+    pub name: &'a str,
+    /// The address it is connected to.
+    pub address: &'a str,
+    pub vars: Option<&'a HostVars>,
+}
+
+/// An expression like `host.ssh_port | default(22)`.
+#[derive(Debug, PartialEq)]
+struct Expr {
+    /// The keys following `host.`, e.g. `["ssh_port"]`.
+    path: Vec<String>,
+    default: Option<ParamValue>,
+}
+
+impl Expr {
+    fn parse(source: &str) -> Result<Expr, String> {
+        let invalid = |reason: &str| format!("invalid template '{{{{{}}}}}': {}", source, reason);
+        let mut parts = source.splitn(2, '|');
+        let path = parts.next().unwrap().trim();
+        if !path.starts_with("host.") {
+            return Err(invalid("expected a variable like 'host.port'"));
+        }
+        let path: Vec<String> = path["host.".len()..].split('.').map(|key| key.to_string()).collect();
+        if path.iter().any(|key| key.is_empty() || !key.chars().all(|c| c.is_alphanumeric() || c == '_')) {
+            return Err(invalid("invalid variable name"));
+        }
+
+        let default = match parts.next().map(|filter| filter.trim()) {
+            None => None,
+            Some(filter) if filter.starts_with("default(") && filter.ends_with(')') => {
+                let literal = filter["default(".len()..filter.len() - 1].trim();
+                Some(try!(parse_literal(literal).ok_or_else(|| invalid("invalid default value"))))
+            }
+            Some(_) => return Err(invalid("the only supported filter is 'default(<value>)'")),
+        };
+
+        Ok(Expr {
+            path: path,
+            default: default,
+        })
+    }
+
+    fn eval(&self, host: &HostContext) -> Result<ParamValue, String> {
+        let value = match &self.path[0][..] {
+            "name" if self.path.len() == 1 => Some(ParamValue::String(host.name.to_string())),
+            "address" if self.path.len() == 1 => Some(ParamValue::String(host.address.to_string())),
+            first => {
+                let mut value = host.vars.and_then(|vars| vars.get(first));
+                for key in &self.path[1..] {
+                    value = value.and_then(|v| v.as_map()).and_then(|map| map.get(key));
+                }
+                value.cloned()
+            }
+        };
+
+        match (value, self.default.as_ref()) {
+            (Some(value), _) => Ok(value),
+            (None, Some(default)) => Ok(default.clone()),
+            (None, None) => {
+                Err(format!("host '{}' has no variable '{}'", host.name, self.path.join(".")))
+            }
+        }
+    }
+}
+
+/// Returns the text of a string quoted with `'` or `"`.
+fn unquote(literal: &str) -> Option<&str> {
+    let quote = match literal.chars().next() {
+        Some(quote) if quote == '\'' || quote == '"' => quote,
+        _ => return None,
+    };
+    if literal.len() < 2 || !literal.ends_with(quote) {
+        return None;
+    }
+    let text = &literal[1..literal.len() - 1];
+    if text.contains(quote) { None } else { Some(text) }
+}
+
+/// Parses a literal of `default(...)`: a quoted string, a number, `true`, `false` or `null`.
+fn parse_literal(literal: &str) -> Option<ParamValue> {
+    if let Some(text) = unquote(literal) {
+        return Some(ParamValue::String(text.to_string()));
+    }
+    if let Ok(i) = literal.parse::<i64>() {
+        return Some(ParamValue::Int(i));
+    }
+    if let Ok(x) = literal.parse::<f64>() {
+        return Some(ParamValue::Float(x));
+    }
+    match literal {
+        "true" | "True" => Some(ParamValue::Bool(true)),
+        "false" | "False" => Some(ParamValue::Bool(false)),
+        "null" | "none" | "None" => Some(ParamValue::Null),
+        _ => None,
+    }
+}
+
+/// A string split into literal text and `{{ ... }}` expressions.
+///
+/// A quoted string like `{{ '{{' }}` is literal text, which escapes braces.
+enum Part<'a> {
+    Text(&'a str),
+    Expr(Expr),
+}
+
+/// Finds the `}}` closing an expression, skipping quoted strings.
+fn find_close(source: &str) -> Option<usize> {
+    let mut quote = None;
+    for (i, c) in source.char_indices() {
+        match quote {
+            Some(q) if c == q => quote = None,
+            Some(_) => {}
+            None if c == '\'' || c == '"' => quote = Some(c),
+            None if source[i..].starts_with("}}") => return Some(i),
+            None => {}
+        }
+    }
+    None
+}
+
+fn parse(template: &str) -> Result<Vec<Part>, String> {
+    let mut parts = Vec::new();
+    let mut rest = template;
+    while let Some(start) = rest.find("{{") {
+        let end = match find_close(&rest[start + 2..]) {
+            Some(end) => start + 2 + end,
+            None => return Err(format!("unclosed '{{{{' in '{}'", template)),
+        };
+        if start > 0 {
+            parts.push(Part::Text(&rest[..start]));
+        }
+        let source = &rest[start + 2..end];
+        match unquote(source.trim()) {
+            Some(text) => parts.push(Part::Text(text)),
+            None => parts.push(Part::Expr(try!(Expr::parse(source)))),
+        }
+        rest = &rest[end + 2..];
+    }
+    if !rest.is_empty() {
+        parts.push(Part::Text(rest));
+    }
+
+    Ok(parts)
+}
+
+/// Checks the syntax of all templates in `value`.
+pub fn check(value: &ParamValue) -> Result<(), String> {
+    match *value {
+        ParamValue::String(ref s) => parse(s).map(|_| ()),
+        ParamValue::List(ref items) => items.iter().map(check).collect(),
+        ParamValue::Map(ref map) => map.values().map(check).collect(),
+        _ => Ok(()),
+    }
+}
+
+/// Replaces the templates in `value` by the variables of `host`.
+///
+/// A string consisting of a single template takes the variable's value and type, so
+/// `"{{ host.ssh_port }}"` may become an integer; otherwise the values are inserted as text.
+pub fn render(value: &ParamValue, host: &HostContext) -> Result<ParamValue, String> {
+    let template = match *value {
+        ParamValue::String(ref s) => s,
+        ParamValue::List(ref items) => {
+            let items: Result<Vec<_>, _> = items.iter().map(|item| render(item, host)).collect();
+            return items.map(ParamValue::List);
+        }
+        ParamValue::Map(ref map) => {
+            let mut rendered = BTreeMap::new();
+            for (key, value) in map {
+                rendered.insert(key.clone(), try!(render(value, host)));
+            }
+            return Ok(ParamValue::Map(rendered));
+        }
+        _ => return Ok(value.clone()),
+    };
+
+    let parts = try!(parse(template));
+    if parts.len() == 1 {
+        if let Part::Expr(ref expr) = parts[0] {
+            return expr.eval(host);
+        }
+    }
+
+    let mut text = String::new();
+    for part in parts {
+        match part {
+            Part::Text(s) => text.push_str(s),
+            Part::Expr(expr) => {
+                match try!(expr.eval(host)) {
+                    value @ ParamValue::List(_) |
+                    value @ ParamValue::Map(_) => {
+                        return Err(format!("cannot insert {} into '{}'", value, template))
+                    }
+                    value => text.push_str(&value.to_string()),
+                }
+            }
+        }
+    }
+
+    Ok(ParamValue::String(text))
+}
@@ -1,41 +1,117 @@
-use cpython::ObjectProtocol; //for call method
-use cpython::{PyBytes, PyDict, PyErr, PyObject, PyString, Python, NoArgs, ToPyObject};
-use std::collections::HashMap;
+use std::ascii;
+use std::cmp;
+use std::collections::{HashMap, VecDeque};
 use std::fmt;
-use term_painter::ToStyle;
-use term_painter::Color::*;
-use term_painter::Attr::*;
+use std::io;
+use std::sync::{Arc, Mutex};
+use std::sync::mpsc::{self, Sender};
+use std::thread;
+use std::time::Duration;
 
 use super::checks::*;
+use super::modules::{CheckModule, Payload, Registry, Response};
 use super::protocols::*;
 
-pub type Kwargs = HashMap<String, String>;
+pub use self::observer::{Observer, Silent};
+pub use self::policy::{FailurePolicy, Verdict};
+pub use self::validation::validate;
+
+mod observer;
+mod policy;
+#[cfg(feature = "python")]
+mod python;
+mod validation;
+
+pub type Kwargs = Params;
 
 pub type Results<'a> = HashMap<&'a str, (u16, u16)>;
 
+/// Maximum number of characters of a response kept in a `PropertyResult`.
+pub const MAX_RESPONSE_LEN: usize = 512;
+
+/// How long the `idle` read strategy waits for more data unless the module says otherwise.
+pub const DEFAULT_IDLE_TIMEOUT_MS: u64 = 200;
+
+/// A Python exception, captured while holding the GIL so it can be reported without it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PythonError {
+    pub class: String,
+    pub message: String,
+}
+
+impl fmt::Display for PythonError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.message.is_empty() {
+            write!(f, "{}", self.class)
+        } else {
+            write!(f, "{}: {}", self.class, self.message)
+        }
+    }
+}
+
 #[derive(Debug)]
 pub enum PropertyError {
-    FailedExecution,
-    FailedResponseCheck,
-    FailedPythonCall(PyErr),
+    FailedExecution(io::ErrorKind, String),
+    Timeout(io::ErrorKind, String),
+    /// The host refused the connection or reported the UDP port unreachable.
+    Closed(io::ErrorKind, String),
+    FailedResponseCheck(String),
+    FailedPythonCall(PythonError),
     Unclassified,
 }
 
+impl PropertyError {
+    pub fn name(&self) -> &'static str {
+        match *self {
+            PropertyError::FailedExecution(..) => "FailedExecution",
+            PropertyError::Timeout(..) => "Timeout",
+            PropertyError::Closed(..) => "Closed",
+            PropertyError::FailedResponseCheck(_) => "FailedResponseCheck",
+            PropertyError::FailedPythonCall(_) => "FailedPythonCall",
+            PropertyError::Unclassified => "Unclassified",
+        }
+    }
+
+    /// Describes what went wrong, if known.
+    pub fn reason(&self) -> Option<String> {
+        match *self {
+            PropertyError::FailedExecution(kind, ref message) |
+            PropertyError::Timeout(kind, ref message) |
+            PropertyError::Closed(kind, ref message) => Some(format!("{:?}: {}", kind, message)),
+            PropertyError::FailedResponseCheck(ref reason) => Some(reason.clone()),
+            PropertyError::FailedPythonCall(ref err) => Some(err.to_string()),
+            PropertyError::Unclassified => None,
+        }
+    }
+
+    /// Whether the property could not be checked because of dirac or Python rather than the host.
+    pub fn is_internal(&self) -> bool {
+        match *self {
+            PropertyError::FailedPythonCall(_) |
+            PropertyError::Unclassified => true,
+            _ => false,
+        }
+    }
+}
+
 impl fmt::Display for PropertyError {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        let err_name = match self {
-            &PropertyError::FailedExecution => "FailedExecution".to_string(),
-            &PropertyError::FailedResponseCheck => "FailedResponseCheck".to_string(),
-            &PropertyError::FailedPythonCall(ref err) => format!("{:?}", err),
-            &PropertyError::Unclassified => "Unclassified".to_string(),
-        };
-        write!(f, "{}", err_name)
+        match self.reason() {
+            Some(reason) => write!(f, "{}: {}", self.name(), reason),
+            None => write!(f, "{}", self.name()),
+        }
     }
 }
 
-impl From<PyErr> for PropertyError {
-    fn from(err: PyErr) -> PropertyError {
-        PropertyError::FailedPythonCall(err)
+impl From<io::Error> for PropertyError {
+    fn from(err: io::Error) -> PropertyError {
+        if is_timeout(&err) {
+            PropertyError::Timeout(err.kind(), err.to_string())
+        } else if err.kind() == io::ErrorKind::ConnectionRefused {
+            PropertyError::Closed(err.kind(), err.to_string())
+        } else {
+            PropertyError::FailedExecution(err.kind(), err.to_string())
+        }
     }
 }
 
@@ -44,6 +120,11 @@ pub struct PropertyResult<'a> {
     pub host: &'a str,
     pub property: &'a Property,
     pub result: Result<(), PropertyError>,
+    /// The beginning of the response received from the host, if any.
+    pub response: Option<String>,
+    /// The property has not been checked against the host, e.g. because of a `Filter`; `result` is
+    /// `Ok` then.
+    pub skipped: bool,
 }
 
 #[derive(Debug)]
@@ -76,321 +157,619 @@ impl<'a> CheckSuiteResult<'a> {
     }
 }
 
-pub fn run(check_suite: &CheckSuite) -> CheckSuiteResult {
-    let mut check_suite_result = CheckSuiteResult::new(check_suite);
+/// A single property to be checked against a single host.
+struct Task<'a> {
+    check_index: usize,
+    check: &'a Check,
+    host: &'a str,
+    /// The position of `property` in `check.properties`.
+    property_index: usize,
+    property: &'a Property,
+    skipped: bool,
+}
 
-    let gil = Python::acquire_gil();
-    let py = gil.python();
+/// An owned copy of a `Task` that can be handed to a worker thread.
+struct Job {
+    index: usize,
+    host: String,
+    /// Where to reach `host`.
+    address: String,
+    /// The property with its params rendered for `host`, or why that failed.
+    property: Result<Property, String>,
+    /// `None` if the property's module is left to Python.
+    module: Option<Arc<CheckModule>>,
+    timeouts: Timeouts,
+}
 
-    let sys = py.import("sys").unwrap();
-    let version: String = sys.get(py, "version").unwrap().extract(py).unwrap();
-    info!("* Running Pythion '{}'.", version);
+/// What a worker reports back about the job with the given index.
+enum Message {
+    Started(usize),
+    Finished(usize, Result<(), PropertyError>, Option<String>),
+}
 
-    for check in &check_suite.checks {
-        println!("CHECKING [{}]", Bold.paint(&check.inventory_name));
-        let mut check_result = CheckResult::new(&check);
+/// Runs check suites with the modules of its `Registry`, leaving all other modules to Python.
+#[derive(Clone, Default)]
+pub struct Engine {
+    modules: Registry,
+}
 
-        for property in &check.properties {
-            println!("  PROPERTY: {} [{}:{}]",
-                     property.name,
-                     Bold.paint(&property.module),
-                     &property.params.get("port").unwrap());
-            for host in check_suite.inventory.get(&check.inventory_name).unwrap() {
-                debug!("+ Running: '{}' with module '{}' and params '{:?}' for host '{}'.",
-                       property.name,
-                       property.module,
-                       property.params,
-                       host);
-                let result = execute_module(py, host, &property);
-
-                match result {
-                    Ok(_) => println!("    {:>11}: [{}]", Green.paint("Success"), host),
-                    Err(ref err) => {
-                        match *err {
-                            PropertyError::FailedExecution => {
-                                println!("    {:>11}: [{}]", Red.paint("Failed (E)"), host)
-                            }
-                            PropertyError::FailedResponseCheck => {
-                                println!("    {:>11}: [{}]", Red.paint("Failed (R)"), host)
-                            }
-                            PropertyError::FailedPythonCall(ref py_err) => {
-                                println!("    {:>11}: [{}]", Red.paint("Failed (P)"), host);
-                                // TODO: Make me configurable
-                                if true {
-                                    // TODO: Destructure and pretty print me
-                                    println!("{:?}", py_err);
-                                }
-                            }
-                            PropertyError::Unclassified => {
-                                println!("    {:>11}: [{}]", Red.paint("Failed (?)"), host)
-                            }
-                        }
-                    }
-                }
+impl Engine {
+    /// An engine with the built-in modules.
+    pub fn new() -> Engine {
+        Engine::with_registry(Registry::new())
+    }
 
-                let property_result = PropertyResult {
-                    host: host,
-                    property: &property,
-                    result: result,
-                };
+    pub fn with_registry(modules: Registry) -> Engine {
+        Engine { modules: modules }
+    }
 
-                check_result.results.push(property_result);
-            }
+    /// Makes `module` available to suites as `name`; cf. `Registry::register`.
+    pub fn register<M: CheckModule + 'static>(&mut self, name: &str, module: M) -> &mut Engine {
+        self.modules.register(name, module);
+        self
+    }
 
-        }
-        check_suite_result.results.push(check_result);
-        println!("");
+    pub fn registry(&self) -> &Registry {
+        &self.modules
     }
 
-    check_suite_result
-}
+    /// Runs all checks of `check_suite` using `settings.jobs` worker threads.
+    ///
+    /// Registered modules run natively; other modules run in Python, holding the GIL only while
+    /// talking to the module, so slow or unreachable hosts do not block the other workers. Results
+    /// are returned in suite order regardless of the order in which the workers finish.
+    pub fn run<'a>(&self, check_suite: &'a CheckSuite) -> CheckSuiteResult<'a> {
+        self.run_observed(check_suite, &mut Silent)
+    }
 
-trait ToData<T> {
-    fn to_data(py: Python, po: PyObject) -> Option<T>;
-}
+    /// Runs `check_suite` like `run` and reports its progress to `observer`.
+    pub fn run_observed<'a>(&self,
+                            check_suite: &'a CheckSuite,
+                            observer: &mut Observer)
+                            -> CheckSuiteResult<'a> {
+        run_with(&self.modules, check_suite, observer)
+    }
 
-impl<'a> ToData<NoData> for TcpConnect<'a> {
-    #[allow(unused_variables)]
-    fn to_data(py: Python, po: PyObject) -> Option<NoData> {
-        None
+    /// Checks `check_suite` without touching the network; cf. `validate`.
+    pub fn validate(&self, check_suite: &CheckSuite) -> Vec<SuiteError> {
+        validation::validate_with(&self.modules, check_suite)
     }
-}
 
-impl<'a> ToData<Vec<u8>> for TcpRaw<'a> {
-    fn to_data(py: Python, po: PyObject) -> Option<Vec<u8>> {
-        let py_none = py.None();
-        if po != py_none {
-            let b = po.extract::<PyBytes>(py).unwrap();
-            let s = b.data(py);
-            let v = From::from(&s[..]);
-            Some(v)
-        } else {
-            None
+    /// Returns an error for every property whose module is not registered if dirac has been built
+    /// without Python support; such properties could only fail.
+    pub fn check_module_support(&self, check_suite: &CheckSuite) -> Vec<SuiteError> {
+        if cfg!(feature = "python") {
+            return Vec::new();
         }
-    }
-}
 
-impl<'a> ToData<String> for TcpText<'a> {
-    fn to_data(py: Python, po: PyObject) -> Option<String> {
-        let py_none = py.None();
-        if po != py_none {
-            let s = string_from(py, po);
-            Some(s)
-        } else {
-            None
+        let mut errors = Vec::new();
+        for check in &check_suite.checks {
+            for property in &check.properties {
+                if !self.modules.contains(&property.module) {
+                    let reason = format!("property '{}' [{}]: {}",
+                                         property.name,
+                                         property.module,
+                                         python_unavailable(&property.module));
+                    errors.push(SuiteError::at_position(property.position, reason));
+                }
+            }
         }
+
+        errors
     }
 }
 
-impl<'a> ToData<String> for UdpText<'a> {
-    fn to_data(py: Python, po: PyObject) -> Option<String> {
-        let py_none = py.None();
-        if po != py_none {
-            let s = string_from(py, po);
-            Some(s)
-        } else {
-            None
-        }
-    }
+/// Runs `check_suite` with the built-in modules; cf. `Engine::run`.
+pub fn run(check_suite: &CheckSuite) -> CheckSuiteResult {
+    Engine::new().run(check_suite)
 }
 
-impl<'a> ToData<String> for TcpHttp<'a> {
-    fn to_data(py: Python, po: PyObject) -> Option<String> {
-        let py_none = py.None();
-        if po != py_none {
-            let s = string_from(py, po);
-            Some(s)
-        } else {
-            None
+/// Cf. `Engine::check_module_support`.
+pub fn check_module_support(check_suite: &CheckSuite) -> Vec<SuiteError> {
+    Engine::new().check_module_support(check_suite)
+}
+
+fn run_with<'a>(modules: &Registry,
+                check_suite: &'a CheckSuite,
+                observer: &mut Observer)
+                -> CheckSuiteResult<'a> {
+    observer.suite_started(check_suite);
+
+    let tasks = create_tasks(check_suite);
+    let jobs: VecDeque<Job> = tasks.iter()
+                                   .enumerate()
+                                   .filter(|&(_, task)| !task.skipped)
+                                   .map(|(index, task)| {
+                                       Job {
+                                           index: index,
+                                           host: task.host.to_string(),
+                                           address: check_suite.address_of(task.host).to_string(),
+                                           property: check_suite.property_for(task.property,
+                                                                              task.host),
+                                           module: modules.get(&task.property.module),
+                                           timeouts: timeouts_for(&check_suite.settings,
+                                                                  task.property),
+                                       }
+                                   })
+                                   .collect();
+    let runnable = jobs.len();
+    let queue = Arc::new(Mutex::new(jobs));
+    let (tx, rx) = mpsc::channel::<Message>();
+
+    let workers = cmp::max(1, cmp::min(check_suite.settings.jobs.unwrap_or(1), runnable));
+    info!("* Running {} tasks with {} worker(s), skipping {}.",
+          runnable,
+          workers,
+          tasks.len() - runnable);
+    let mut handles = Vec::with_capacity(workers);
+    for _ in 0..workers {
+        let queue = queue.clone();
+        let tx = tx.clone();
+        handles.push(thread::spawn(move || worker(queue, tx)));
+    }
+    drop(tx);
+
+    let mut results: Vec<Option<PropertyResult>> = tasks.iter()
+                                                        .map(|task| {
+                                                            if task.skipped {
+                                                                Some(property_result(task, Ok(()), None))
+                                                            } else {
+                                                                None
+                                                            }
+                                                        })
+                                                        .collect();
+    let mut next = 0;
+    for message in rx.iter() {
+        match message {
+            Message::Started(index) => {
+                let task = &tasks[index];
+                observer.property_started(task.check, task.property, task.host);
+            }
+            Message::Finished(index, result, response) => {
+                results[index] = Some(property_result(&tasks[index], result, response));
+                while next < tasks.len() && results[next].is_some() {
+                    report_result(observer, &tasks, next, results[next].as_ref().unwrap());
+                    next += 1;
+                }
+            }
         }
     }
-}
+    for handle in handles {
+        let _ = handle.join();
+    }
 
-impl<'a> ToData<String> for TcpHttps<'a> {
-    fn to_data(py: Python, po: PyObject) -> Option<String> {
-        let py_none = py.None();
-        if po != py_none {
-            let s = string_from(py, po);
-            Some(s)
-        } else {
-            None
+    // Reports what is left: skipped properties if no job has been run at all and jobs of a worker
+    // that panicked, which never finish.
+    for (index, (task, result)) in tasks.iter().zip(results.iter_mut()).enumerate().skip(next) {
+        if result.is_none() {
+            *result = Some(property_result(task, Err(PropertyError::Unclassified), None));
         }
+        report_result(observer, &tasks, index, result.as_ref().unwrap());
     }
-}
 
+    let mut check_suite_result = CheckSuiteResult::new(check_suite);
+    check_suite_result.results = check_suite.checks.iter().map(CheckResult::new).collect();
+    for (task, result) in tasks.iter().zip(results.into_iter()) {
+        check_suite_result.results[task.check_index].results.push(result.unwrap());
+    }
 
-fn string_from(py: Python, po: PyObject) -> String {
-    let s = po.extract::<PyString>(py).unwrap().to_string(py).unwrap().to_string();
-    s
+    observer.suite_finished(&check_suite_result);
+    check_suite_result
 }
 
-trait ToDict {
-    fn to_dict(py: Python, response: Self) -> PyDict;
+fn property_result<'a>(task: &Task<'a>,
+                       result: Result<(), PropertyError>,
+                       response: Option<String>)
+                       -> PropertyResult<'a> {
+    PropertyResult {
+        host: task.host,
+        property: task.property,
+        result: result,
+        response: response,
+        skipped: task.skipped,
+    }
 }
 
-impl ToDict for TcpConnectResponse {
-    #[allow(unused_variables)]
-    fn to_dict(py: Python, response: TcpConnectResponse) -> PyDict {
-        PyDict::new(py)
+/// Passes the result of `tasks[index]` to `observer`, preceded by the start of its check and
+/// property if it is their first result.
+fn report_result(observer: &mut Observer, tasks: &[Task], index: usize, result: &PropertyResult) {
+    let task = &tasks[index];
+    let new_check = index == 0 || tasks[index - 1].check_index != task.check_index;
+    if new_check {
+        observer.check_results_started(task.check);
+    }
+    if new_check || tasks[index - 1].property_index != task.property_index {
+        observer.property_results_started(task.check, task.property);
     }
+    observer.property_finished(task.check, result);
 }
 
-impl ToDict for TcpRawResponse {
-    fn to_dict(py: Python, response: TcpRawResponse) -> PyDict {
-        let TcpRawResponse(response) = response;
-        let py_dict = PyDict::new(py);
-        let py_bytes: PyBytes = PyBytes::new(py, &response);
-        let _ = py_dict.set_item(py, "response", py_bytes);
-        py_dict
+fn create_tasks(check_suite: &CheckSuite) -> Vec<Task> {
+    let mut tasks = Vec::new();
+
+    for (check_index, check) in check_suite.checks.iter().enumerate() {
+        for (property_index, property) in check.properties.iter().enumerate() {
+            for host in &check.hosts {
+                tasks.push(Task {
+                    check_index: check_index,
+                    check: check,
+                    host: host,
+                    property_index: property_index,
+                    property: property,
+                    skipped: property.skipped_hosts.contains(host),
+                });
+            }
+        }
     }
+
+    tasks
 }
 
-impl ToDict for TcpTextResponse {
-    fn to_dict(py: Python, response: TcpTextResponse) -> PyDict {
-        let TcpTextResponse(response) = response;
-        let py_dict = PyDict::new(py);
-        let py_string = response.to_py_object(py);
-        let _ = py_dict.set_item(py, "response", py_string);
-        py_dict
-    }
+fn python_unavailable(module: &str) -> String {
+    format!("module '{}' is not a native module and needs Python, but dirac has been built without the \
+             'python' feature",
+            module)
 }
 
-impl ToDict for UdpTextResponse {
-    fn to_dict(py: Python, response: UdpTextResponse) -> PyDict {
-        let UdpTextResponse(response) = response;
-        let py_dict = PyDict::new(py);
-        let py_string = response.to_py_object(py);
-        let _ = py_dict.set_item(py, "response", py_string);
-        py_dict
+/// Stands in for the Python bridge in builds without the `python` feature.
+#[cfg(not(feature = "python"))]
+mod python {
+    use std::io;
+
+    use super::super::checks::Property;
+    use super::super::protocols::Timeouts;
+    use super::{python_unavailable, PropertyError};
+
+    pub fn execute(_: &str,
+                   _: &str,
+                   property: &Property,
+                   _: Timeouts,
+                   _: &mut Option<String>)
+                   -> Result<(), PropertyError> {
+        Err(PropertyError::FailedExecution(io::ErrorKind::InvalidInput,
+                                           python_unavailable(&property.module)))
     }
-}
 
-impl ToDict for TcpHttpTextResponse {
-    fn to_dict(py: Python, response: TcpHttpTextResponse) -> PyDict {
-        let TcpHttpTextResponse(response) = response;
-        let py_dict = PyDict::new(py);
-        let py_response_code = response.response_code.to_py_object(py);
-        let _ = py_dict.set_item(py, "response_code", py_response_code);
-        // TODO: Implement me
-        // let py_headers = self.data.headers.to_py_object(py);
-        // py_dict.set_item(py, "headers", py_headers);
-        let _ = py_dict.set_item(py, "headers", py.None());
-        let py_body = response.body.to_py_object(py);
-        let _ = py_dict.set_item(py, "body", py_body);
-        py_dict
+    pub fn validate_property(property: &Property) -> Result<(), String> {
+        Err(python_unavailable(&property.module))
     }
 }
 
-impl ToDict for TcpHttpsTextResponse {
-    fn to_dict(py: Python, response: TcpHttpsTextResponse) -> PyDict {
-        let TcpHttpsTextResponse(response) = response;
-        let py_dict = PyDict::new(py);
-        let py_response_code = response.response_code.to_py_object(py);
-        let _ = py_dict.set_item(py, "response_code", py_response_code);
-        // TODO: Implement me
-        // let py_headers = self.data.headers.to_py_object(py);
-        // py_dict.set_item(py, "headers", py_headers);
-        let _ = py_dict.set_item(py, "headers", py.None());
-        let py_body = response.body.to_py_object(py);
-        let _ = py_dict.set_item(py, "body", py_body);
-        py_dict
+/// Combines the suite's and the property's timeouts; the property's take precedence.
+fn timeouts_for(settings: &Settings, property: &Property) -> Timeouts {
+    let mut timeouts = Timeouts::default();
+    if let Some(ms) = property.timeout_ms.or(settings.timeout_ms) {
+        timeouts.timeout = Duration::from_millis(ms);
     }
+    if let Some(ms) = property.connect_timeout_ms.or(settings.connect_timeout_ms) {
+        timeouts.connect_timeout = Duration::from_millis(ms);
+    }
+    if let Some(retries) = property.retries.or(settings.retries) {
+        timeouts.retries = retries;
+    }
+
+    timeouts
 }
 
-fn execute_module<'a>(py: Python, host: &str, property: &Property) -> Result<(), PropertyError> {
-    let module_name = format!("dirac.{}", &property.module);
-    let import = try!(py.import(&module_name));
-    let module: PyObject = try!(import.get(py, "Module"));
-    info!("* Loaded module '{}'.", &module_name);
+fn worker(queue: Arc<Mutex<VecDeque<Job>>>, tx: Sender<Message>) {
+    loop {
+        let job = match queue.lock() {
+            Ok(mut jobs) => jobs.pop_front(),
+            Err(_) => None,
+        };
+        let job = match job {
+            Some(job) => job,
+            None => break,
+        };
+
+        if tx.send(Message::Started(job.index)).is_err() {
+            break;
+        }
+        let mut response = None;
+        let (host, address, timeouts) = (&job.host, &job.address, job.timeouts);
+        let result = match (job.property, job.module) {
+            (Err(err), _) => {
+                Err(PropertyError::FailedExecution(io::ErrorKind::InvalidInput,
+                                                   format!("invalid params: {}", err)))
+            }
+            (Ok(ref property), ref module) => {
+                debug!("+ Running: '{}' with module '{}' and params '{:?}' for host '{}'.",
+                       property.name,
+                       property.module,
+                       property.params,
+                       host);
+                match *module {
+                    Some(ref module) => {
+                        execute_native(&**module, host, address, property, timeouts, &mut response)
+                    }
+                    None => python::execute(host, address, property, timeouts, &mut response),
+                }
+            }
+        };
+
+        if tx.send(Message::Finished(job.index, result, response)).is_err() {
+            break;
+        }
+    }
+}
 
-    let protocol_fn = try!(module.getattr(py, "protocol"));
-    let protocol: String = try!(protocol_fn.call(py, NoArgs, None)).extract(py).unwrap();
+fn execute_native(module: &CheckModule,
+                  host: &str,
+                  address: &str,
+                  property: &Property,
+                  timeouts: Timeouts,
+                  response: &mut Option<String>)
+                  -> Result<(), PropertyError> {
+    let params = &property.params;
+    try!(module.check_args(params).map_err(|err| {
+        PropertyError::FailedExecution(io::ErrorKind::InvalidInput, format!("invalid params: {}", err))
+    }));
+
+    let protocol = try!(with_tls(module.protocol(params), property));
     debug!("- Module protocol is '{}'.", protocol);
+    let read = module.read_strategy(params);
+    debug!("- Module read strategy is '{:?}'.", read);
+
+    let payload = module.challenge(params);
+    let result = try!(exchange(&protocol, host, address, property, timeouts, read, payload));
+    *response = excerpt(&result);
+
+    info!("* Checking response.");
+    module.check_response(params, &result).map_err(PropertyError::FailedResponseCheck)
+}
 
-    let check_args_fn = try!(module.getattr(py, "check_args"));
-    let check_args: bool = try!(check_args_fn.call(py,
-                                                   NoArgs,
-                                                   Some(&property.params.to_py_object(py))))
-                               .extract(py)
-                               .unwrap();
-    debug!("- Module check args is '{}'.", check_args);
-
-    let instance: PyObject = try!(module.call(py, NoArgs, Some(&property.params.to_py_object(py))))
-                                 .extract(py)
-                                 .unwrap();
-    debug!("- Module instance is '{}'.", instance);
-
-    let py_challenge: PyObject = try!(instance.call_method(py, "challenge", NoArgs, None));
-    let port = property.params["port"].parse::<u16>().unwrap();
-    let result = match &protocol[..] {
+fn port_of(property: &Property) -> Result<u16, PropertyError> {
+    match property.params.get("port").and_then(|p| p.as_port()) {
+        Some(port) => Ok(port),
+        None => {
+            Err(PropertyError::FailedExecution(io::ErrorKind::InvalidInput,
+                                               "missing or invalid param 'port'".to_string()))
+        }
+    }
+}
+
+/// Runs `protocol` against `host`, reachable at `address`, and returns what has been received.
+fn exchange(protocol: &str,
+            host: &str,
+            address: &str,
+            property: &Property,
+            timeouts: Timeouts,
+            read: ReadStrategy,
+            payload: Payload)
+            -> Result<Response, PropertyError> {
+    info!("* Running protocol '{}'.", protocol);
+    let port = try!(port_of(property));
+
+    let response = match protocol {
         "connect/tcp" => {
-            let p = TcpConnect::new(host, port);
-            try!(run_protocol(py, p, instance, None))
+            let mut p = TcpConnect::new(address, port);
+            p.set_timeouts(timeouts);
+            try!(p.send_challenge());
+            Response::Connected
         }
         "raw/tcp" => {
-            let p = TcpRaw::new(host, port);
-            let challenge = TcpRaw::to_data(py, py_challenge);
-            try!(run_protocol(py, p, instance, challenge))
+            let mut p = TcpRaw::new(address, port);
+            p.set_timeouts(timeouts);
+            p.set_read_strategy(read);
+            if let Some(bytes) = bytes_of(payload) {
+                p.set_data(bytes);
+            }
+            let TcpRawResponse(response) = try!(p.send_challenge());
+            Response::Bytes(response)
         }
         "text/tcp" => {
-            let p = TcpText::new(host, port);
-            let challenge = TcpText::to_data(py, py_challenge);
-            try!(run_protocol(py, p, instance, challenge))
+            let mut p = TcpText::new(address, port);
+            p.set_timeouts(timeouts);
+            p.set_read_strategy(read);
+            if let Some(text) = text_of(payload) {
+                p.set_data(text);
+            }
+            let TcpTextResponse(response) = try!(p.send_challenge());
+            Response::Text(response)
         }
         "text/udp" => {
-            let p = UdpText::new(host, port);
-            let challenge = UdpText::to_data(py, py_challenge);
-            try!(run_protocol(py, p, instance, challenge))
+            let mut p = UdpText::new(address, port);
+            p.set_timeouts(timeouts);
+            p.set_read_strategy(read);
+            if let Some(text) = text_of(payload) {
+                p.set_data(text);
+            }
+            let UdpTextResponse(response) = try!(p.send_challenge());
+            Response::Text(response)
+        }
+        "dialog/tcp" => {
+            let mut p = TcpDialog::new(address, port);
+            p.set_timeouts(timeouts);
+            if let Payload::Dialog(steps) = payload {
+                p.set_data(steps);
+            }
+            let TcpDialogResponse(transcript) = try!(p.send_challenge());
+            Response::Dialog(transcript)
+        }
+        "tls/raw" => {
+            let mut p = TlsRaw::new(address, port);
+            p.set_timeouts(timeouts);
+            p.set_read_strategy(read);
+            p.set_data(TlsData {
+                server_name: server_name_of(property),
+                payload: bytes_of(payload),
+            });
+            let TlsRawResponse(response) = try!(p.send_challenge());
+            Response::TlsBytes(response)
+        }
+        "tls/tcp" => {
+            let mut p = TlsText::new(address, port);
+            p.set_timeouts(timeouts);
+            p.set_read_strategy(read);
+            p.set_data(TlsData {
+                server_name: server_name_of(property),
+                payload: text_of(payload),
+            });
+            let TlsTextResponse(response) = try!(p.send_challenge());
+            Response::TlsText(response)
+        }
+        starttls if starttls.starts_with("starttls/") => {
+            let mut p = StartTlsText::new(address, port);
+            p.set_timeouts(timeouts);
+            p.set_read_strategy(read);
+            p.set_data(StartTlsData {
+                service: try!(starttls["starttls/".len()..].parse()),
+                server_name: server_name_of(property),
+                payload: text_of(payload),
+            });
+            let StartTlsTextResponse(response) = try!(p.send_challenge());
+            Response::StartTls(response)
+        }
+        "dns/udp" => {
+            let mut p = DnsUdp::new(address, port);
+            p.set_timeouts(timeouts);
+            if let Payload::Dns(query) = payload {
+                p.set_data(query);
+            }
+            let DnsUdpResponse(response) = try!(p.send_challenge());
+            Response::Dns(response)
+        }
+        "dns/tcp" => {
+            let mut p = DnsTcp::new(address, port);
+            p.set_timeouts(timeouts);
+            if let Payload::Dns(query) = payload {
+                p.set_data(query);
+            }
+            let DnsTcpResponse(response) = try!(p.send_challenge());
+            Response::Dns(response)
         }
         "http/tcp" => {
-            let p = TcpHttp::new(host, port);
-            let challenge = TcpHttp::to_data(py, py_challenge);
-            try!(run_protocol(py, p, instance, challenge))
+            let mut p = TcpHttp::new(address, port);
+            p.set_timeouts(timeouts);
+            p.set_read_strategy(read);
+            if let Payload::Http(request) = payload {
+                p.set_data(with_server_name(request, host, address));
+            }
+            let TcpHttpTextResponse(response) = try!(p.send_challenge());
+            Response::Http(response)
         }
         "https/tcp" => {
-            let p = TcpHttps::new(host, port);
-            let challenge = TcpHttps::to_data(py, py_challenge);
-            try!(run_protocol(py, p, instance, challenge))
+            let mut p = TcpHttps::new(address, port);
+            p.set_timeouts(timeouts);
+            p.set_read_strategy(read);
+            if let Payload::Http(request) = payload {
+                p.set_data(with_server_name(request, host, address));
+            }
+            let TcpHttpsTextResponse(response) = try!(p.send_challenge());
+            Response::Http(response)
+        }
+        unknown => {
+            return Err(PropertyError::FailedExecution(io::ErrorKind::InvalidInput,
+                                                      format!("unknown protocol '{}'", unknown)))
         }
-        unknown => panic!("Unknown protocol '{}'.", unknown),
     };
-    debug!("- Module response check is '{}'.", result);
+    debug!("- Received response from '{}/{}'.", address, port);
 
-    return if result {
-        Ok(())
-    } else {
-        Err(PropertyError::FailedExecution)
+    Ok(response)
+}
+
+/// Text payloads are sent as UTF-8 by the protocols reading raw bytes.
+fn bytes_of(payload: Payload) -> Option<Vec<u8>> {
+    match payload {
+        Payload::Bytes(bytes) => Some(bytes),
+        Payload::Text(text) => Some(text.into_bytes()),
+        _ => None,
+    }
+}
+
+fn text_of(payload: Payload) -> Option<String> {
+    match payload {
+        Payload::Text(text) => Some(text),
+        Payload::Bytes(bytes) => Some(String::from_utf8_lossy(&bytes).into_owned()),
+        _ => None,
+    }
+}
+
+/// Renders a response as text for reports.
+fn excerpt(response: &Response) -> Option<String> {
+    let excerpt = match *response {
+        Response::Connected => return None,
+        Response::Bytes(ref bytes) => escape(bytes),
+        Response::Text(ref text) => text.clone(),
+        Response::TlsBytes(ref response) => format!("{}\n{}", response.tls, escape(&response.response)),
+        Response::TlsText(ref response) => format!("{}\n{}", response.tls, response.response),
+        Response::StartTls(ref response) => {
+            format!("{}\n{}{}", response.tls, response.banner, response.response)
+        }
+        Response::Dialog(ref transcript) => transcript.to_string(),
+        Response::Http(ref response) => http_excerpt(response),
+        Response::Dns(ref response) => response.to_string(),
     };
+
+    Some(truncate(&excerpt, MAX_RESPONSE_LEN))
 }
 
-fn run_protocol<'a, S, T, V, P>(py: Python,
-                                mut p: P,
-                                instance: PyObject,
-                                data: Option<T>)
-                                -> Result<bool, PropertyError>
-    where V: ToDict,
-          P: Protocol<'a, S, T, V> + ToData<T>
-{
-    info!("* Running protocol.");
-
-    if data.is_some() {
-        p.set_data(data.unwrap());
-    }
-    debug!("- Sending challenge.", );
-    if let Ok(response) = p.send_challenge() {
-        debug!("- Challenge sent.", );
-        let kwargs = ToDict::to_dict(py, response);
-        Ok(try!(check_response(py, &instance, &kwargs)))
-    } else {
-        Err(PropertyError::FailedExecution)
+fn escape(bytes: &[u8]) -> String {
+    let escaped: Vec<u8> = bytes.iter()
+                                .flat_map(|b| ascii::escape_default(*b))
+                                .collect();
+    String::from_utf8_lossy(&escaped).into_owned()
+}
+
+fn http_excerpt(response: &HttpResponse<String>) -> String {
+    let mut excerpt = format!("{}\n", response.response_code);
+    let mut headers: Vec<_> = response.headers.iter().collect();
+    headers.sort();
+    for (name, value) in headers {
+        excerpt.push_str(&format!("{}: {}\n", name, value));
+    }
+    excerpt.push_str(&format!("\n{}", response.body));
+    if response.body_truncated {
+        excerpt.push_str(&format!("\n[body truncated after {} bytes]", response.body.len()));
+    }
+    match response.tls {
+        Some(ref tls) => format!("{}\n{}", tls, excerpt),
+        None => excerpt,
     }
 }
 
-fn check_response(py: Python, instance: &PyObject, response: &PyDict) -> Result<bool, PyErr> {
-    info!("* Checking response.");
+/// Switches `text/tcp` and `raw/tcp` to their TLS counterparts if the property asks for TLS.
+fn with_tls(protocol: String, property: &Property) -> Result<String, PropertyError> {
+    if property.tls.is_none() {
+        return Ok(protocol);
+    }
+    match tls_protocol(&protocol) {
+        Some(tls_protocol) => Ok(tls_protocol),
+        None => {
+            let reason = format!("protocol '{}' cannot be run over TLS", protocol);
+            Err(PropertyError::FailedExecution(io::ErrorKind::InvalidInput, reason))
+        }
+    }
+}
 
-    let r: bool = try!(instance.call_method(py, "check_response", NoArgs, Some(response)))
-                      .extract(py)
-                      .unwrap();
-    Ok(r)
+fn tls_protocol(protocol: &str) -> Option<String> {
+    match protocol {
+        "text/tcp" | "tls/tcp" => Some("tls/tcp".to_string()),
+        "raw/tcp" | "tls/raw" => Some("tls/raw".to_string()),
+        starttls if starttls.starts_with("starttls/") => Some(starttls.to_string()),
+        _ => None,
+    }
+}
+
+/// Sends `host` in the Host header and via SNI when connecting to an address of its own, like
+/// `property_for` does for TLS properties.
+fn with_server_name(mut request: HttpRequest, host: &str, address: &str) -> HttpRequest {
+    if request.server_name.is_none() && address != host {
+        request.server_name = Some(host.to_string());
+    }
+    request
+}
+
+fn server_name_of(property: &Property) -> Option<String> {
+    property.tls.as_ref().and_then(|tls| tls.server_name.clone())
+}
+
+fn truncate(s: &str, max_len: usize) -> String {
+    if s.chars().count() <= max_len {
+        s.to_string()
+    } else {
+        let mut truncated: String = s.chars().take(max_len).collect();
+        truncated.push_str("...");
+        truncated
+    }
 }
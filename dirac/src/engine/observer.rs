@@ -0,0 +1,34 @@
+use super::super::checks::{Check, CheckSuite, Property};
+use super::{CheckSuiteResult, PropertyResult};
+
+/// Receives the events of a running check suite.
+///
+/// All methods are called on the thread that called `Engine::run_observed` and default to doing
+/// nothing, so an observer only implements the events it is interested in.
+pub trait Observer {
+    /// Called before any property of `check_suite` is checked.
+    fn suite_started(&mut self, _check_suite: &CheckSuite) {}
+
+    /// Called when a worker starts checking `property` of `check` against `host`. With more than
+    /// one worker, properties are started in no particular order.
+    fn property_started(&mut self, _check: &Check, _property: &Property, _host: &str) {}
+
+    /// Called before the first result of `check` is reported.
+    fn check_results_started(&mut self, _check: &Check) {}
+
+    /// Called before the first result of `property` of `check` is reported.
+    fn property_results_started(&mut self, _check: &Check, _property: &Property) {}
+
+    /// Called with the result of a property of `check`. Results are reported in suite order
+    /// regardless of the order in which the workers finish.
+    fn property_finished(&mut self, _check: &Check, _result: &PropertyResult) {}
+
+    /// Called with the results of all properties once the suite has been checked.
+    fn suite_finished(&mut self, _check_suite_result: &CheckSuiteResult) {}
+}
+
+/// An observer that ignores all events.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Silent;
+
+impl Observer for Silent {}
@@ -0,0 +1,72 @@
+use std::cmp;
+
+use super::{CheckSuiteResult, PropertyResult};
+
+/// How a run turned out as a whole; later variants are worse.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Verdict {
+    /// No property failed fatally.
+    Passed,
+    /// At least one property failed fatally.
+    Failed,
+    /// At least one property could not be checked because of a Python or internal error.
+    InternalError,
+}
+
+/// Decides which failed properties fail a run.
+///
+/// By default every failure is fatal. Failures of some classes, e.g. `Timeout`, or of properties
+/// with some tags, e.g. `warning`, may be declared non-fatal. Python and internal errors always
+/// make a run an `InternalError`.
+#[derive(Debug, Clone, Default)]
+pub struct FailurePolicy {
+    non_fatal_errors: Vec<String>,
+    non_fatal_tags: Vec<String>,
+}
+
+impl FailurePolicy {
+    pub fn new() -> FailurePolicy {
+        FailurePolicy::default()
+    }
+
+    /// Treats failures named `name` as non-fatal; cf. `PropertyError::name`.
+    pub fn non_fatal_error(&mut self, name: &str) -> &mut FailurePolicy {
+        self.non_fatal_errors.push(name.to_string());
+        self
+    }
+
+    /// Treats failures of properties tagged `tag` as non-fatal.
+    pub fn non_fatal_tag(&mut self, tag: &str) -> &mut FailurePolicy {
+        self.non_fatal_tags.push(tag.to_string());
+        self
+    }
+
+    pub fn is_fatal(&self, property_result: &PropertyResult) -> bool {
+        let err = match property_result.result {
+            Ok(_) => return false,
+            Err(ref err) => err,
+        };
+        if err.is_internal() {
+            return true;
+        }
+
+        !self.non_fatal_errors.iter().any(|name| name == err.name()) &&
+        !property_result.property.tags.iter().any(|tag| self.non_fatal_tags.contains(tag))
+    }
+
+    pub fn verdict(&self, check_suite_result: &CheckSuiteResult) -> Verdict {
+        let mut verdict = Verdict::Passed;
+        for check_result in &check_suite_result.results {
+            for property_result in &check_result.results {
+                let property_verdict = match property_result.result {
+                    Err(ref err) if err.is_internal() => Verdict::InternalError,
+                    _ if self.is_fatal(property_result) => Verdict::Failed,
+                    _ => Verdict::Passed,
+                };
+                verdict = cmp::max(verdict, property_verdict);
+            }
+        }
+
+        verdict
+    }
+}
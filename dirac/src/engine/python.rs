@@ -0,0 +1,520 @@
+//! The bridge to check modules written in Python.
+//!
+//! The GIL is only held while talking to the module; the network exchange runs without it, so
+//! slow or unreachable hosts do not block the other workers.
+
+use cpython::ObjectProtocol; //for call method
+use cpython::{PyBytes, PyDict, PyErr, PyList, PyObject, PyString, Python, PythonObject, NoArgs, ToPyObject};
+use std::cmp;
+use std::io;
+use std::sync::{Once, ONCE_INIT};
+use std::time::Duration;
+
+use super::super::checks::*;
+use super::super::modules::{Payload, Response};
+use super::super::protocols::*;
+use super::{exchange, excerpt, tls_protocol, with_tls, PropertyError, PythonError,
+            DEFAULT_IDLE_TIMEOUT_MS};
+
+static LOG_VERSION: Once = ONCE_INIT;
+
+impl PythonError {
+    pub fn new(py: Python, mut err: PyErr) -> PythonError {
+        let class = err.get_type(py).name(py).into_owned();
+        let message = match err.instance(py).str(py) {
+            Ok(message) => message.to_string_lossy(py).into_owned(),
+            Err(_) => String::new(),
+        };
+
+        PythonError {
+            class: class,
+            message: message,
+        }
+    }
+}
+
+impl From<PyErr> for PropertyError {
+    fn from(err: PyErr) -> PropertyError {
+        // The GIL is already held wherever Python calls fail; acquiring it again is cheap.
+        let gil = Python::acquire_gil();
+        PropertyError::FailedPythonCall(PythonError::new(gil.python(), err))
+    }
+}
+
+impl ToPyObject for ParamValue {
+    type ObjectType = PyObject;
+
+    fn to_py_object(&self, py: Python) -> PyObject {
+        match *self {
+            ParamValue::Null => py.None(),
+            ParamValue::Bool(b) => b.to_py_object(py).into_object(),
+            ParamValue::Int(i) => i.to_py_object(py).into_object(),
+            ParamValue::Float(x) => x.to_py_object(py).into_object(),
+            ParamValue::String(ref s) => s.to_py_object(py).into_object(),
+            ParamValue::List(ref l) => l.to_py_object(py).into_object(),
+            ParamValue::Map(ref m) => {
+                let py_dict = PyDict::new(py);
+                for (k, v) in m {
+                    let _ = py_dict.set_item(py, k, v);
+                }
+                py_dict.into_object()
+            }
+        }
+    }
+}
+
+pub fn load_module(py: Python, property: &Property) -> Result<PyObject, PyErr> {
+    let module_name = format!("dirac.{}", &property.module);
+    let import = try!(py.import(&module_name));
+    let module: PyObject = try!(import.get(py, "Module"));
+    info!("* Loaded module '{}'.", &module_name);
+
+    Ok(module)
+}
+
+/// Checks `property` against `host`, reachable at `address`, with the Python module `dirac.<module>`.
+pub fn execute(host: &str,
+               address: &str,
+               property: &Property,
+               timeouts: Timeouts,
+               response: &mut Option<String>)
+               -> Result<(), PropertyError> {
+    let gil = Python::acquire_gil();
+    let py = gil.python();
+    LOG_VERSION.call_once(|| log_version(py));
+
+    let module = try!(load_module(py, property));
+
+    let check_args_fn = try!(module.getattr(py, "check_args"));
+    let check_args: bool = try!(try!(check_args_fn.call(py,
+                                                        NoArgs,
+                                                        Some(&property.params.to_py_object(py))))
+                               .extract(py));
+    debug!("- Module check args is '{}'.", check_args);
+    if !check_args {
+        return Err(PropertyError::FailedExecution(io::ErrorKind::InvalidInput,
+                                                  "invalid params: check_args returned False".to_string()));
+    }
+
+    let instance: PyObject = try!(try!(module.call(py, NoArgs, Some(&property.params.to_py_object(py))))
+                                      .extract(py));
+    debug!("- Module instance is '{}'.", instance);
+
+    // Asking the instance lets modules pick a protocol depending on their params
+    let protocol: String = try!(try!(instance.call_method(py, "protocol", NoArgs, None)).extract(py));
+    let protocol = try!(with_tls(protocol, property));
+    debug!("- Module protocol is '{}'.", protocol);
+
+    let read = try!(read_strategy_of(py, &instance));
+    debug!("- Module read strategy is '{:?}'.", read);
+
+    let py_challenge: PyObject = try!(instance.call_method(py, "challenge", NoArgs, None));
+    let payload = try!(payload_of(py, &protocol, py_challenge));
+
+    // Release the GIL while waiting for the network so other workers can talk to Python.
+    let result = try!(py.allow_threads(|| {
+        exchange(&protocol, host, address, property, timeouts, read, payload)
+    }));
+    *response = excerpt(&result);
+    let kwargs = response_dict(py, result);
+
+    let result = try!(check_response(py, &instance, &kwargs));
+    debug!("- Module response check is '{}'.", result);
+
+    if result {
+        Ok(())
+    } else {
+        Err(PropertyError::FailedResponseCheck("check_response returned False".to_string()))
+    }
+}
+
+fn log_version(py: Python) {
+    match py.import("sys").and_then(|sys| sys.get(py, "version")).and_then(|v| v.extract::<String>(py)) {
+        Ok(version) => info!("* Running Python '{}'.", version),
+        Err(err) => warn!("* Could not determine the Python version: {}", PythonError::new(py, err)),
+    }
+}
+
+/// Imports the module and calls its `check_args` with the property's params.
+pub fn validate_property(property: &Property) -> Result<(), String> {
+    let gil = Python::acquire_gil();
+    let py = gil.python();
+
+    let module: PyObject = try!(load_module(py, property).map_err(|err| {
+        format!("could not load module 'dirac.{}': {}",
+                property.module,
+                PythonError::new(py, err))
+    }));
+
+    let kwargs = property.params.to_py_object(py);
+    let check_args = try!(module.getattr(py, "check_args")
+                                .and_then(|f| f.call(py, NoArgs, Some(&kwargs)))
+                                .and_then(|r| r.extract::<bool>(py))
+                                .map_err(|err| format!("invalid params: {}", PythonError::new(py, err))));
+    debug!("- Module check args is '{}'.", check_args);
+
+    if !check_args {
+        return Err("invalid params: check_args returned False".to_string());
+    }
+
+    if property.tls.is_some() {
+        let protocol = try!(module.call(py, NoArgs, Some(&kwargs))
+                                  .and_then(|i| i.call_method(py, "protocol", NoArgs, None))
+                                  .and_then(|r| r.extract::<String>(py))
+                                  .map_err(|err| {
+                                      format!("could not get protocol: {}", PythonError::new(py, err))
+                                  }));
+        if tls_protocol(&protocol).is_none() {
+            return Err(format!("protocol '{}' cannot be run over TLS", protocol));
+        }
+    }
+
+    Ok(())
+}
+
+/// Reads what `challenge` returned in the form `protocol` expects.
+fn payload_of(py: Python, protocol: &str, po: PyObject) -> Result<Payload, PropertyError> {
+    if po == py.None() {
+        return Ok(Payload::None);
+    }
+    let payload = match protocol {
+        "raw/tcp" | "tls/raw" => Payload::Bytes(try!(bytes_from(py, po))),
+        "text/tcp" | "text/udp" | "tls/tcp" => Payload::Text(try!(string_from(py, po))),
+        starttls if starttls.starts_with("starttls/") => Payload::Text(try!(string_from(py, po))),
+        "dialog/tcp" => Payload::Dialog(try!(dialog_steps_from(py, po))),
+        "http/tcp" | "https/tcp" => try!(http_request_from(py, po)).map_or(Payload::None, Payload::Http),
+        "dns/udp" | "dns/tcp" => Payload::Dns(try!(dns_query_from(py, po))),
+        _ => Payload::None,
+    };
+
+    Ok(payload)
+}
+
+/// Asks the module how its response is to be read.
+///
+/// `read_strategy` returns `None` for a single read or a dict with the key `until` set to `first`,
+/// `delimiter` (with `delimiter`), `bytes` (with `bytes`), `idle` (with an optional `idle_timeout`
+/// in seconds) or `close`, and an optional `max_size` in bytes.
+fn read_strategy_of(py: Python, instance: &PyObject) -> Result<ReadStrategy, PropertyError> {
+    let po = try!(instance.call_method(py, "read_strategy", NoArgs, None));
+    if po == py.None() {
+        return Ok(ReadStrategy::default());
+    }
+    let invalid = |reason: &str| {
+        PropertyError::FailedExecution(io::ErrorKind::InvalidInput,
+                                       format!("invalid read strategy: {}", reason))
+    };
+    let dict = try!(po.extract::<PyDict>(py).map_err(|_| invalid("not a dict")));
+    let item = |key: &str| {
+        dict.get_item(py, key).and_then(|v| if v == py.None() { None } else { Some(v) })
+    };
+
+    let until = match item("until") {
+        Some(v) => try!(string_from(py, v)),
+        None => "first".to_string(),
+    };
+    let until = match &until[..] {
+        "first" => ReadUntil::FirstChunk,
+        "delimiter" => {
+            let delimiter = match item("delimiter") {
+                Some(v) => Some(try!(bytes_from(py, v))),
+                None => None,
+            };
+            match delimiter {
+                Some(ref delimiter) if !delimiter.is_empty() => ReadUntil::Delimiter(delimiter.clone()),
+                _ => return Err(invalid("'delimiter' must not be empty")),
+            }
+        }
+        "bytes" => {
+            match item("bytes").and_then(|v| v.extract::<usize>(py).ok()) {
+                Some(bytes) => ReadUntil::Bytes(bytes),
+                None => return Err(invalid("'bytes' must be a number")),
+            }
+        }
+        "idle" => {
+            let ms = match item("idle_timeout").map(|v| v.extract::<f64>(py)) {
+                Some(Ok(secs)) if secs.is_finite() && secs > 0.0 => cmp::max((secs * 1000.0) as u64, 1),
+                Some(_) => return Err(invalid("'idle_timeout' must be a positive number of seconds")),
+                None => DEFAULT_IDLE_TIMEOUT_MS,
+            };
+            ReadUntil::Idle(Duration::from_millis(ms))
+        }
+        "close" => ReadUntil::Close,
+        other => return Err(invalid(&format!("unknown 'until' '{}'", other))),
+    };
+    let max_size = match item("max_size") {
+        Some(v) => try!(v.extract::<usize>(py).map_err(|_| invalid("'max_size' must be a number"))),
+        None => DEFAULT_MAX_SIZE,
+    };
+
+    Ok(ReadStrategy {
+        until: until,
+        max_size: max_size,
+    })
+}
+
+/// Accepts both `str` and `bytes`.
+fn bytes_from(py: Python, po: PyObject) -> Result<Vec<u8>, PyErr> {
+    match po.extract::<PyBytes>(py) {
+        Ok(bytes) => Ok(bytes.data(py).to_vec()),
+        Err(_) => string_from(py, po).map(String::into_bytes),
+    }
+}
+
+fn string_from(py: Python, po: PyObject) -> Result<String, PyErr> {
+    let s = try!(po.extract::<PyString>(py));
+    let s = try!(s.to_string(py)).into_owned();
+    Ok(s)
+}
+
+/// Reads the optional string `po`, e.g. an item of a dict.
+fn optional_string_from(py: Python, po: Option<PyObject>) -> Result<Option<String>, PyErr> {
+    match po {
+        Some(po) => string_from(py, po).map(Some),
+        None => Ok(None),
+    }
+}
+
+/// Reads an HTTP request either from the short form `"<verb> <uri>"` or from a dict with the keys
+/// `verb`, `uri` and optionally `headers`, `body` and `follow_redirects`.
+fn http_request_from(py: Python, po: PyObject) -> Result<Option<HttpRequest>, PropertyError> {
+    if po == py.None() {
+        return Ok(None);
+    }
+    let dict = match po.extract::<PyDict>(py) {
+        Ok(dict) => dict,
+        Err(_) => return Ok(HttpRequest::parse(&try!(string_from(py, po)))),
+    };
+    let item = |key: &str| {
+        dict.get_item(py, key).and_then(|v| if v == py.None() { None } else { Some(v) })
+    };
+
+    let verb = try!(optional_string_from(py, item("verb"))).unwrap_or("GET".to_string());
+    let uri = try!(optional_string_from(py, item("uri"))).unwrap_or("/".to_string());
+    let mut request = HttpRequest::new(&verb, &uri);
+    if let Some(headers) = item("headers") {
+        let headers = try!(headers.extract::<PyDict>(py)
+                                  .map_err(|_| invalid_payload("HTTP request", "'headers' is not a dict")));
+        for (name, value) in headers.items(py) {
+            request.headers.push((try!(string_from(py, name)), try!(string_from(py, value))));
+        }
+    }
+    request.body = try!(optional_string_from(py, item("body")));
+    request.follow_redirects = match item("follow_redirects") {
+        Some(v) => {
+            try!(v.extract::<bool>(py)
+                  .map_err(|_| invalid_payload("HTTP request", "'follow_redirects' is not a bool")))
+        }
+        None => false,
+    };
+
+    Ok(Some(request))
+}
+
+/// Reads a list of dicts with the optional keys `send`, `expect` and `timeout` in seconds.
+fn dialog_steps_from(py: Python, po: PyObject) -> Result<Vec<DialogStep>, PropertyError> {
+    let py_steps = try!(po.extract::<PyList>(py).map_err(|_| invalid_payload("dialog", "not a list")));
+    let mut steps = Vec::with_capacity(py_steps.len(py));
+    for index in 0..py_steps.len(py) {
+        let py_step = py_steps.get_item(py, index);
+        let invalid = |reason: &str| invalid_payload("dialog", &format!("step {} {}", index + 1, reason));
+        let py_step = try!(py_step.extract::<PyDict>(py).map_err(|_| invalid("is not a dict")));
+        let item = |key: &str| {
+            py_step.get_item(py, key).and_then(|v| if v == py.None() { None } else { Some(v) })
+        };
+        let text = |key: &str| {
+            optional_string_from(py, item(key)).map_err(|_| invalid(&format!("'{}' is not a string", key)))
+        };
+        let timeout = match item("timeout") {
+            Some(v) => {
+                match v.extract::<f64>(py) {
+                    Ok(secs) if secs.is_finite() && secs > 0.0 => {
+                        Some(Duration::from_millis(cmp::max((secs * 1000.0) as u64, 1)))
+                    }
+                    _ => return Err(invalid("'timeout' is not a positive number of seconds")),
+                }
+            }
+            None => None,
+        };
+        steps.push(DialogStep {
+            send: try!(text("send")),
+            expect: try!(text("expect")),
+            timeout: timeout,
+        });
+    }
+
+    Ok(steps)
+}
+
+/// Reads a dict with the keys `name` and optionally `type` (default `A`) and `recursion_desired`
+/// (default `True`).
+fn dns_query_from(py: Python, po: PyObject) -> Result<DnsQuery, PropertyError> {
+    let dict = try!(po.extract::<PyDict>(py).map_err(|_| invalid_payload("DNS query", "not a dict")));
+    let item = |key: &str| {
+        dict.get_item(py, key).and_then(|v| if v == py.None() { None } else { Some(v) })
+    };
+
+    let name = match try!(optional_string_from(py, item("name"))) {
+        Some(name) => name,
+        None => return Err(invalid_payload("DNS query", "missing 'name'")),
+    };
+    let record_type = match try!(optional_string_from(py, item("type"))) {
+        Some(record_type) => {
+            try!(record_type.parse().map_err(|_| {
+                invalid_payload("DNS query", &format!("unknown type '{}'", record_type))
+            }))
+        }
+        None => RecordType::A,
+    };
+    let mut query = DnsQuery::new(&name, record_type);
+    if let Some(recursion_desired) = item("recursion_desired") {
+        query.recursion_desired = try!(recursion_desired.extract::<bool>(py).map_err(|_| {
+            invalid_payload("DNS query", "'recursion_desired' is not a bool")
+        }));
+    }
+
+    Ok(query)
+}
+
+/// Reports a malformed `what` returned by the module's `challenge`.
+fn invalid_payload(what: &str, reason: &str) -> PropertyError {
+    PropertyError::FailedExecution(io::ErrorKind::InvalidInput, format!("invalid {}: {}", what, reason))
+}
+
+/// Turns a response into the keyword arguments of `check_response`.
+fn response_dict(py: Python, response: Response) -> PyDict {
+    let py_dict = PyDict::new(py);
+    match response {
+        Response::Connected => {}
+        Response::Bytes(bytes) => {
+            let _ = py_dict.set_item(py, "response", PyBytes::new(py, &bytes));
+        }
+        Response::Text(text) => {
+            let _ = py_dict.set_item(py, "response", text);
+        }
+        Response::TlsBytes(response) => {
+            let _ = py_dict.set_item(py, "response", PyBytes::new(py, &response.response));
+            let _ = py_dict.set_item(py, "tls", tls_dict(py, &response.tls));
+        }
+        Response::TlsText(response) => {
+            let _ = py_dict.set_item(py, "response", response.response);
+            let _ = py_dict.set_item(py, "tls", tls_dict(py, &response.tls));
+        }
+        Response::StartTls(response) => {
+            let _ = py_dict.set_item(py, "banner", response.banner);
+            let _ = py_dict.set_item(py, "response", response.response);
+            let _ = py_dict.set_item(py, "tls", tls_dict(py, &response.tls));
+        }
+        Response::Dialog(Transcript(steps)) => {
+            let py_steps: Vec<PyObject> = steps.into_iter()
+                                               .map(|step| {
+                                                   let py_step = PyDict::new(py);
+                                                   let _ = py_step.set_item(py, "sent", step.sent);
+                                                   let _ = py_step.set_item(py, "received", step.received);
+                                                   let _ = py_step.set_item(py, "expect", step.expect);
+                                                   let _ = py_step.set_item(py, "matched", step.matched);
+                                                   py_step.into_object()
+                                               })
+                                               .collect();
+            let _ = py_dict.set_item(py, "transcript", py_steps);
+        }
+        Response::Http(response) => return http_dict(py, response),
+        Response::Dns(response) => return dns_dict(py, response),
+    }
+    py_dict
+}
+
+fn dns_dict(py: Python, response: DnsResponse) -> PyDict {
+    let records = |records: Vec<DnsRecord>| -> Vec<PyObject> {
+        records.into_iter()
+               .map(|record| {
+                   let py_record = PyDict::new(py);
+                   let _ = py_record.set_item(py, "name", record.name);
+                   let _ = py_record.set_item(py, "type", record.record_type);
+                   let _ = py_record.set_item(py, "ttl", record.ttl);
+                   let _ = py_record.set_item(py, "data", record.data);
+                   py_record.into_object()
+               })
+               .collect()
+    };
+    let py_dict = PyDict::new(py);
+    let _ = py_dict.set_item(py, "rcode", response.rcode);
+    let _ = py_dict.set_item(py, "authoritative", response.authoritative);
+    let _ = py_dict.set_item(py, "truncated", response.truncated);
+    let _ = py_dict.set_item(py, "recursion_available", response.recursion_available);
+    let _ = py_dict.set_item(py, "answers", records(response.answers));
+    let _ = py_dict.set_item(py, "authority", records(response.authority));
+    py_dict
+}
+
+fn http_dict(py: Python, response: HttpResponse<String>) -> PyDict {
+    let py_dict = PyDict::new(py);
+    let _ = py_dict.set_item(py, "response_code", response.response_code);
+    let py_headers = PyDict::new(py);
+    for (name, value) in &response.headers {
+        let _ = py_headers.set_item(py, name, value);
+    }
+    let _ = py_dict.set_item(py, "headers", py_headers);
+    let _ = py_dict.set_item(py, "body", response.body);
+    let _ = py_dict.set_item(py, "body_truncated", response.body_truncated);
+    let py_redirects: Vec<PyObject> = response.redirects
+                                              .iter()
+                                              .map(|redirect| {
+                                                  let py_redirect = PyDict::new(py);
+                                                  let _ = py_redirect.set_item(py,
+                                                                               "response_code",
+                                                                               redirect.response_code);
+                                                  let _ = py_redirect.set_item(py,
+                                                                               "location",
+                                                                               &redirect.location);
+                                                  py_redirect.into_object()
+                                              })
+                                              .collect();
+    let _ = py_dict.set_item(py, "redirects", py_redirects);
+    let py_tls = match response.tls {
+        Some(ref tls) => tls_dict(py, tls).into_object(),
+        None => py.None(),
+    };
+    let _ = py_dict.set_item(py, "tls", py_tls);
+    py_dict
+}
+
+fn tls_dict(py: Python, tls: &TlsInfo) -> PyDict {
+    let py_dict = PyDict::new(py);
+    let _ = py_dict.set_item(py, "version", &tls.version);
+    let _ = py_dict.set_item(py, "cipher", &tls.cipher);
+    let py_chain: Vec<PyObject> = tls.chain
+                                     .iter()
+                                     .map(|cert| {
+                                         let py_cert = PyDict::new(py);
+                                         let _ = py_cert.set_item(py, "subject", &cert.subject);
+                                         let _ = py_cert.set_item(py, "issuer", &cert.issuer);
+                                         let _ = py_cert.set_item(py, "sans", &cert.sans);
+                                         let _ = py_cert.set_item(py, "not_before", &cert.not_before);
+                                         let _ = py_cert.set_item(py, "not_after", &cert.not_after);
+                                         let _ = py_cert.set_item(py, "days_valid", cert.days_valid);
+                                         let _ = py_cert.set_item(py, "key_type", &cert.key_type);
+                                         py_cert.into_object()
+                                     })
+                                     .collect();
+    let _ = py_dict.set_item(py, "chain", py_chain);
+    let _ = py_dict.set_item(py, "verify_error", &tls.verify_error);
+    py_dict
+}
+
+fn check_response(py: Python, instance: &PyObject, response: &PyDict) -> Result<bool, PropertyError> {
+    info!("* Checking response.");
+
+    match instance.call_method(py, "check_response", NoArgs, Some(response)) {
+        Ok(r) => Ok(try!(r.extract::<bool>(py))),
+        Err(err) => {
+            let err = PythonError::new(py, err);
+            if err.class == "ResponseCheckError" {
+                Err(PropertyError::FailedResponseCheck(err.message))
+            } else {
+                Err(PropertyError::FailedPythonCall(err))
+            }
+        }
+    }
+}
+
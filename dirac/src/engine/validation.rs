@@ -0,0 +1,73 @@
+use super::super::checks::{CheckSuite, Property, SuiteError};
+use super::super::modules::Registry;
+use super::{python, tls_protocol};
+
+/// Checks a suite without touching the network and returns every problem found.
+///
+/// Besides the inventory references, each property's params are checked by its module's
+/// `check_args`, once for every distinct set of params it has for the check's hosts; Python
+/// modules are imported for that. Only the built-in modules are known; cf. `Engine::validate` for
+/// others.
+pub fn validate(check_suite: &CheckSuite) -> Vec<SuiteError> {
+    validate_with(&Registry::new(), check_suite)
+}
+
+pub fn validate_with(modules: &Registry, check_suite: &CheckSuite) -> Vec<SuiteError> {
+    let mut errors = check_suite.check_inventory_names();
+
+    for check in &check_suite.checks {
+        for property in &check.properties {
+            if let Err(reason) = validate_for_hosts(modules, check_suite, &check.hosts, property) {
+                let reason = format!("property '{}' [{}]: {}", property.name, property.module, reason);
+                errors.push(SuiteError::at_position(property.position, reason));
+            }
+        }
+    }
+
+    errors
+}
+
+/// Validates `property` as rendered for each of `hosts`, stopping at the first invalid one; errors
+/// name the host if its variables changed the property.
+fn validate_for_hosts(modules: &Registry,
+                      check_suite: &CheckSuite,
+                      hosts: &[String],
+                      property: &Property)
+                      -> Result<(), String> {
+    if hosts.is_empty() {
+        return validate_property(modules, property);
+    }
+
+    let mut validated: Vec<Property> = Vec::new();
+    for host in hosts {
+        let rendered = try!(check_suite.property_for(property, host)
+                                       .map_err(|err| format!("invalid params: {}", err)));
+        if validated.iter().any(|p| p.params == rendered.params && p.tls == rendered.tls) {
+            continue;
+        }
+        let per_host = rendered.params != property.params || rendered.tls != property.tls;
+        try!(validate_property(modules, &rendered).map_err(|err| {
+            if per_host { format!("host '{}': {}", host, err) } else { err }
+        }));
+        validated.push(rendered);
+    }
+
+    Ok(())
+}
+
+fn validate_property(modules: &Registry, property: &Property) -> Result<(), String> {
+    let module = match modules.get(&property.module) {
+        Some(module) => module,
+        None => return python::validate_property(property),
+    };
+
+    try!(module.check_args(&property.params).map_err(|err| format!("invalid params: {}", err)));
+    if property.tls.is_some() {
+        let protocol = module.protocol(&property.params);
+        if tls_protocol(&protocol).is_none() {
+            return Err(format!("protocol '{}' cannot be run over TLS", protocol));
+        }
+    }
+
+    Ok(())
+}
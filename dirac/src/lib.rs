@@ -1,12 +1,16 @@
+#[cfg(feature = "python")]
 extern crate cpython;
 extern crate hyper;
+extern crate libc;
 #[macro_use]
 extern crate log;
+extern crate openssl;
+extern crate regex;
 extern crate rustc_serialize;
-extern crate term_painter;
 extern crate yaml_rust;
 
 pub mod checks;
 pub mod engine;
+pub mod modules;
 pub mod protocols;
 pub mod reports;
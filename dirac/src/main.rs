@@ -1,29 +1,43 @@
 extern crate clap;
 extern crate env_logger;
-#[macro_use]
-extern crate log;
 extern crate term_painter;
 
 extern crate dirac;
 
-use clap::{Arg, App};
+use clap::{Arg, App, AppSettings, ArgMatches, SubCommand};
 use term_painter::ToStyle;
 use term_painter::Color::*;
 use term_painter::Attr::*;
+use std::cmp;
 use std::collections::HashMap;
+use std::io::Write;
+use std::process;
 
-use dirac::checks::CheckSuite;
-use dirac::engine::CheckSuiteResult;
-use dirac::reports::{Report, Reporter};
+use dirac::checks::{Check, CheckSuite, Filter, Property, Selector};
+use dirac::engine::{CheckSuiteResult, Engine, FailurePolicy, Observer, PropertyError, PropertyResult,
+                    Verdict};
+use dirac::reports::Reporter;
 
 static VERSION: &'static str = env!("CARGO_PKG_VERSION");
 
+/// All properties passed or failed non-fatally.
+const EXIT_PASSED: i32 = 0;
+/// At least one property failed.
+const EXIT_FAILED: i32 = 1;
+/// A check suite or the command line is invalid.
+const EXIT_SUITE_ERROR: i32 = 2;
+/// A property could not be checked because of a Python or internal error, or the report could not
+/// be written.
+const EXIT_INTERNAL_ERROR: i32 = 3;
+
 fn main() {
     if env_logger::init().is_err() {
         panic!("Could not initiliaze logger");
     }
     let cli_args = App::new("Dirac Host Properties Checker")
                        .version(VERSION)
+                       // `validate` takes its own check suites
+                       .setting(AppSettings::SubcommandsNegateReqs)
                        .arg(Arg::with_name("report")
                                 .takes_value(true)
                                 .requires("output")
@@ -39,19 +53,134 @@ fn main() {
                                 .long("output")
                                 .value_name("FILENAME")
                                 .help("Sets output file for report"))
+                       .arg(Arg::with_name("jobs")
+                                .takes_value(true)
+                                .short("j")
+                                .long("jobs")
+                                .value_name("N")
+                                .validator(|n| {
+                                    match n.parse::<usize>() {
+                                        Ok(n) if n > 0 => Ok(()),
+                                        _ => Err("must be a positive number".to_string()),
+                                    }
+                                })
+                                .help("Sets number of properties checked concurrently; overrides \
+                                       the suite's settings"))
+                       .arg(Arg::with_name("timeout")
+                                .takes_value(true)
+                                .long("timeout")
+                                .value_name("SECONDS")
+                                .validator(is_positive_seconds)
+                                .help("Sets read timeout for all properties; overrides the \
+                                       suite's settings"))
+                       .arg(Arg::with_name("connect_timeout")
+                                .takes_value(true)
+                                .long("connect-timeout")
+                                .value_name("SECONDS")
+                                .validator(is_positive_seconds)
+                                .help("Sets connect timeout for all properties; overrides the \
+                                       suite's settings"))
+                       .arg(Arg::with_name("non_fatal")
+                                .takes_value(true)
+                                .multiple(true)
+                                .number_of_values(1)
+                                .long("non-fatal")
+                                .value_name("FAILURE")
+                                .possible_values(&["FailedExecution",
+                                                   "Timeout",
+                                                   "Closed",
+                                                   "FailedResponseCheck"])
+                                .help("Does not fail the run because of properties failing this \
+                                       way"))
+                       .arg(Arg::with_name("non_fatal_tag")
+                                .takes_value(true)
+                                .multiple(true)
+                                .number_of_values(1)
+                                .long("non-fatal-tag")
+                                .value_name("TAG")
+                                .help("Does not fail the run because of failing properties with \
+                                       this tag"))
+                       .args(&filter_args())
                        .arg(Arg::with_name("check_suite")
                                 .takes_value(true)
                                 .value_name("FILENAME")
                                 .min_values(1)
+                                .required(true)
                                 .help("Check suites to run"))
-                       .get_matches();
+                       .subcommand(SubCommand::with_name("validate")
+                                       .about("Validates check suites without touching the \
+                                               network")
+                                       .arg(Arg::with_name("check_suite")
+                                                .takes_value(true)
+                                                .value_name("FILENAME")
+                                                .min_values(1)
+                                                .required(true)
+                                                .help("Check suites to validate")))
+                       .get_matches_safe()
+                       .unwrap_or_else(|err| {
+                           if !err.use_stderr() {
+                               err.exit();
+                           }
+                           let _ = writeln!(&mut std::io::stderr(), "{}", err);
+                           process::exit(EXIT_SUITE_ERROR);
+                       });
+
+    if let Some(validate_args) = cli_args.subcommand_matches("validate") {
+        let filenames = validate_args.values_of("check_suite").unwrap();
+        let valid = filenames.fold(true, |valid, filename| validate(filename) && valid);
+        process::exit(if valid { EXIT_PASSED } else { EXIT_SUITE_ERROR });
+    }
 
     let check_suite_filenames = cli_args.values_of("check_suite").unwrap();
+    let jobs = cli_args.value_of("jobs").map(|n| n.parse::<usize>().unwrap());
+    let timeout_ms = cli_args.value_of("timeout").map(seconds_to_ms);
+    let connect_timeout_ms = cli_args.value_of("connect_timeout").map(seconds_to_ms);
+    let mut policy = FailurePolicy::new();
+    for name in cli_args.values_of("non_fatal").into_iter().flat_map(|names| names) {
+        policy.non_fatal_error(name);
+    }
+    for tag in cli_args.values_of("non_fatal_tag").into_iter().flat_map(|tags| tags) {
+        policy.non_fatal_tag(tag);
+    }
+
+    let filter = match create_filter(&cli_args) {
+        Ok(filter) => filter,
+        Err(err) => {
+            let _ = writeln!(&mut std::io::stderr(), "{}", err);
+            process::exit(EXIT_SUITE_ERROR);
+        }
+    };
 
+    let mut verdict = Verdict::Passed;
     for filename in check_suite_filenames {
-        let check_suite = CheckSuite::read_from_file(&filename).unwrap();
-        let results = dirac::engine::run(&check_suite);
-        print_summary(&results);
+        let mut check_suite = match CheckSuite::read_from_file(&filename) {
+            Ok(check_suite) => check_suite,
+            Err(err) => {
+                let _ = writeln!(&mut std::io::stderr(), "{}", err);
+                process::exit(EXIT_SUITE_ERROR);
+            }
+        };
+        if jobs.is_some() {
+            check_suite.settings.jobs = jobs;
+        }
+        if timeout_ms.is_some() {
+            check_suite.settings.timeout_ms = timeout_ms;
+        }
+        if connect_timeout_ms.is_some() {
+            check_suite.settings.connect_timeout_ms = connect_timeout_ms;
+        }
+        check_suite.apply_filter(&filter);
+        let engine = Engine::new();
+        let mut errors = check_suite.check_inventory_names();
+        errors.extend(engine.check_module_support(&check_suite));
+        if !errors.is_empty() {
+            for err in errors {
+                let _ = writeln!(&mut std::io::stderr(), "{}", err.with_filename(filename));
+            }
+            process::exit(EXIT_SUITE_ERROR);
+        }
+        let results = engine.run_observed(&check_suite, &mut Console::new());
+        verdict = cmp::max(verdict, policy.verdict(&results));
 
         if cli_args.is_present("report") && cli_args.is_present("output") {
             let report_type = cli_args.value_of("report").unwrap().to_string();
@@ -59,8 +188,147 @@ fn main() {
 
             let mut report_builder = Reporter::new(&results, &report_type);
             let report = report_builder.with_filename(&report_filename).create();
-            let _ = report.write_to_file();
+            if let Err(err) = report.write_to_file() {
+                let _ = writeln!(&mut std::io::stderr(),
+                                 "could not write report '{}': {}",
+                                 report_filename,
+                                 err);
+                verdict = Verdict::InternalError;
+            }
+        }
+    }
+
+    process::exit(match verdict {
+        Verdict::Passed => EXIT_PASSED,
+        Verdict::Failed => EXIT_FAILED,
+        Verdict::InternalError => EXIT_INTERNAL_ERROR,
+    });
+}
+
+/// The options selecting hosts and properties, as `(name, long, selector, exclude, help)`.
+const FILTER_OPTIONS: &'static [(&'static str, &'static str, Selector, bool, &'static str)] = &[
+    ("hosts", "hosts", Selector::Host, false, "Only checks hosts matching PATTERN"),
+    ("exclude_hosts", "exclude-hosts", Selector::Host, true, "Skips hosts matching PATTERN"),
+    ("group", "group", Selector::Group, false, "Only checks hosts of groups matching PATTERN"),
+    ("exclude_group", "exclude-group", Selector::Group, true, "Skips hosts of groups matching PATTERN"),
+    ("property", "property", Selector::Property, false, "Only checks properties named like PATTERN"),
+    ("exclude_property", "exclude-property", Selector::Property, true, "Skips properties named like PATTERN"),
+    ("module", "module", Selector::Module, false, "Only checks modules matching PATTERN"),
+    ("exclude_module", "exclude-module", Selector::Module, true, "Skips modules matching PATTERN"),
+];
+
+fn filter_args<'a, 'b>() -> Vec<Arg<'a, 'b>> {
+    FILTER_OPTIONS.iter()
+                  .map(|&(name, long, _, _, help)| {
+                      Arg::with_name(name)
+                          .takes_value(true)
+                          .multiple(true)
+                          .number_of_values(1)
+                          .use_delimiter(false)
+                          .long(long)
+                          .value_name("PATTERN")
+                          .help(help)
+                  })
+                  .collect()
+}
+
+fn create_filter(cli_args: &ArgMatches) -> Result<Filter, String> {
+    let mut filter = Filter::new();
+    for &(name, _, selector, exclude, _) in FILTER_OPTIONS {
+        for pattern in cli_args.values_of(name).into_iter().flat_map(|patterns| patterns) {
+            if exclude {
+                try!(filter.exclude(selector, pattern));
+            } else {
+                try!(filter.include(selector, pattern));
+            }
+        }
+    }
+
+    Ok(filter)
+}
+
+fn is_positive_seconds(seconds: String) -> Result<(), String> {
+    match seconds.parse::<f64>() {
+        Ok(s) if s > 0.0 => Ok(()),
+        _ => Err("must be a positive number of seconds".to_string()),
+    }
+}
+
+fn seconds_to_ms(seconds: &str) -> u64 {
+    (seconds.parse::<f64>().unwrap() * 1000.0) as u64
+}
+
+fn validate(filename: &str) -> bool {
+    let errors = match CheckSuite::read_from_file(filename) {
+        Ok(check_suite) => dirac::engine::validate(&check_suite),
+        Err(err) => vec![err],
+    };
+
+    if errors.is_empty() {
+        println!("{}: {}", filename, Green.paint("OK"));
+        return true;
+    }
+    for err in errors {
+        println!("{}", Red.paint(err.with_filename(filename)));
+    }
+    false
+}
+
+/// Prints the results of a running check suite in colour, followed by a summary per host.
+struct Console {
+    /// Whether a check has been printed, which is followed by a blank line.
+    printed_check: bool,
+}
+
+impl Console {
+    fn new() -> Console {
+        Console { printed_check: false }
+    }
+}
+
+impl Observer for Console {
+    fn check_results_started(&mut self, check: &Check) {
+        if self.printed_check {
+            println!("");
+        }
+        println!("CHECKING [{}]", Bold.paint(&check.inventory_name));
+        self.printed_check = true;
+    }
+
+    fn property_results_started(&mut self, _: &Check, property: &Property) {
+        let port = property.params.get("port").map_or(String::new(), |p| p.to_string());
+        println!("  PROPERTY: {} [{}:{}]", property.name, Bold.paint(&property.module), port);
+    }
+
+    fn property_finished(&mut self, _: &Check, property_result: &PropertyResult) {
+        let host = property_result.host;
+        match property_result.result {
+            Ok(_) if property_result.skipped => {
+                println!("    {:>11}: [{}]", Yellow.paint("Skipped"), host)
+            }
+            Ok(_) => println!("    {:>11}: [{}]", Green.paint("Success"), host),
+            Err(ref err) => {
+                let label = match *err {
+                    PropertyError::FailedExecution(..) => "Failed (E)",
+                    PropertyError::Timeout(..) => "Failed (T)",
+                    PropertyError::Closed(..) => "Failed (C)",
+                    PropertyError::FailedResponseCheck(_) => "Failed (R)",
+                    PropertyError::FailedPythonCall(_) => "Failed (P)",
+                    PropertyError::Unclassified => "Failed (?)",
+                };
+                println!("    {:>11}: [{}]", Red.paint(label), host);
+                if let Some(reason) = err.reason() {
+                    println!("    {:>11}  {}", "", reason);
+                }
+            }
+        }
+    }
+
+    fn suite_finished(&mut self, check_suite_result: &CheckSuiteResult) {
+        if self.printed_check {
+            println!("");
         }
+        print_summary(check_suite_result);
     }
 }
 
@@ -69,20 +337,23 @@ fn print_summary(check_suite_result: &CheckSuiteResult) {
 
     print!("{}\n", Bold.paint("SUMMARY"));
     for kv in summary {
-        print!(" * {:<30} Success {:4}, Failed {:4}\n",
+        print!(" * {:<30} Success {:4}, Failed {:4}, Skipped {:4}\n",
                kv.0,
                Green.paint((kv.1).0),
-               Red.paint((kv.1).1));
+               Red.paint((kv.1).1),
+               Yellow.paint((kv.1).2));
     }
 }
 
-fn create_summary<'a>(check_suite_result: &'a CheckSuiteResult) -> HashMap<&'a str, (u16, u16)> {
+fn create_summary<'a>(check_suite_result: &'a CheckSuiteResult) -> HashMap<&'a str, (u16, u16, u16)> {
     let mut result = HashMap::new();
 
     for check in &check_suite_result.results {
         for property in &check.results {
-            let mut host_result = result.entry(property.host).or_insert((0, 0));
-            if property.result.is_ok() {
+            let host_result = result.entry(property.host).or_insert((0, 0, 0));
+            if property.skipped {
+                host_result.2 += 1;
+            } else if property.result.is_ok() {
                 host_result.0 += 1;
             } else {
                 host_result.1 += 1;
@@ -0,0 +1,21 @@
+use super::*;
+
+/// Passes if a TCP connection can be established.
+pub struct ConnectTcp;
+
+impl CheckModule for ConnectTcp {
+    /// Accepts any params like the Python module; only the port is needed.
+    fn check_args(&self, params: &Params) -> Result<(), ArgumentError> {
+        try!(Args::new(params).port());
+
+        Ok(())
+    }
+
+    fn protocol(&self, _: &Params) -> String {
+        "connect/tcp".to_string()
+    }
+
+    fn check_response(&self, _: &Params, _: &Response) -> Result<(), String> {
+        Ok(())
+    }
+}
@@ -0,0 +1,227 @@
+use super::*;
+
+/// Sends a DNS query over `dns/udp` or `dns/tcp` and checks the rcode and answers.
+pub struct Dns;
+
+/// `dns` over UDP; without a `name`, it asks for the root's NS records and accepts any answer.
+pub struct DnsUdp;
+
+const RECORD_TYPES: &'static [&'static str] = &["A", "AAAA", "MX", "TXT", "SRV", "CNAME", "PTR", "SOA",
+                                                 "NS"];
+const RCODES: &'static [&'static str] = &["NOERROR", "FORMERR", "SERVFAIL", "NXDOMAIN", "NOTIMP",
+                                          "REFUSED"];
+const TRANSPORTS: &'static [&'static str] = &["udp", "tcp"];
+
+const PARAMS: &'static [&'static str] = &["port",
+                                           "name",
+                                           "type",
+                                           "transport",
+                                           "recursion_desired",
+                                           "rcode",
+                                           "answers",
+                                           "min_ttl",
+                                           "max_ttl",
+                                           "authoritative"];
+
+/// A property's params, read and checked.
+struct Query {
+    query: DnsQuery,
+    transport: String,
+    /// `None` accepts any rcode.
+    rcode: Option<String>,
+    answers: Vec<String>,
+    min_ttl: Option<i64>,
+    max_ttl: Option<i64>,
+    authoritative: Option<bool>,
+}
+
+impl Query {
+    fn from(params: &Params, udp: bool) -> Result<Query, ArgumentError> {
+        let args = Args::new(params);
+        try!(args.only(PARAMS));
+        try!(args.port());
+
+        let name = try!(args.string("name").map_err(|mut err| {
+            err.reason = "is not a domain name".to_string();
+            err
+        }));
+        // dns_udp without a name checks that a server is listening at all
+        let probe = udp && name.is_none();
+        let name = match (name, probe) {
+            (_, true) => ".",
+            (Some(name), false) if !name.is_empty() => name,
+            _ => return Err(ArgumentError::new("name", args.get("name"), "is not a domain name")),
+        };
+
+        let record_type = try!(args.string("type")).unwrap_or(if probe { "NS" } else { "A" });
+        let record_type = try!(record_type.parse::<RecordType>().map_err(|_| {
+            ArgumentError::new("type",
+                               args.get("type"),
+                               &format!("is not one of {}", one_of(RECORD_TYPES)))
+        }));
+
+        let transport = if udp { "udp" } else { try!(args.string("transport")).unwrap_or("udp") };
+        if !TRANSPORTS.contains(&transport) {
+            return Err(ArgumentError::new("transport",
+                                          args.get("transport"),
+                                          &format!("is not one of {}", one_of(TRANSPORTS))));
+        }
+
+        let mut query = DnsQuery::new(name, record_type);
+        query.recursion_desired = try!(args.bool("recursion_desired")).unwrap_or(true);
+
+        // An explicit null accepts any rcode, too
+        let rcode = match params.get("rcode") {
+            None if !probe => Some("NOERROR".to_string()),
+            None | Some(&ParamValue::Null) => None,
+            Some(value) => {
+                match value.as_str().map(|rcode| rcode.to_uppercase()) {
+                    Some(ref rcode) if RCODES.contains(&&rcode[..]) => Some(rcode.clone()),
+                    _ => {
+                        return Err(ArgumentError::new("rcode",
+                                                      Some(value),
+                                                      &format!("is not one of {}", one_of(RCODES))))
+                    }
+                }
+            }
+        };
+
+        let answers = try!(args.strings("answers").map_err(|mut err| {
+            err.reason = "is not a record or a list of records".to_string();
+            err
+        }));
+        let min_ttl = try!(args.number("min_ttl", 0, 0x7FFFFFFF, "is not a valid TTL"));
+        let max_ttl = try!(args.number("max_ttl", 0, 0x7FFFFFFF, "is not a valid TTL"));
+        if let (Some(min_ttl), Some(max_ttl)) = (min_ttl, max_ttl) {
+            if min_ttl > max_ttl {
+                return Err(ArgumentError::new("min_ttl", args.get("min_ttl"), "is greater than max_ttl"));
+            }
+        }
+
+        Ok(Query {
+            query: query,
+            transport: transport.to_string(),
+            rcode: rcode,
+            answers: answers.into_iter().map(|answer| answer.to_string()).collect(),
+            min_ttl: min_ttl,
+            max_ttl: max_ttl,
+            authoritative: try!(args.bool("authoritative")),
+        })
+    }
+
+    fn check_response(&self, response: &Response) -> Result<(), String> {
+        let response = match *response {
+            Response::Dns(ref response) => response,
+            _ => return Err(unexpected(response)),
+        };
+        let name = &self.query.name;
+        let record_type = self.query.record_type.to_string();
+
+        if let Some(ref rcode) = self.rcode {
+            if response.rcode != *rcode {
+                return Err(format!("Unexpected rcode '{}' for '{}'; expected '{}'.",
+                                   response.rcode,
+                                   name,
+                                   rcode));
+            }
+        }
+        if let Some(authoritative) = self.authoritative {
+            if response.authoritative != authoritative {
+                return Err(format!("Answer for '{}' is {}authoritative; expected the opposite.",
+                                   name,
+                                   if response.authoritative { "" } else { "not " }));
+            }
+        }
+
+        // Answers may also include the CNAME records leading to the records asked for
+        let records: Vec<&DnsRecord> = response.answers
+                                               .iter()
+                                               .filter(|record| record.record_type == record_type)
+                                               .collect();
+        let found: Vec<String> = records.iter()
+                                        .map(|record| normalize(&record_type, &record.data))
+                                        .collect();
+        for answer in &self.answers {
+            if !found.contains(&normalize(&record_type, answer)) {
+                let data: Vec<&str> = records.iter().map(|record| &record.data[..]).collect();
+                return Err(format!("Missing {} record '{}' for '{}'; found '{}'.",
+                                   record_type,
+                                   answer,
+                                   name,
+                                   data.join("', '")));
+            }
+        }
+        for record in records {
+            let ttl = record.ttl as i64;
+            if let Some(min_ttl) = self.min_ttl {
+                if ttl < min_ttl {
+                    return Err(format!("TTL {} of {} record '{}' is less than {}.",
+                                       ttl,
+                                       record_type,
+                                       record.data,
+                                       min_ttl));
+                }
+            }
+            if let Some(max_ttl) = self.max_ttl {
+                if ttl > max_ttl {
+                    return Err(format!("TTL {} of {} record '{}' is greater than {}.",
+                                       ttl,
+                                       record_type,
+                                       record.data,
+                                       max_ttl));
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Makes record data comparable: names without the trailing dot and case, TXT strings without quotes.
+fn normalize(record_type: &str, data: &str) -> String {
+    let data = data.trim();
+    if record_type == "TXT" && data.starts_with('"') {
+        let strings = Regex::new(r#""((?:[^"\\]|\\.)*)""#).unwrap();
+        return strings.captures_iter(data).filter_map(|c| c.at(1)).collect();
+    }
+    data.trim_end_matches('.').to_lowercase()
+}
+
+impl CheckModule for Dns {
+    fn check_args(&self, params: &Params) -> Result<(), ArgumentError> {
+        Query::from(params, false).map(|_| ())
+    }
+
+    fn protocol(&self, params: &Params) -> String {
+        let transport = Query::from(params, false).map(|q| q.transport).unwrap_or("udp".to_string());
+        format!("dns/{}", transport)
+    }
+
+    fn challenge(&self, params: &Params) -> Payload {
+        Query::from(params, false).map_or(Payload::None, |q| Payload::Dns(q.query))
+    }
+
+    fn check_response(&self, params: &Params, response: &Response) -> Result<(), String> {
+        let query = try!(Query::from(params, false).map_err(|err| err.to_string()));
+        query.check_response(response)
+    }
+}
+
+impl CheckModule for DnsUdp {
+    fn check_args(&self, params: &Params) -> Result<(), ArgumentError> {
+        Query::from(params, true).map(|_| ())
+    }
+
+    fn protocol(&self, _: &Params) -> String {
+        "dns/udp".to_string()
+    }
+
+    fn challenge(&self, params: &Params) -> Payload {
+        Query::from(params, true).map_or(Payload::None, |q| Payload::Dns(q.query))
+    }
+
+    fn check_response(&self, params: &Params, response: &Response) -> Result<(), String> {
+        let query = try!(Query::from(params, true).map_err(|err| err.to_string()));
+        query.check_response(response)
+    }
+}
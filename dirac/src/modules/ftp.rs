@@ -0,0 +1,45 @@
+use super::*;
+
+/// Checks the response code of an FTP server's greeting.
+pub struct Ftp;
+
+impl CheckModule for Ftp {
+    fn check_args(&self, params: &Params) -> Result<(), ArgumentError> {
+        let args = Args::new(params);
+        try!(args.only(&["port", "response_code"]));
+        try!(args.port());
+        try!(args.required("response_code"));
+        try!(args.number("response_code", 1, 699, "is not a valid FTP response code"));
+
+        Ok(())
+    }
+
+    fn protocol(&self, _: &Params) -> String {
+        "text/tcp".to_string()
+    }
+
+    fn read_strategy(&self, _: &Params) -> ReadStrategy {
+        // Greetings may span several lines and segments
+        until_idle()
+    }
+
+    fn check_response(&self, params: &Params, response: &Response) -> Result<(), String> {
+        let expected_code = try!(Args::new(params)
+                                     .number("response_code", 1, 699, "is not a valid FTP response code")
+                                     .map_err(|err| err.to_string()))
+                                .unwrap_or_default();
+        let response = try!(response.text().ok_or_else(|| unexpected(response)));
+
+        let response_code = response.split(' ').next().unwrap_or("");
+        let response_code = try!(response_code.parse::<i64>().map_err(|_| {
+            format!("Invalid response code in response '{}'.", response)
+        }));
+        if response_code != expected_code {
+            return Err(format!("Unexpected response code '{}'; expected '{}'.",
+                               response_code,
+                               expected_code));
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,253 @@
+use super::*;
+
+/// Sends an HTTP request and checks the response code, headers and body.
+pub struct Http;
+
+/// `http` over TLS; additionally checks the certificate and the negotiated TLS version.
+pub struct Https;
+
+const VERBS: &'static [&'static str] = &["GET", "HEAD", "POST", "PUT", "DELETE", "OPTIONS"];
+const TLS_VERSIONS: &'static [&'static str] = &["SSLv3", "TLSv1", "TLSv1.1", "TLSv1.2", "TLSv1.3"];
+
+const HTTP_PARAMS: &'static [&'static str] = &["port",
+                                                "verb",
+                                                "uri",
+                                                "response_code",
+                                                "headers",
+                                                "body",
+                                                "follow_redirects",
+                                                "location",
+                                                "redirect_chain",
+                                                "expected_headers",
+                                                "body_regex",
+                                                "body_contains"];
+const HTTPS_PARAMS: &'static [&'static str] = &["min_days_valid", "expected_san", "min_tls_version"];
+
+impl CheckModule for Http {
+    fn check_args(&self, params: &Params) -> Result<(), ArgumentError> {
+        let args = Args::new(params);
+        try!(args.only(HTTP_PARAMS));
+
+        check_http_args(&args)
+    }
+
+    fn protocol(&self, _: &Params) -> String {
+        "http/tcp".to_string()
+    }
+
+    fn challenge(&self, params: &Params) -> Payload {
+        Payload::Http(request_of(&Args::new(params)))
+    }
+
+    fn check_response(&self, params: &Params, response: &Response) -> Result<(), String> {
+        match *response {
+            Response::Http(ref response) => check_http_response(&Args::new(params), response),
+            _ => Err(unexpected(response)),
+        }
+    }
+}
+
+impl CheckModule for Https {
+    fn check_args(&self, params: &Params) -> Result<(), ArgumentError> {
+        let args = Args::new(params);
+        let known: Vec<&str> = HTTP_PARAMS.iter().chain(HTTPS_PARAMS).cloned().collect();
+        try!(args.only(&known));
+        try!(args.number("min_days_valid", 0, 0x7FFFFFFF, "is not a valid number of days"));
+        if let Err(mut err) = args.strings("expected_san") {
+            err.reason = "is not a name or a list of names".to_string();
+            return Err(err);
+        }
+        if let Some(version) = try!(args.string("min_tls_version")) {
+            if !TLS_VERSIONS.contains(&version) {
+                return Err(ArgumentError::new("min_tls_version",
+                                              args.get("min_tls_version"),
+                                              &format!("is not one of {}", one_of(TLS_VERSIONS))));
+            }
+        }
+
+        check_http_args(&args)
+    }
+
+    fn protocol(&self, _: &Params) -> String {
+        "https/tcp".to_string()
+    }
+
+    fn challenge(&self, params: &Params) -> Payload {
+        Payload::Http(request_of(&Args::new(params)))
+    }
+
+    fn check_response(&self, params: &Params, response: &Response) -> Result<(), String> {
+        let args = Args::new(params);
+        match *response {
+            Response::Http(ref response) => {
+                try!(check_http_response(&args, response));
+                check_tls(&args, response.tls.as_ref())
+            }
+            _ => Err(unexpected(response)),
+        }
+    }
+}
+
+fn check_http_args(args: &Args) -> Result<(), ArgumentError> {
+    try!(args.port());
+    let verb = try!(args.required_string("verb"));
+    if !VERBS.contains(&&verb.to_uppercase()[..]) {
+        return Err(ArgumentError::new("verb", args.get("verb"), "is not a valid HTTP verb"));
+    }
+    if try!(args.required_string("uri")).is_empty() {
+        return Err(ArgumentError::new("uri", args.get("uri"), "is not a valid URI"));
+    }
+    try!(args.required("response_code"));
+    try!(args.numbers("response_code", 1, 599, "is not a valid HTTP response code"));
+    try!(header_map(args, "headers"));
+    try!(args.string("body"));
+    let follow_redirects = try!(args.bool("follow_redirects")).unwrap_or(false);
+    try!(args.string("location"));
+    if let Some(redirect_chain) = args.get("redirect_chain") {
+        if !follow_redirects {
+            return Err(ArgumentError::new("redirect_chain",
+                                          Some(redirect_chain),
+                                          "requires follow_redirects"));
+        }
+        if redirect_chain.as_list().is_none() || args.strings("redirect_chain").is_err() {
+            return Err(ArgumentError::new("redirect_chain",
+                                          Some(redirect_chain),
+                                          "is not a list of strings"));
+        }
+    }
+    try!(header_map(args, "expected_headers"));
+    try!(args.regex("body_regex", false));
+    try!(args.string("body_contains"));
+
+    Ok(())
+}
+
+/// Reads a hash of header names to string or integer values.
+fn header_map(args: &Args, name: &str) -> Result<Vec<(String, String)>, ArgumentError> {
+    let value = match args.get(name) {
+        Some(value) => value,
+        None => return Ok(Vec::new()),
+    };
+    let invalid = || ArgumentError::new(name, Some(value), "is not a hash of header names to values");
+    let map = try!(value.as_map().ok_or_else(&invalid));
+    map.iter()
+       .map(|(name, value)| {
+           match *value {
+               ParamValue::String(_) | ParamValue::Int(_) => Ok((name.clone(), value.to_string())),
+               _ => Err(invalid()),
+           }
+       })
+       .collect()
+}
+
+fn request_of(args: &Args) -> HttpRequest {
+    let mut request = HttpRequest::new(args.required_string("verb").unwrap_or("GET"),
+                                       args.required_string("uri").unwrap_or("/"));
+    request.headers = header_map(args, "headers").unwrap_or_default();
+    request.body = args.string("body").unwrap_or_default().map(|body| body.to_string());
+    request.follow_redirects = args.bool("follow_redirects").unwrap_or_default().unwrap_or(false);
+
+    request
+}
+
+fn check_http_response(args: &Args, response: &HttpResponse<String>) -> Result<(), String> {
+    let codes = try!(args.numbers("response_code", 1, 599, "is not a valid HTTP response code")
+                         .map_err(|err| err.to_string()));
+    if !codes.contains(&(response.response_code as i64)) {
+        let codes: Vec<String> = codes.iter().map(|c| c.to_string()).collect();
+        return Err(format!("Unexpected response code '{}'; expected '{}'.",
+                           response.response_code,
+                           codes.join("' or '")));
+    }
+
+    let header = |name: &str| {
+        response.headers
+                .iter()
+                .find(|&(n, _)| n.eq_ignore_ascii_case(name))
+                .map(|(_, value)| &value[..])
+    };
+    if let Some(location) = args.string("location").unwrap_or_default() {
+        if header("location") != Some(location) {
+            return Err(format!("Unexpected location '{}'; expected '{}'.",
+                               header("location").unwrap_or("None"),
+                               location));
+        }
+    }
+    if args.get("redirect_chain").is_some() {
+        let expected = args.strings("redirect_chain").unwrap_or_default();
+        let chain: Vec<&str> = response.redirects.iter().map(|r| &r.location[..]).collect();
+        if chain != expected {
+            return Err(format!("Unexpected redirect chain '{}'; expected '{}'.",
+                               chain.join(" -> "),
+                               expected.join(" -> ")));
+        }
+    }
+    for (name, value) in header_map(args, "expected_headers").unwrap_or_default() {
+        let name = name.to_lowercase();
+        if header(&name) != Some(&value[..]) {
+            return Err(format!("Unexpected value '{}' of header '{}'; expected '{}'.",
+                               header(&name).unwrap_or("None"),
+                               name,
+                               value));
+        }
+    }
+    let truncated = if response.body_truncated {
+        format!(" (truncated after {} bytes)", response.body.len())
+    } else {
+        String::new()
+    };
+    if let Some(body_regex) = try!(args.regex("body_regex", false).map_err(|err| err.to_string())) {
+        if !body_regex.is_match(&response.body) {
+            return Err(format!("Body{} does not match '{}'.", truncated, body_regex));
+        }
+    }
+    if let Some(body_contains) = args.string("body_contains").unwrap_or_default() {
+        if !response.body.contains(body_contains) {
+            return Err(format!("Body{} does not contain '{}'.", truncated, body_contains));
+        }
+    }
+
+    Ok(())
+}
+
+fn check_tls(args: &Args, tls: Option<&TlsInfo>) -> Result<(), String> {
+    let min_days_valid = args.number("min_days_valid", 0, 0x7FFFFFFF, "is not a valid number of days")
+                             .unwrap_or_default();
+    let expected_sans = args.strings("expected_san").unwrap_or_default();
+    let min_tls_version = args.string("min_tls_version").unwrap_or_default();
+    if min_days_valid.is_none() && expected_sans.is_empty() && min_tls_version.is_none() {
+        return Ok(());
+    }
+
+    let (tls, certificate) = match tls {
+        Some(tls) if !tls.chain.is_empty() => (tls, &tls.chain[0]),
+        _ => return Err("No TLS certificate in response.".to_string()),
+    };
+    if let Some(min_days_valid) = min_days_valid {
+        if certificate.days_valid < min_days_valid {
+            return Err(format!("Certificate '{}' expires in {} days on '{}'; expected at least {} days.",
+                               certificate.subject,
+                               certificate.days_valid,
+                               certificate.not_after,
+                               min_days_valid));
+        }
+    }
+    for san in expected_sans {
+        if !certificate.sans.iter().any(|s| s == san) {
+            return Err(format!("Certificate '{}' does not include SAN '{}'; found '{}'.",
+                               certificate.subject,
+                               san,
+                               certificate.sans.join("', '")));
+        }
+    }
+    if let Some(min_tls_version) = min_tls_version {
+        let rank = |version: &str| TLS_VERSIONS.iter().position(|v| *v == version);
+        if rank(&tls.version).map_or(true, |r| Some(r) < rank(min_tls_version)) {
+            return Err(format!("Unexpected TLS version '{}'; expected at least '{}'.",
+                               tls.version,
+                               min_tls_version));
+        }
+    }
+
+    Ok(())
+}
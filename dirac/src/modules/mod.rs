@@ -0,0 +1,426 @@
+//! Check modules implemented in Rust.
+//!
+//! They follow the contract of the Python modules in `check_modules/dirac`: a module validates
+//! a property's params, names the protocol to run, provides the challenge to send and checks the
+//! response. Modules are looked up by name in a `Registry`, which starts out with the built-in
+//! modules; names not found there are left to Python.
+//!
+//! Library users can add their own modules:
+//!
+//! ```no_run
+//! use dirac::checks::{CheckSuite, Params};
+//! use dirac::engine::Engine;
+//! use dirac::modules::{ArgumentError, Args, CheckModule, Payload, Response};
+//!
+//! struct License;
+//!
+//! impl CheckModule for License {
+//!     fn check_args(&self, params: &Params) -> Result<(), ArgumentError> {
+//!         Args::new(params).port().map(|_| ())
+//!     }
+//!
+//!     fn protocol(&self, _: &Params) -> String {
+//!         "text/tcp".to_string()
+//!     }
+//!
+//!     fn challenge(&self, _: &Params) -> Payload {
+//!         Payload::Text("STATUS\n".to_string())
+//!     }
+//!
+//!     fn check_response(&self, _: &Params, response: &Response) -> Result<(), String> {
+//!         match response.text() {
+//!             Some(text) if text.starts_with("OK") => Ok(()),
+//!             _ => Err("License server is not ready.".to_string()),
+//!         }
+//!     }
+//! }
+//!
+//! let check_suite = CheckSuite::read_from_file("license.yml").unwrap();
+//! let mut engine = Engine::new();
+//! engine.register("license", License);
+//! engine.run(&check_suite);
+//! ```
+
+use regex::Regex;
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::Arc;
+use std::time::Duration;
+
+use super::checks::{ParamValue, Params};
+use super::engine::DEFAULT_IDLE_TIMEOUT_MS;
+use super::protocols::*;
+
+mod connect_tcp;
+mod dns;
+mod ftp;
+mod http;
+mod mysql;
+mod raw_tcp;
+mod smtp;
+mod ssh;
+
+/// The Rust counterpart of Python's `dirac.Module`.
+///
+/// Modules are shared by all worker threads and get the property's params with every call, so
+/// they usually do not keep any state.
+pub trait CheckModule: Send + Sync {
+    /// Validates `params` without touching the network.
+    fn check_args(&self, params: &Params) -> Result<(), ArgumentError>;
+
+    /// The protocol to run, e.g. `text/tcp`.
+    fn protocol(&self, params: &Params) -> String;
+
+    /// What to send to the host; only called with params that passed `check_args`.
+    fn challenge(&self, params: &Params) -> Payload {
+        let _ = params;
+        Payload::None
+    }
+
+    fn read_strategy(&self, params: &Params) -> ReadStrategy {
+        let _ = params;
+        ReadStrategy::default()
+    }
+
+    /// Checks what has been received; the error explains why the property failed.
+    fn check_response(&self, params: &Params, response: &Response) -> Result<(), String>;
+}
+
+/// Names of the modules implemented in Rust.
+pub const BUILTIN_MODULES: &'static [&'static str] = &["connect_tcp", "dns", "dns_udp", "ftp", "http",
+                                                       "https", "mysql", "raw_tcp", "smtp", "ssh"];
+
+/// Returns the native implementation of the built-in module `name`, if there is one.
+pub fn builtin(name: &str) -> Option<Box<CheckModule>> {
+    let module: Box<CheckModule> = match name {
+        "connect_tcp" => Box::new(connect_tcp::ConnectTcp),
+        "dns" => Box::new(dns::Dns),
+        "dns_udp" => Box::new(dns::DnsUdp),
+        "ftp" => Box::new(ftp::Ftp),
+        "http" => Box::new(http::Http),
+        "https" => Box::new(http::Https),
+        "mysql" => Box::new(mysql::Mysql),
+        "raw_tcp" => Box::new(raw_tcp::RawTcp),
+        "smtp" => Box::new(smtp::Smtp),
+        "ssh" => Box::new(ssh::Ssh),
+        _ => return None,
+    };
+    Some(module)
+}
+
+/// Maps module names, as used in suites, to modules.
+#[derive(Clone)]
+pub struct Registry {
+    modules: HashMap<String, Arc<CheckModule>>,
+}
+
+impl Registry {
+    /// A registry without any modules; everything is left to Python.
+    pub fn empty() -> Registry {
+        Registry { modules: HashMap::new() }
+    }
+
+    /// A registry with the built-in modules.
+    pub fn new() -> Registry {
+        let mut registry = Registry::empty();
+        for name in BUILTIN_MODULES {
+            if let Some(module) = builtin(name) {
+                registry.modules.insert(name.to_string(), Arc::from(module));
+            }
+        }
+        registry
+    }
+
+    /// Adds `module` as `name`, replacing a module of the same name, e.g. a built-in one.
+    pub fn register<M: CheckModule + 'static>(&mut self, name: &str, module: M) -> &mut Registry {
+        self.modules.insert(name.to_string(), Arc::new(module));
+        self
+    }
+
+    pub fn get(&self, name: &str) -> Option<Arc<CheckModule>> {
+        self.modules.get(name).cloned()
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.modules.contains_key(name)
+    }
+
+    /// The registered names in alphabetical order.
+    pub fn names(&self) -> Vec<&str> {
+        let mut names: Vec<&str> = self.modules.keys().map(|name| &name[..]).collect();
+        names.sort();
+        names
+    }
+}
+
+impl Default for Registry {
+    fn default() -> Registry {
+        Registry::new()
+    }
+}
+
+/// What a module sends; the variant has to fit the module's protocol.
+#[derive(Debug, Clone)]
+pub enum Payload {
+    None,
+    /// For `raw/tcp` and `tls/raw`.
+    Bytes(Vec<u8>),
+    /// For `text/tcp`, `text/udp`, `tls/tcp` and `starttls/*`.
+    Text(String),
+    Dialog(Vec<DialogStep>),
+    Http(HttpRequest),
+    Dns(DnsQuery),
+}
+
+/// What has been received, depending on the protocol.
+#[derive(Debug)]
+pub enum Response {
+    /// `connect/tcp`: the connection has been established.
+    Connected,
+    /// `raw/tcp`
+    Bytes(Vec<u8>),
+    /// `text/tcp` and `text/udp`
+    Text(String),
+    /// `tls/raw`
+    TlsBytes(TlsResponse<Vec<u8>>),
+    /// `tls/tcp`
+    TlsText(TlsResponse<String>),
+    /// `starttls/*`
+    StartTls(StartTlsResponse),
+    /// `dialog/tcp`
+    Dialog(Transcript),
+    /// `http/tcp` and `https/tcp`
+    Http(HttpResponse<String>),
+    /// `dns/udp` and `dns/tcp`
+    Dns(DnsResponse),
+}
+
+impl Response {
+    /// The text received, also if it has been received over TLS.
+    pub fn text(&self) -> Option<&str> {
+        match *self {
+            Response::Text(ref text) => Some(text),
+            Response::TlsText(ref response) => Some(&response.response),
+            Response::StartTls(ref response) => Some(&response.response),
+            _ => None,
+        }
+    }
+
+    /// The bytes received, also if they have been received over TLS.
+    pub fn bytes(&self) -> Option<&[u8]> {
+        match *self {
+            Response::Bytes(ref bytes) => Some(bytes),
+            Response::TlsBytes(ref response) => Some(&response.response),
+            _ => None,
+        }
+    }
+
+    /// What has been negotiated, if the connection has been secured by TLS.
+    pub fn tls(&self) -> Option<&TlsInfo> {
+        match *self {
+            Response::TlsBytes(ref response) => Some(&response.tls),
+            Response::TlsText(ref response) => Some(&response.tls),
+            Response::StartTls(ref response) => Some(&response.tls),
+            Response::Http(ref response) => response.tls.as_ref(),
+            _ => None,
+        }
+    }
+}
+
+/// A param a module cannot work with, like Python's `InvalidArgumentError`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ArgumentError {
+    pub argument: String,
+    /// `None` if the param is missing.
+    pub value: Option<ParamValue>,
+    pub reason: String,
+}
+
+impl ArgumentError {
+    pub fn new(argument: &str, value: Option<&ParamValue>, reason: &str) -> ArgumentError {
+        ArgumentError {
+            argument: argument.to_string(),
+            value: value.cloned(),
+            reason: reason.to_string(),
+        }
+    }
+}
+
+impl fmt::Display for ArgumentError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.value {
+            Some(ref value) => write!(f, "'{}': '{}' {}", self.argument, value, self.reason),
+            None => write!(f, "'{}' {}", self.argument, self.reason),
+        }
+    }
+}
+
+/// Typed access to a property's params; a param set to null counts as missing.
+pub struct Args<'a> {
+    params: &'a Params,
+}
+
+impl<'a> Args<'a> {
+    pub fn new(params: &'a Params) -> Args<'a> {
+        Args { params: params }
+    }
+
+    /// Fails for the first param not in `known`.
+    pub fn only(&self, known: &[&str]) -> Result<(), ArgumentError> {
+        let mut names: Vec<&String> = self.params.keys().collect();
+        names.sort();
+        match names.into_iter().find(|name| !known.contains(&&name[..])) {
+            Some(name) => Err(ArgumentError::new(name, self.params.get(name), "is not a known param")),
+            None => Ok(()),
+        }
+    }
+
+    pub fn get(&self, name: &str) -> Option<&'a ParamValue> {
+        match self.params.get(name) {
+            Some(&ParamValue::Null) | None => None,
+            Some(value) => Some(value),
+        }
+    }
+
+    pub fn required(&self, name: &str) -> Result<&'a ParamValue, ArgumentError> {
+        self.get(name).ok_or_else(|| ArgumentError::new(name, None, "is missing"))
+    }
+
+    pub fn port(&self) -> Result<u16, ArgumentError> {
+        let port = try!(self.required("port"));
+        port.as_port().ok_or_else(|| ArgumentError::new("port", Some(port), "is not a valid port number"))
+    }
+
+    pub fn string(&self, name: &str) -> Result<Option<&'a str>, ArgumentError> {
+        match self.get(name) {
+            Some(value) => {
+                value.as_str()
+                     .map(Some)
+                     .ok_or_else(|| ArgumentError::new(name, Some(value), "is not a string"))
+            }
+            None => Ok(None),
+        }
+    }
+
+    pub fn required_string(&self, name: &str) -> Result<&'a str, ArgumentError> {
+        try!(self.required(name));
+        self.string(name).map(|s| s.unwrap_or_default())
+    }
+
+    pub fn bool(&self, name: &str) -> Result<Option<bool>, ArgumentError> {
+        match self.get(name) {
+            Some(value) => {
+                value.as_bool()
+                     .map(Some)
+                     .ok_or_else(|| ArgumentError::new(name, Some(value), "is not a bool"))
+            }
+            None => Ok(None),
+        }
+    }
+
+    /// An integer between `min` and `max`; quoted numbers are accepted as well.
+    pub fn number(&self,
+                  name: &str,
+                  min: i64,
+                  max: i64,
+                  reason: &str)
+                  -> Result<Option<i64>, ArgumentError> {
+        match self.get(name) {
+            Some(value) => number_in(name, value, min, max, reason).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    /// A single number or a list of numbers, each between `min` and `max`; missing is empty.
+    pub fn numbers(&self,
+                   name: &str,
+                   min: i64,
+                   max: i64,
+                   reason: &str)
+                   -> Result<Vec<i64>, ArgumentError> {
+        match self.get(name) {
+            Some(&ParamValue::List(ref l)) => {
+                l.iter().map(|v| number_in(name, v, min, max, reason)).collect()
+            }
+            Some(value) => number_in(name, value, min, max, reason).map(|n| vec![n]),
+            None => Ok(Vec::new()),
+        }
+    }
+
+    /// A single string or a list of strings; missing is empty.
+    pub fn strings(&self, name: &str) -> Result<Vec<&'a str>, ArgumentError> {
+        let value = match self.get(name) {
+            Some(value) => value,
+            None => return Ok(Vec::new()),
+        };
+        let strings: Option<Vec<&str>> = match *value {
+            ParamValue::String(ref s) => Some(vec![s]),
+            ParamValue::List(ref l) => l.iter().map(|v| v.as_str()).collect(),
+            _ => None,
+        };
+        strings.ok_or_else(|| ArgumentError::new(name, Some(value), "is not a string or a list of strings"))
+    }
+
+    /// A regular expression; `anchored` makes it match at the beginning only, like Python's
+    /// `re.match`.
+    pub fn regex(&self, name: &str, anchored: bool) -> Result<Option<Regex>, ArgumentError> {
+        let pattern = match try!(self.string(name)) {
+            Some(pattern) => pattern,
+            None => return Ok(None),
+        };
+        let pattern = if anchored { format!("^(?:{})", pattern) } else { pattern.to_string() };
+        Regex::new(&pattern)
+            .map(Some)
+            .map_err(|_| ArgumentError::new(name, self.get(name), "is not a valid regular expression"))
+    }
+
+    pub fn required_regex(&self, name: &str, anchored: bool) -> Result<Regex, ArgumentError> {
+        try!(self.required(name));
+        self.regex(name, anchored).map(|r| r.expect("regex is present"))
+    }
+}
+
+fn number_in(name: &str,
+             value: &ParamValue,
+             min: i64,
+             max: i64,
+             reason: &str)
+             -> Result<i64, ArgumentError> {
+    let number = match *value {
+        ParamValue::Int(i) => Some(i),
+        ParamValue::String(ref s) => s.trim().parse::<i64>().ok(),
+        _ => None,
+    };
+    match number {
+        Some(n) if n >= min && n <= max => Ok(n),
+        _ => Err(ArgumentError::new(name, Some(value), reason)),
+    }
+}
+
+/// Formats allowed values like Python prints a list, e.g. `['udp', 'tcp']`.
+pub fn one_of(values: &[&str]) -> String {
+    let quoted: Vec<String> = values.iter().map(|v| format!("'{}'", v)).collect();
+    format!("[{}]", quoted.join(", "))
+}
+
+/// Reads until nothing has been received for a while, like `{"until": "idle"}` in Python.
+pub fn until_idle() -> ReadStrategy {
+    ReadStrategy {
+        until: ReadUntil::Idle(Duration::from_millis(DEFAULT_IDLE_TIMEOUT_MS)),
+        ..ReadStrategy::default()
+    }
+}
+
+/// Complains about a response of the wrong kind; this means the protocol does not fit the module.
+pub fn unexpected(response: &Response) -> String {
+    let kind = match *response {
+        Response::Connected => "a connection",
+        Response::Bytes(_) | Response::TlsBytes(_) => "bytes",
+        Response::Text(_) | Response::TlsText(_) => "text",
+        Response::StartTls(_) => "a STARTTLS response",
+        Response::Dialog(_) => "a transcript",
+        Response::Http(_) => "an HTTP response",
+        Response::Dns(_) => "a DNS response",
+    };
+    format!("Unexpected response: received {}.", kind)
+}
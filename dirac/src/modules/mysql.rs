@@ -0,0 +1,82 @@
+use super::*;
+
+/// Passes if the host greets with a MySQL handshake or error packet.
+pub struct Mysql;
+
+impl CheckModule for Mysql {
+    fn check_args(&self, params: &Params) -> Result<(), ArgumentError> {
+        let args = Args::new(params);
+        try!(args.only(&["port"]));
+        try!(args.port());
+
+        Ok(())
+    }
+
+    fn protocol(&self, _: &Params) -> String {
+        "raw/tcp".to_string()
+    }
+
+    fn read_strategy(&self, _: &Params) -> ReadStrategy {
+        // The handshake packet may be split across segments
+        until_idle()
+    }
+
+    fn check_response(&self, _: &Params, response: &Response) -> Result<(), String> {
+        let response = try!(response.bytes().ok_or_else(|| unexpected(response)));
+
+        let valid = match packet_payload(response) {
+            // A host that is not allowed to connect gets an error packet, which shows that
+            // there is a MySQL server, too.
+            Some(payload) => is_error_packet(payload) || is_initial_handshake_packet(payload),
+            None => false,
+        };
+        if !valid {
+            return Err(format!("Did not get expected response: {}", as_hex(response)));
+        }
+
+        Ok(())
+    }
+}
+
+/// Returns the payload of the first packet if the response is exactly that packet.
+///
+/// cf. https://dev.mysql.com/doc/internals/en/mysql-packet.html
+fn packet_payload(response: &[u8]) -> Option<&[u8]> {
+    // 3 bytes payload length and 1 byte sequence id
+    if response.len() < 4 {
+        return None;
+    }
+    let expected_len = response[0] as usize | (response[1] as usize) << 8 | (response[2] as usize) << 16;
+    let payload = &response[4..];
+    if expected_len == 0 || payload.len() != expected_len || response[3] != 0 {
+        return None;
+    }
+
+    Some(payload)
+}
+
+/// cf. https://dev.mysql.com/doc/internals/en/packet-ERR_Packet.html
+fn is_error_packet(payload: &[u8]) -> bool {
+    payload.len() >= 9 && payload[0] == 0xFF
+}
+
+/// cf. https://dev.mysql.com/doc/internals/en/connection-phase-packets.html#packet-Protocol::Handshake
+fn is_initial_handshake_packet(payload: &[u8]) -> bool {
+    // There are variable length strings, but the server version should not be _that_ long
+    if payload.len() < 15 || payload.len() > 128 || payload[0] != 0x0A {
+        return false;
+    }
+    // NUL terminated server version, followed by connection id, auth plugin data and a filler
+    match payload[1..].iter().position(|b| *b == 0x00) {
+        Some(version_len) => {
+            let filler = 1 + version_len + 1 + 4 + 8;
+            filler < payload.len() && payload[filler] == 0x00
+        }
+        None => false,
+    }
+}
+
+fn as_hex(data: &[u8]) -> String {
+    let hex: Vec<String> = data.iter().map(|b| format!("{:02x}", b)).collect();
+    hex.join(" ")
+}
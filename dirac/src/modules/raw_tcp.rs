@@ -0,0 +1,21 @@
+use super::*;
+
+/// Passes if the host answers a TCP connection with anything at all.
+pub struct RawTcp;
+
+impl CheckModule for RawTcp {
+    /// Accepts any params like the Python module; only the port is needed.
+    fn check_args(&self, params: &Params) -> Result<(), ArgumentError> {
+        try!(Args::new(params).port());
+
+        Ok(())
+    }
+
+    fn protocol(&self, _: &Params) -> String {
+        "raw/tcp".to_string()
+    }
+
+    fn check_response(&self, _: &Params, _: &Response) -> Result<(), String> {
+        Ok(())
+    }
+}
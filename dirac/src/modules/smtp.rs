@@ -0,0 +1,88 @@
+use super::*;
+
+/// Checks an SMTP server's greeting, optionally after upgrading the connection via STARTTLS.
+pub struct Smtp;
+
+impl Smtp {
+    fn starttls(params: &Params) -> bool {
+        Args::new(params).string("starttls") == Ok(Some("required"))
+    }
+}
+
+impl CheckModule for Smtp {
+    fn check_args(&self, params: &Params) -> Result<(), ArgumentError> {
+        let args = Args::new(params);
+        try!(args.only(&["port", "software", "proxy", "return_code", "starttls"]));
+        try!(args.port());
+        try!(args.required_regex("software", true));
+        try!(args.required("proxy"));
+        let proxy = try!(args.bool("proxy")).unwrap_or(false);
+        try!(args.required("return_code"));
+        try!(args.number("return_code", 100, 600, "is not a valid return code"));
+        if let Some(starttls) = args.get("starttls") {
+            if starttls.as_str() != Some("required") {
+                return Err(ArgumentError::new("starttls", Some(starttls), "is not 'required'"));
+            }
+            if proxy {
+                return Err(ArgumentError::new("starttls", Some(starttls), "cannot be combined with proxy"));
+            }
+        }
+
+        Ok(())
+    }
+
+    fn protocol(&self, params: &Params) -> String {
+        if Smtp::starttls(params) {
+            "starttls/smtp".to_string()
+        } else {
+            "text/tcp".to_string()
+        }
+    }
+
+    fn challenge(&self, params: &Params) -> Payload {
+        let mut challenge = String::new();
+        if Args::new(params).bool("proxy") == Ok(Some(true)) {
+            challenge.push_str("PROXY TCP4 127.0.0.1 127.0.0.1 63322 25\n");
+        }
+        challenge.push_str("quit\n");
+        Payload::Text(challenge)
+    }
+
+    fn read_strategy(&self, _: &Params) -> ReadStrategy {
+        // Greetings may span several lines and segments
+        until_idle()
+    }
+
+    fn check_response(&self, params: &Params, response: &Response) -> Result<(), String> {
+        let args = Args::new(params);
+        let software_pattern = args.get("software").map(|s| s.to_string()).unwrap_or_default();
+        let software_regex = try!(args.required_regex("software", true).map_err(|err| err.to_string()));
+        let expected_code = try!(args.number("return_code", 100, 600, "is not a valid return code")
+                                     .map_err(|err| err.to_string()))
+                                .unwrap_or_default();
+        let response = if Smtp::starttls(params) {
+            // With STARTTLS, the greeting has been received before the upgrade
+            match *response {
+                Response::StartTls(ref response) => &response.banner[..],
+                _ => return Err("Connection has not been upgraded via STARTTLS.".to_string()),
+            }
+        } else {
+            try!(response.text().ok_or_else(|| unexpected(response)))
+        };
+
+        let return_code = response.split(|c| c == '-' || c == ' ').next().unwrap_or("");
+        let return_code = try!(return_code.parse::<i64>().map_err(|_| {
+            format!("Invalid identification string '{}' in response.", response)
+        }));
+        if return_code != expected_code {
+            return Err(format!("Unexpected result code '{}'; expected '{}'.", return_code, expected_code));
+        }
+        if !software_regex.is_match(response) {
+            return Err(format!("Unexpected software version '{}'; expected to match against '{}'.",
+                               response,
+                               software_pattern));
+        }
+
+        Ok(())
+    }
+}
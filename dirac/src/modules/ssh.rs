@@ -0,0 +1,58 @@
+use super::*;
+
+/// Checks the SSH identification string; cf. RFC4253, section 4.2.
+pub struct Ssh;
+
+impl CheckModule for Ssh {
+    fn check_args(&self, params: &Params) -> Result<(), ArgumentError> {
+        let args = Args::new(params);
+        try!(args.only(&["port", "version", "software"]));
+        try!(args.port());
+        try!(args.required("version"));
+        try!(args.required_regex("software", true));
+
+        Ok(())
+    }
+
+    fn protocol(&self, _: &Params) -> String {
+        "text/tcp".to_string()
+    }
+
+    fn read_strategy(&self, _: &Params) -> ReadStrategy {
+        // The identification string is a single line
+        ReadStrategy {
+            until: ReadUntil::Delimiter(b"\n".to_vec()),
+            ..ReadStrategy::default()
+        }
+    }
+
+    fn check_response(&self, params: &Params, response: &Response) -> Result<(), String> {
+        let args = Args::new(params);
+        // YAML turns unquoted versions like 2.0 into floats
+        let expected_version = args.get("version").map(|v| v.to_string()).unwrap_or_default();
+        let software_pattern = args.get("software").map(|s| s.to_string()).unwrap_or_default();
+        let software_regex = try!(args.required_regex("software", true).map_err(|err| err.to_string()));
+        let response = try!(response.text().ok_or_else(|| unexpected(response)));
+
+        let identification = response.trim().split(' ').next().unwrap_or("");
+        let parts: Vec<&str> = identification.splitn(3, '-').collect();
+        if parts.len() != 3 {
+            return Err(format!("Invalid identification string '{}' in response; cf. RFC4253, section 4.2.",
+                               response));
+        }
+        let (ssh, version, software) = (parts[0], parts[1], parts[2]);
+        if ssh != "SSH" {
+            return Err(format!("Invalid prefix '{}' in response; cf. RFC4253, section 4.2.", ssh));
+        }
+        if version != expected_version {
+            return Err(format!("Unexpected version '{}'; expected '{}'.", version, expected_version));
+        }
+        if !software_regex.is_match(software) {
+            return Err(format!("Unexpected software version '{}'; expected to match against '{}'.",
+                               software,
+                               software_pattern));
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,138 @@
+use regex::Regex;
+use std::fmt;
+use std::io::{Error, ErrorKind};
+use std::io::prelude::*;
+use std::net::TcpStream;
+use std::cmp;
+use std::str;
+use std::time::{Duration, Instant};
+
+use super::read::DEFAULT_MAX_SIZE;
+
+/// One step of a conversation: optionally send something, then optionally wait for a reply
+/// matching `expect`.
+#[derive(Debug, Clone, Default)]
+pub struct DialogStep {
+    pub send: Option<String>,
+    /// Regular expression the data received during this step has to match.
+    pub expect: Option<String>,
+    /// How long to wait for `expect`; defaults to the property's timeout.
+    pub timeout: Option<Duration>,
+}
+
+/// What happened during one step.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DialogStepResult {
+    pub sent: Option<String>,
+    pub received: String,
+    pub expect: Option<String>,
+    /// Whether `expect` has been matched in time; `None` if there has been nothing to expect.
+    pub matched: Option<bool>,
+}
+
+/// The steps that have been run; the dialog stops after the first step that did not match.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Transcript(pub Vec<DialogStepResult>);
+
+impl fmt::Display for Transcript {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for step in &self.0 {
+            if let Some(ref sent) = step.sent {
+                for line in sent.lines() {
+                    try!(writeln!(f, "> {}", line));
+                }
+            }
+            for line in step.received.lines() {
+                try!(writeln!(f, "< {}", line));
+            }
+        }
+        Ok(())
+    }
+}
+
+pub fn run(mut stream: TcpStream,
+           steps: &[DialogStep],
+           default_timeout: Duration)
+           -> Result<Transcript, Error> {
+    let mut patterns = Vec::with_capacity(steps.len());
+    for step in steps {
+        let pattern = match step.expect {
+            Some(ref expect) => {
+                Some(try!(Regex::new(expect).map_err(|err| {
+                    Error::new(ErrorKind::InvalidInput, format!("invalid regex '{}': {}", expect, err))
+                })))
+            }
+            None => None,
+        };
+        patterns.push(pattern);
+    }
+
+    let mut transcript = Vec::with_capacity(steps.len());
+    // What has been received after the match of the previous step, e.g. the next prompt
+    let mut pending = Vec::new();
+    for (step, pattern) in steps.iter().zip(patterns.iter()) {
+        if let Some(ref send) = step.send {
+            try!(stream.write_all(send.as_bytes()));
+            debug!("- Sent '{}'.", send);
+        }
+        let (received, matched) = match *pattern {
+            Some(ref pattern) => {
+                let timeout = step.timeout.unwrap_or(default_timeout);
+                let (received, matched) = try!(receive_until(&mut stream, pattern, timeout, &mut pending));
+                (received, Some(matched))
+            }
+            None => (String::new(), None),
+        };
+        debug!("- Received '{}', matched '{:?}'.", received, matched);
+        transcript.push(DialogStepResult {
+            sent: step.send.clone(),
+            received: received,
+            expect: step.expect.clone(),
+            matched: matched,
+        });
+        if matched == Some(false) {
+            break;
+        }
+    }
+
+    Ok(Transcript(transcript))
+}
+
+/// Reads until the data received matches `pattern`, the peer closes the connection, `timeout`
+/// has passed or `DEFAULT_MAX_SIZE` bytes have been received.
+///
+/// Reading starts with `pending`, which is left with the lines following the line of the match,
+/// e.g. a second prompt sent in the same segment.
+fn receive_until(stream: &mut TcpStream,
+                 pattern: &Regex,
+                 timeout: Duration,
+                 pending: &mut Vec<u8>)
+                 -> Result<(String, bool), Error> {
+    let deadline = Instant::now() + timeout;
+    let mut received = pending.split_off(0);
+    let mut buf = [0u8; 1024];
+    loop {
+        let text = String::from_utf8_lossy(&received).into_owned();
+        if let Some((_, end)) = pattern.find(&text) {
+            // Offsets into the text are offsets into the bytes only if they are valid UTF-8.
+            if str::from_utf8(&received).is_ok() {
+                let line_end = text[end..].find('\n').map_or(text.len(), |i| end + i + 1);
+                *pending = received.split_off(line_end);
+                return Ok((text[..line_end].to_string(), true));
+            }
+            return Ok((text, true));
+        }
+        let now = Instant::now();
+        if now >= deadline || received.len() >= DEFAULT_MAX_SIZE {
+            return Ok((text, false));
+        }
+        try!(stream.set_read_timeout(Some(deadline - now)));
+        let len = cmp::min(buf.len(), DEFAULT_MAX_SIZE - received.len());
+        match stream.read(&mut buf[..len]) {
+            Ok(0) => return Ok((text, false)),
+            Ok(len) => received.extend_from_slice(&buf[..len]),
+            Err(ref err) if super::is_timeout(err) => return Ok((text, false)),
+            Err(err) => return Err(err),
+        }
+    }
+}
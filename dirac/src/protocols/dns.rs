@@ -0,0 +1,412 @@
+use openssl::rand;
+use std::fmt;
+use std::io::{Error, ErrorKind};
+use std::net::IpAddr;
+use std::str::FromStr;
+
+/// Record types a query may ask for.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RecordType {
+    A,
+    Ns,
+    Cname,
+    Soa,
+    Ptr,
+    Mx,
+    Txt,
+    Aaaa,
+    Srv,
+}
+
+impl RecordType {
+    fn code(&self) -> u16 {
+        match *self {
+            RecordType::A => 1,
+            RecordType::Ns => 2,
+            RecordType::Cname => 5,
+            RecordType::Soa => 6,
+            RecordType::Ptr => 12,
+            RecordType::Mx => 15,
+            RecordType::Txt => 16,
+            RecordType::Aaaa => 28,
+            RecordType::Srv => 33,
+        }
+    }
+
+    fn from_code(code: u16) -> Option<RecordType> {
+        let record_type = match code {
+            1 => RecordType::A,
+            2 => RecordType::Ns,
+            5 => RecordType::Cname,
+            6 => RecordType::Soa,
+            12 => RecordType::Ptr,
+            15 => RecordType::Mx,
+            16 => RecordType::Txt,
+            28 => RecordType::Aaaa,
+            33 => RecordType::Srv,
+            _ => return None,
+        };
+        Some(record_type)
+    }
+}
+
+impl FromStr for RecordType {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<RecordType, Error> {
+        match &s.to_uppercase()[..] {
+            "A" => Ok(RecordType::A),
+            "NS" => Ok(RecordType::Ns),
+            "CNAME" => Ok(RecordType::Cname),
+            "SOA" => Ok(RecordType::Soa),
+            "PTR" => Ok(RecordType::Ptr),
+            "MX" => Ok(RecordType::Mx),
+            "TXT" => Ok(RecordType::Txt),
+            "AAAA" => Ok(RecordType::Aaaa),
+            "SRV" => Ok(RecordType::Srv),
+            _ => Err(Error::new(ErrorKind::InvalidInput, format!("unknown DNS record type '{}'", s))),
+        }
+    }
+}
+
+impl fmt::Display for RecordType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match *self {
+            RecordType::A => "A",
+            RecordType::Ns => "NS",
+            RecordType::Cname => "CNAME",
+            RecordType::Soa => "SOA",
+            RecordType::Ptr => "PTR",
+            RecordType::Mx => "MX",
+            RecordType::Txt => "TXT",
+            RecordType::Aaaa => "AAAA",
+            RecordType::Srv => "SRV",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct DnsQuery {
+    /// Name to look up; for `PTR` queries, an IP address is turned into its reverse name.
+    pub name: String,
+    pub record_type: RecordType,
+    /// Asks the server to resolve the name recursively.
+    pub recursion_desired: bool,
+}
+
+impl DnsQuery {
+    pub fn new(name: &str, record_type: RecordType) -> DnsQuery {
+        DnsQuery {
+            name: name.to_string(),
+            record_type: record_type,
+            recursion_desired: true,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct DnsRecord {
+    /// Fully qualified, e.g. `example.com.`.
+    pub name: String,
+    /// E.g. `A`, or `TYPE99` for types without a name.
+    pub record_type: String,
+    pub ttl: u32,
+    /// Presentation format as printed by `dig`, e.g. `10 mail.example.com.` for `MX`.
+    pub data: String,
+}
+
+impl fmt::Display for DnsRecord {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} {} IN {} {}", self.name, self.ttl, self.record_type, self.data)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct DnsResponse {
+    /// E.g. `NOERROR` or `NXDOMAIN`.
+    pub rcode: String,
+    pub authoritative: bool,
+    /// The answer did not fit into a datagram; retry over TCP.
+    pub truncated: bool,
+    pub recursion_available: bool,
+    pub answers: Vec<DnsRecord>,
+    pub authority: Vec<DnsRecord>,
+}
+
+impl fmt::Display for DnsResponse {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        try!(write!(f, "{}", self.rcode));
+        if self.authoritative {
+            try!(write!(f, ", authoritative"));
+        }
+        if self.truncated {
+            try!(write!(f, ", truncated"));
+        }
+        for record in self.answers.iter().chain(self.authority.iter()) {
+            try!(write!(f, "\n{}", record));
+        }
+        Ok(())
+    }
+}
+
+/// Returns a random id for a new query, so that off-path replies cannot easily guess it.
+pub fn query_id() -> Result<u16, Error> {
+    let mut id = [0u8; 2];
+    try!(rand::rand_bytes(&mut id).map_err(|err| Error::new(ErrorKind::Other, err)));
+    Ok(read_u16(&id, 0))
+}
+
+/// Whether `message` carries `id`, i.e. may be the response to the query sent with it.
+pub fn has_id(message: &[u8], id: u16) -> bool {
+    message.len() >= 2 && read_u16(message, 0) == id
+}
+
+pub fn encode_query(id: u16, query: &DnsQuery) -> Result<Vec<u8>, Error> {
+    let flags: u16 = if query.recursion_desired { 0x0100 } else { 0 };
+    let mut message = Vec::with_capacity(512);
+    push_u16(&mut message, id);
+    push_u16(&mut message, flags);
+    // One question, no answer, authority or additional records
+    for count in &[1, 0, 0, 0] {
+        push_u16(&mut message, *count);
+    }
+    let name = match (query.record_type, query.name.parse::<IpAddr>()) {
+        (RecordType::Ptr, Ok(addr)) => reverse_name(addr),
+        _ => query.name.clone(),
+    };
+    try!(encode_name(&mut message, &name));
+    push_u16(&mut message, query.record_type.code());
+    push_u16(&mut message, 1); // IN
+
+    Ok(message)
+}
+
+pub fn decode_response(id: u16, message: &[u8]) -> Result<DnsResponse, Error> {
+    if message.len() < 12 {
+        return Err(malformed("message shorter than its header"));
+    }
+    if read_u16(message, 0) != id {
+        return Err(malformed("response id does not match the query"));
+    }
+    let flags = read_u16(message, 2);
+    if flags & 0x8000 == 0 {
+        return Err(malformed("message is not a response"));
+    }
+    let counts: Vec<usize> = (0..4).map(|i| read_u16(message, 4 + 2 * i) as usize).collect();
+
+    let mut offset = 12;
+    for _ in 0..counts[0] {
+        let (_, next) = try!(decode_name(message, offset));
+        offset = next + 4;
+    }
+    let mut answers = Vec::with_capacity(counts[1]);
+    for _ in 0..counts[1] {
+        let (record, next) = try!(decode_record(message, offset));
+        answers.push(record);
+        offset = next;
+    }
+    let mut authority = Vec::with_capacity(counts[2]);
+    for _ in 0..counts[2] {
+        let (record, next) = try!(decode_record(message, offset));
+        authority.push(record);
+        offset = next;
+    }
+
+    Ok(DnsResponse {
+        rcode: rcode_name(flags & 0x000f),
+        authoritative: flags & 0x0400 != 0,
+        truncated: flags & 0x0200 != 0,
+        recursion_available: flags & 0x0080 != 0,
+        answers: answers,
+        authority: authority,
+    })
+}
+
+fn rcode_name(rcode: u16) -> String {
+    let name = match rcode {
+        0 => "NOERROR",
+        1 => "FORMERR",
+        2 => "SERVFAIL",
+        3 => "NXDOMAIN",
+        4 => "NOTIMP",
+        5 => "REFUSED",
+        _ => return format!("RCODE{}", rcode),
+    };
+    name.to_string()
+}
+
+/// Turns `192.0.2.1` into `1.2.0.192.in-addr.arpa`.
+fn reverse_name(addr: IpAddr) -> String {
+    match addr {
+        IpAddr::V4(v4) => {
+            let o = v4.octets();
+            format!("{}.{}.{}.{}.in-addr.arpa", o[3], o[2], o[1], o[0])
+        }
+        IpAddr::V6(v6) => {
+            let nibbles: Vec<String> = v6.octets()
+                                         .iter()
+                                         .rev()
+                                         .flat_map(|b| vec![b & 0x0f, b >> 4])
+                                         .map(|n| format!("{:x}", n))
+                                         .collect();
+            format!("{}.ip6.arpa", nibbles.join("."))
+        }
+    }
+}
+
+fn encode_name(message: &mut Vec<u8>, name: &str) -> Result<(), Error> {
+    let mut len = 1;
+    for label in name.split('.').filter(|label| !label.is_empty()) {
+        if label.len() > 63 {
+            return Err(Error::new(ErrorKind::InvalidInput,
+                                  format!("label '{}' of '{}' is longer than 63 bytes", label, name)));
+        }
+        len += label.len() + 1;
+        message.push(label.len() as u8);
+        message.extend_from_slice(label.as_bytes());
+    }
+    if len > 255 {
+        return Err(Error::new(ErrorKind::InvalidInput,
+                              format!("name '{}' is longer than 255 bytes", name)));
+    }
+    message.push(0);
+
+    Ok(())
+}
+
+/// Reads a possibly compressed name and returns it with the offset following it.
+fn decode_name(message: &[u8], offset: usize) -> Result<(String, usize), Error> {
+    let mut name = String::new();
+    let mut position = offset;
+    let mut end = None;
+    // Every pointer has to point backwards; this bounds the number of jumps.
+    let mut jumps = 0;
+    loop {
+        let len = match message.get(position) {
+            Some(len) => *len as usize,
+            None => return Err(malformed("name exceeds the message")),
+        };
+        match len & 0xc0 {
+            0x00 if len == 0 => {
+                let end = end.unwrap_or(position + 1);
+                if name.is_empty() {
+                    name.push('.');
+                }
+                return Ok((name, end));
+            }
+            0x00 => {
+                let label = try!(message.get(position + 1..position + 1 + len)
+                                        .ok_or_else(|| malformed("label exceeds the message")));
+                name.push_str(&String::from_utf8_lossy(label));
+                name.push('.');
+                position += 1 + len;
+            }
+            0xc0 => {
+                let pointer = try!(message.get(position + 1)
+                                          .ok_or_else(|| malformed("pointer exceeds the message")));
+                let target = (len & 0x3f) << 8 | *pointer as usize;
+                jumps += 1;
+                if target >= position || jumps > 127 {
+                    return Err(malformed("name compression loops"));
+                }
+                if end.is_none() {
+                    end = Some(position + 2);
+                }
+                position = target;
+            }
+            _ => return Err(malformed("unknown label type")),
+        }
+    }
+}
+
+fn decode_record(message: &[u8], offset: usize) -> Result<(DnsRecord, usize), Error> {
+    let (name, offset) = try!(decode_name(message, offset));
+    if message.len() < offset + 10 {
+        return Err(malformed("record exceeds the message"));
+    }
+    let code = read_u16(message, offset);
+    let ttl = (read_u16(message, offset + 4) as u32) << 16 | read_u16(message, offset + 6) as u32;
+    let len = read_u16(message, offset + 8) as usize;
+    let start = offset + 10;
+    let rdata = try!(message.get(start..start + len)
+                            .ok_or_else(|| malformed("record data exceeds the message")));
+
+    let data = match RecordType::from_code(code) {
+        Some(RecordType::A) if len == 4 => {
+            format!("{}.{}.{}.{}", rdata[0], rdata[1], rdata[2], rdata[3])
+        }
+        Some(RecordType::Aaaa) if len == 16 => {
+            let mut octets = [0u8; 16];
+            octets.copy_from_slice(rdata);
+            IpAddr::from(octets).to_string()
+        }
+        Some(RecordType::Ns) | Some(RecordType::Cname) | Some(RecordType::Ptr) => {
+            try!(decode_name(message, start)).0
+        }
+        Some(RecordType::Mx) if len > 2 => {
+            format!("{} {}", read_u16(message, start), try!(decode_name(message, start + 2)).0)
+        }
+        Some(RecordType::Srv) if len > 6 => {
+            format!("{} {} {} {}",
+                    read_u16(message, start),
+                    read_u16(message, start + 2),
+                    read_u16(message, start + 4),
+                    try!(decode_name(message, start + 6)).0)
+        }
+        Some(RecordType::Txt) => {
+            let mut strings = Vec::new();
+            let mut position = 0;
+            while position < len {
+                let string_len = rdata[position] as usize;
+                let string = try!(rdata.get(position + 1..position + 1 + string_len)
+                                       .ok_or_else(|| malformed("TXT string exceeds the record")));
+                strings.push(format!("{:?}", String::from_utf8_lossy(string)));
+                position += 1 + string_len;
+            }
+            strings.join(" ")
+        }
+        Some(RecordType::Soa) => {
+            let (mname, next) = try!(decode_name(message, start));
+            let (rname, next) = try!(decode_name(message, next));
+            if next + 20 > start + len {
+                return Err(malformed("SOA record too short"));
+            }
+            let numbers: Vec<String> = (0..5)
+                                           .map(|i| {
+                                               (read_u16(message, next + 4 * i) as u32) << 16 |
+                                               read_u16(message, next + 4 * i + 2) as u32
+                                           })
+                                           .map(|n| n.to_string())
+                                           .collect();
+            format!("{} {} {}", mname, rname, numbers.join(" "))
+        }
+        _ => rdata.iter().map(|b| format!("{:02x}", b)).collect::<Vec<String>>().join(""),
+    };
+    let record_type = match RecordType::from_code(code) {
+        Some(record_type) => record_type.to_string(),
+        None => format!("TYPE{}", code),
+    };
+
+    Ok((DnsRecord {
+        name: name,
+        record_type: record_type,
+        ttl: ttl,
+        data: data,
+    },
+        start + len))
+}
+
+fn push_u16(message: &mut Vec<u8>, value: u16) {
+    message.push((value >> 8) as u8);
+    message.push(value as u8);
+}
+
+/// Reads a big-endian `u16`; callers make sure `offset + 1` is within `message`.
+fn read_u16(message: &[u8], offset: usize) -> u16 {
+    (message[offset] as u16) << 8 | message[offset + 1] as u16
+}
+
+fn malformed(reason: &str) -> Error {
+    Error::new(ErrorKind::InvalidData, format!("malformed DNS message: {}", reason))
+}
@@ -1,14 +1,53 @@
+use hyper;
 use hyper::client::{Client, RedirectPolicy};
+use hyper::header::Headers;
+use hyper::method::Method;
+use hyper::net::{HttpStream, HttpsConnector, NetworkConnector};
 use std::collections::HashMap;
-use std::io::Error;
+use std::io::{Error, ErrorKind};
 use std::io::prelude::*;
-use std::net::{TcpStream, UdpSocket};
-use std::time::Duration;
+use std::net::{TcpStream, ToSocketAddrs, UdpSocket};
+use std::time::{Duration, Instant};
+
+pub use self::dialog::{DialogStep, DialogStepResult, Transcript};
+pub use self::dns::{DnsQuery, DnsRecord, DnsResponse, RecordType};
+pub use self::read::{DEFAULT_MAX_SIZE, ReadStrategy, ReadUntil};
+pub use self::starttls::StartTlsService;
+pub use self::tls::{Certificate, TlsInfo};
+
+mod dialog;
+mod dns;
+mod read;
+mod starttls;
+mod tls;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Timeouts {
+    /// Maximum time to wait for a response once connected.
+    pub timeout: Duration,
+    /// Maximum time to wait for a TCP connection to be established.
+    pub connect_timeout: Duration,
+    /// How often a UDP request is sent again if no response arrives; every retransmission waits
+    /// twice as long as the attempt before.
+    pub retries: u32,
+}
+
+impl Default for Timeouts {
+    fn default() -> Timeouts {
+        Timeouts {
+            timeout: Duration::from_secs(1),
+            connect_timeout: Duration::from_secs(5),
+            retries: 0,
+        }
+    }
+}
 
 pub struct Challenge<'a, T> {
     host: &'a str,
     port: u16,
     data: Option<T>,
+    timeouts: Timeouts,
+    read: ReadStrategy,
 }
 
 pub type NoData = ();
@@ -16,9 +55,24 @@ pub type NoData = ();
 pub trait Protocol<'a, S, T, V> {
     fn new(host: &'a str, port: u16) -> S;
     fn set_data(self: &mut Self, data: T);
+    fn set_timeouts(self: &mut Self, timeouts: Timeouts);
+    /// Sets how the response is read; only honoured by protocols reading raw bytes or text, and by
+    /// HTTP for the size of the body.
+    fn set_read_strategy(self: &mut Self, read: ReadStrategy);
     fn send_challenge(self: &Self) -> Result<V, Error>;
 }
 
+/// Tells whether `err` has been caused by a connect or read timeout.
+///
+/// Depending on the platform, a socket read timeout is reported either as `WouldBlock` or as
+/// `TimedOut`.
+pub fn is_timeout(err: &Error) -> bool {
+    match err.kind() {
+        ErrorKind::TimedOut | ErrorKind::WouldBlock => true,
+        _ => false,
+    }
+}
+
 macro_rules! create_protocol {
     ($protocol_name:ident, $data_type:ty, $protocol_response_name: ident, $response_type:ty, $sel:ident, $sender: block) => (
         pub struct $protocol_name<'a>(Challenge<'a, $data_type>);
@@ -31,6 +85,8 @@ macro_rules! create_protocol {
                     host: host,
                     port: port,
                     data: None,
+                    timeouts: Timeouts::default(),
+                    read: ReadStrategy::default(),
                 })
             }
 
@@ -39,6 +95,16 @@ macro_rules! create_protocol {
                     challenge.data = Some(data);
             }
 
+            fn set_timeouts(self: &mut Self, timeouts: Timeouts) {
+                    let $protocol_name(ref mut challenge) = *self;
+                    challenge.timeouts = timeouts;
+            }
+
+            fn set_read_strategy(self: &mut Self, read: ReadStrategy) {
+                    let $protocol_name(ref mut challenge) = *self;
+                    challenge.read = read;
+            }
+
             fn send_challenge($sel: &Self) -> Result<$protocol_response_name, Error> $sender
 
         }
@@ -47,7 +113,7 @@ macro_rules! create_protocol {
 
 create_protocol!(TcpConnect, NoData, TcpConnectResponse, NoData, self, {
     let TcpConnect(ref challenge) = *self;
-    let _ = try!(TcpStream::connect((challenge.host, challenge.port)));
+    let _ = try!(tcp_connect(challenge.host, challenge.port, challenge.timeouts.connect_timeout));
 
     Ok(TcpConnectResponse(()))
 });
@@ -60,7 +126,7 @@ create_protocol!(TcpRaw, Vec<u8>, TcpRawResponse, Vec<u8>, self, {
             let empty: &[u8] = &[0u8; 0];
             empty
         };
-        let response_bytes = try!(tcp_stream_send_recv(challenge.host, challenge.port, bytes));
+        let response_bytes = try!(tcp_stream_send_recv(challenge, bytes));
 
         Ok(TcpRawResponse(response_bytes))
     }
@@ -74,7 +140,7 @@ create_protocol!(TcpText, String, TcpTextResponse, String, self, {
         let empty: &[u8] = &[0u8; 0];
         empty
     };
-    let response_bytes = try!(tcp_stream_send_recv(challenge.host, challenge.port, bytes));
+    let response_bytes = try!(tcp_stream_send_recv(challenge, bytes));
     let string = String::from_utf8_lossy(&response_bytes.as_slice()).to_string();
 
     Ok(TcpTextResponse(string))
@@ -88,20 +154,194 @@ create_protocol!(UdpText, String, UdpTextResponse, String, self, {
         let empty: &[u8] = &[0u8; 0];
         empty
     };
-    let response_bytes = try!(udp_datagram_send_recv(challenge.host, challenge.port, bytes));
+    let response_bytes = try!(udp_datagram_send_recv(challenge, bytes, |_| true));
     let string = String::from_utf8_lossy(&response_bytes.as_slice()).to_string();
 
     Ok(UdpTextResponse(string))
 });
 
 
+create_protocol!(TcpDialog, Vec<DialogStep>, TcpDialogResponse, Transcript, self, {
+    let TcpDialog(ref challenge) = *self;
+    let steps = match challenge.data {
+        Some(ref steps) => &steps[..],
+        None => &[],
+    };
+    let stream = try!(tcp_stream_connect(challenge));
+    let transcript = try!(dialog::run(stream, steps, challenge.timeouts.timeout));
+
+    Ok(TcpDialogResponse(transcript))
+});
+
+create_protocol!(DnsUdp, DnsQuery, DnsUdpResponse, DnsResponse, self, {
+    let DnsUdp(ref challenge) = *self;
+    let query = match challenge.data {
+        Some(ref query) => query,
+        None => return Err(Error::new(ErrorKind::InvalidInput, "no DNS query given")),
+    };
+    let id = try!(dns::query_id());
+    let message = try!(dns::encode_query(id, query));
+    let response = try!(udp_datagram_send_recv(challenge, &message, |reply| dns::has_id(reply, id)));
+
+    Ok(DnsUdpResponse(try!(dns::decode_response(id, &response))))
+});
+
+create_protocol!(DnsTcp, DnsQuery, DnsTcpResponse, DnsResponse, self, {
+    let DnsTcp(ref challenge) = *self;
+    let query = match challenge.data {
+        Some(ref query) => query,
+        None => return Err(Error::new(ErrorKind::InvalidInput, "no DNS query given")),
+    };
+    let id = try!(dns::query_id());
+    let message = try!(dns::encode_query(id, query));
+    let mut stream = try!(tcp_stream_connect(challenge));
+    // Over TCP, every message is preceded by its length
+    let len = [(message.len() >> 8) as u8, message.len() as u8];
+    try!(stream.write_all(&len));
+    try!(stream.write_all(&message));
+    debug!("- Sent DNS query for '{}'.", query.name);
+
+    let mut len = [0u8; 2];
+    try!(stream.read_exact(&mut len));
+    let mut response = vec![0u8; (len[0] as usize) << 8 | len[1] as usize];
+    try!(stream.read_exact(&mut response));
+
+    Ok(DnsTcpResponse(try!(dns::decode_response(id, &response))))
+});
+
+/// What to send over a TLS connection, and which server name to ask for.
+#[derive(Debug, Clone, Default)]
+pub struct TlsData<T> {
+    /// Name sent via SNI and verified against the certificate; defaults to the host.
+    pub server_name: Option<String>,
+    pub payload: Option<T>,
+}
+
+#[derive(Debug)]
+pub struct TlsResponse<T> {
+    pub response: T,
+    pub tls: TlsInfo,
+}
+
+create_protocol!(TlsRaw, TlsData<Vec<u8>>, TlsRawResponse, TlsResponse<Vec<u8>>, self, {
+    let TlsRaw(ref challenge) = *self;
+    let response = try!(tls_stream_send_recv(challenge));
+
+    Ok(TlsRawResponse(response))
+});
+
+create_protocol!(TlsText, TlsData<String>, TlsTextResponse, TlsResponse<String>, self, {
+    let TlsText(ref challenge) = *self;
+    let response = try!(tls_stream_send_recv(challenge));
+    let string = String::from_utf8_lossy(&response.response.as_slice()).to_string();
+
+    Ok(TlsTextResponse(TlsResponse {
+        response: string,
+        tls: response.tls,
+    }))
+});
+
+pub struct StartTlsData {
+    pub service: StartTlsService,
+    /// Name sent via SNI and verified against the certificate; defaults to the host.
+    pub server_name: Option<String>,
+    /// Sent once the connection has been upgraded.
+    pub payload: Option<String>,
+}
+
+#[derive(Debug)]
+pub struct StartTlsResponse {
+    /// The server's plaintext greeting.
+    pub banner: String,
+    /// The reply to the payload; empty if there has been no payload.
+    pub response: String,
+    pub tls: TlsInfo,
+}
+
+create_protocol!(StartTlsText, StartTlsData, StartTlsTextResponse, StartTlsResponse, self, {
+    let StartTlsText(ref challenge) = *self;
+    let data = match challenge.data {
+        Some(ref data) => data,
+        None => return Err(Error::new(ErrorKind::InvalidInput, "no STARTTLS service given")),
+    };
+    let mut stream = try!(tcp_stream_connect(challenge));
+    let banner = try!(starttls::preamble(&mut stream, data.service, challenge.timeouts.timeout));
+    debug!("- {} server '{}/{}' agreed to STARTTLS.", data.service, challenge.host, challenge.port);
+    let server_name = data.server_name.as_ref().map(|s| &s[..]).unwrap_or(challenge.host);
+    let stream = try!(tls::handshake(stream, server_name));
+    let tls = tls::tls_info(stream.ssl());
+    debug!("- TLS handshake with '{}/{}': {}.", challenge.host, challenge.port, tls);
+    let response = match data.payload {
+        Some(ref payload) => try!(stream_send_recv(stream, challenge, payload.as_bytes())),
+        None => Vec::new(),
+    };
+
+    Ok(StartTlsTextResponse(StartTlsResponse {
+        banner: banner,
+        response: String::from_utf8_lossy(&response).into_owned(),
+        tls: tls,
+    }))
+});
+
+/// Maximum number of redirects followed for a single HTTP request.
+pub const MAX_REDIRECTS: usize = 10;
+
+#[derive(Debug, Clone)]
+pub struct HttpRequest {
+    pub verb: String,
+    pub uri: String,
+    pub headers: Vec<(String, String)>,
+    pub body: Option<String>,
+    /// Whether redirects are followed up to `MAX_REDIRECTS` times or returned as the response.
+    pub follow_redirects: bool,
+    /// Name sent in the Host header and via SNI; defaults to the host connected to.
+    pub server_name: Option<String>,
+}
+
+impl HttpRequest {
+    pub fn new(verb: &str, uri: &str) -> HttpRequest {
+        HttpRequest {
+            verb: verb.to_uppercase(),
+            uri: uri.to_string(),
+            headers: Vec::new(),
+            body: None,
+            follow_redirects: false,
+            server_name: None,
+        }
+    }
+
+    /// Parses the short form `"<verb> <uri>"`.
+    pub fn parse(s: &str) -> Option<HttpRequest> {
+        let parts: Vec<&str> = s.split_whitespace().collect();
+        if parts.len() == 2 {
+            Some(HttpRequest::new(parts[0], parts[1]))
+        } else {
+            None
+        }
+    }
+}
+
+/// A redirect that has been followed on the way to the final response.
+#[derive(Debug, Clone, PartialEq)]
+pub struct HttpRedirect {
+    pub response_code: u16,
+    /// The `Location` header exactly as sent by the server.
+    pub location: String,
+}
+
+#[derive(Debug)]
 pub struct HttpResponse<T> {
     pub response_code: u16,
     pub headers: HashMap<String, String>,
     pub body: T,
+    pub redirects: Vec<HttpRedirect>,
+    /// The outcome of the TLS handshake for HTTPS requests.
+    pub tls: Option<TlsInfo>,
+    /// Whether the body has been cut off after the read strategy's `max_size` bytes.
+    pub body_truncated: bool,
 }
 
-create_protocol!(TcpHttp, String, TcpHttpTextResponse, HttpResponse<String>, self, {
+create_protocol!(TcpHttp, HttpRequest, TcpHttpTextResponse, HttpResponse<String>, self, {
         let TcpHttp(ref challenge) = *self;
         let response_data = try!(http_send_recv("http", challenge));
 
@@ -109,7 +349,7 @@ create_protocol!(TcpHttp, String, TcpHttpTextResponse, HttpResponse<String>, sel
     }
 );
 
-create_protocol!(TcpHttps, String, TcpHttpsTextResponse, HttpResponse<String>, self, {
+create_protocol!(TcpHttps, HttpRequest, TcpHttpsTextResponse, HttpResponse<String>, self, {
         let TcpHttps(ref challenge) = *self;
         let response_data = try!(http_send_recv("https", challenge));
 
@@ -118,78 +358,327 @@ create_protocol!(TcpHttps, String, TcpHttpsTextResponse, HttpResponse<String>, s
 );
 
 
-fn tcp_stream_send_recv(host: &str, port: u16, bytes: &[u8]) -> Result<Vec<u8>, Error> {
-    let mut stream = try!(TcpStream::connect((host, port)));
-    let dur = Duration::new(1, 0);
-    stream.set_read_timeout(Some(dur)).unwrap();
-
-    if bytes.len() > 0 {
-        let tx_res = try!(stream.write(&bytes));
-        // TODO: Assert to real check
-        assert_eq!(tx_res, bytes.len());
-        debug!("- Sent data '{:?}'.", bytes);
+/// Connects to the first address `host` resolves to that accepts a connection within `timeout`.
+fn tcp_connect(host: &str, port: u16, timeout: Duration) -> Result<TcpStream, Error> {
+    let mut last_err = Error::new(ErrorKind::InvalidInput,
+                                  format!("could not resolve '{}'", host));
+    for addr in try!((host, port).to_socket_addrs()) {
+        match TcpStream::connect_timeout(&addr, timeout) {
+            Ok(stream) => return Ok(stream),
+            Err(err) => last_err = err,
+        }
     }
 
-    let mut response_bytes = [0u8; 1024];
-    let rx_len = try!(stream.read(&mut response_bytes));
-    debug!("- Received result from '{}/{}', '{}' bytes.",
-           host,
-           port,
-           rx_len);
+    Err(last_err)
+}
 
-    let v = From::from(&response_bytes[0..rx_len]);
+fn tcp_stream_send_recv<T>(challenge: &Challenge<T>, bytes: &[u8]) -> Result<Vec<u8>, Error> {
+    let stream = try!(tcp_stream_connect(challenge));
 
-    Ok(v)
+    stream_send_recv(stream, challenge, bytes)
 }
 
-fn udp_datagram_send_recv(host: &str, port: u16, bytes: &[u8]) -> Result<Vec<u8>, Error> {
-    let socket = try!(UdpSocket::bind(("0.0.0.0", 18181)));
-    let dur = Duration::new(1, 0);
-    socket.set_read_timeout(Some(dur)).unwrap();
+fn tcp_stream_connect<T>(challenge: &Challenge<T>) -> Result<TcpStream, Error> {
+    let stream = try!(tcp_connect(challenge.host, challenge.port, challenge.timeouts.connect_timeout));
+    try!(stream.set_read_timeout(Some(challenge.timeouts.timeout)));
+    try!(stream.set_write_timeout(Some(challenge.timeouts.timeout)));
 
+    Ok(stream)
+}
+
+fn stream_send_recv<S: read::Receiver + Write, T>(mut stream: S,
+                                                  challenge: &Challenge<T>,
+                                                  bytes: &[u8])
+                                                  -> Result<Vec<u8>, Error> {
     if bytes.len() > 0 {
-        let tx_res = try!(socket.send_to(&bytes, (host, port)));
-        // TODO: Assert to real check
-        assert_eq!(tx_res, bytes.len());
+        try!(stream.write_all(bytes));
         debug!("- Sent data '{:?}'.", bytes);
     }
 
-    let mut rx_buf = [0u8; 1024];
-    let (rx_len, _) = try!(socket.recv_from(&mut rx_buf));
+    let response = try!(read::read_response(&mut stream, &challenge.read, challenge.timeouts.timeout));
     debug!("- Received result from '{}/{}', '{}' bytes.",
-           host,
-           port,
-           rx_len);
+           challenge.host,
+           challenge.port,
+           response.len());
+
+    Ok(response)
+}
+
+fn tls_stream_send_recv<T: AsRef<[u8]>>(challenge: &Challenge<TlsData<T>>)
+                                        -> Result<TlsResponse<Vec<u8>>, Error> {
+    let (server_name, bytes) = match challenge.data {
+        Some(ref data) => (data.server_name.as_ref().map(|s| &s[..]), data.payload.as_ref()),
+        None => (None, None),
+    };
+    let stream = try!(tcp_stream_connect(challenge));
+    let stream = try!(tls::handshake(stream, server_name.unwrap_or(challenge.host)));
+    let tls = tls::tls_info(stream.ssl());
+    debug!("- TLS handshake with '{}/{}': {}.", challenge.host, challenge.port, tls);
+    let empty: &[u8] = &[0u8; 0];
+    let response = try!(stream_send_recv(stream, challenge, bytes.map(|b| b.as_ref()).unwrap_or(empty)));
+
+    Ok(TlsResponse {
+        response: response,
+        tls: tls,
+    })
+}
+
+/// Sends `bytes` from an ephemeral port and reads the response, retransmitting on timeouts.
+///
+/// The socket is connected to the target, so the kernel drops datagrams from other peers and
+/// reports an ICMP port unreachable as `ConnectionRefused`. Responses rejected by `accept`, e.g.
+/// stale replies to an earlier query, are dropped and reading goes on until the timeout.
+fn udp_datagram_send_recv<T, F>(challenge: &Challenge<T>, bytes: &[u8], accept: F) -> Result<Vec<u8>, Error>
+    where F: Fn(&[u8]) -> bool
+{
+    let (host, port) = (challenge.host, challenge.port);
+    let target = match try!((host, port).to_socket_addrs()).next() {
+        Some(target) => target,
+        None => return Err(Error::new(ErrorKind::InvalidInput, format!("could not resolve '{}'", host))),
+    };
+    let any = if target.is_ipv4() { "0.0.0.0:0" } else { "[::]:0" };
+    let socket = try!(UdpSocket::bind(any));
+    try!(socket.connect(target));
+
+    let mut timeout = challenge.timeouts.timeout;
+    let mut attempt = 0;
+    loop {
+        if bytes.len() > 0 {
+            let tx_len = try!(socket.send(bytes).map_err(port_unreachable));
+            if tx_len != bytes.len() {
+                return Err(Error::new(ErrorKind::WriteZero,
+                                      format!("sent only {} of {} bytes", tx_len, bytes.len())));
+            }
+            debug!("- Sent data '{:?}'.", bytes);
+        }
+
+        match udp_read_accepted(&socket, &challenge.read, timeout, &accept) {
+            Ok(response) => {
+                debug!("- Received result from '{}/{}', '{}' bytes.",
+                       host,
+                       port,
+                       response.len());
+                return Ok(response);
+            }
+            Err(ref err) if is_timeout(err) && attempt < challenge.timeouts.retries => {
+                attempt += 1;
+                timeout = timeout * 2;
+                debug!("- No response from '{}/{}', retransmission {}.", host, port, attempt);
+            }
+            Err(err) => return Err(port_unreachable(err)),
+        }
+    }
+}
 
-    let v = From::from(&rx_buf[0..rx_len]);
+/// Reads responses until one is accepted or `timeout` has passed.
+fn udp_read_accepted<F>(socket: &UdpSocket,
+                        read: &ReadStrategy,
+                        timeout: Duration,
+                        accept: &F)
+                        -> Result<Vec<u8>, Error>
+    where F: Fn(&[u8]) -> bool
+{
+    let deadline = Instant::now() + timeout;
+    loop {
+        let now = Instant::now();
+        if now >= deadline {
+            return Err(Error::new(ErrorKind::TimedOut, "no matching response within the timeout"));
+        }
+        let response = try!(read::read_response(&mut read::Datagrams(socket), read, deadline - now));
+        if accept(&response) {
+            return Ok(response);
+        }
+        debug!("- Dropped unexpected response, '{}' bytes.", response.len());
+    }
+}
 
-    Ok(v)
+fn port_unreachable(err: Error) -> Error {
+    if err.kind() == ErrorKind::ConnectionRefused {
+        Error::new(ErrorKind::ConnectionRefused, "port unreachable")
+    } else {
+        err
+    }
 }
 
 fn http_send_recv<'a>(url_scheme: &str,
-                      challenge: &Challenge<'a, String>)
+                      challenge: &Challenge<'a, HttpRequest>)
                       -> Result<HttpResponse<String>, Error> {
-    let mut client = Client::new();
+    let request = match challenge.data {
+        Some(ref request) => request,
+        None => return Err(Error::new(ErrorKind::InvalidInput, "no HTTP request given")),
+    };
+    let tls_client = tls::TlsClient::new();
+    let server_name = request.server_name.as_ref().map_or(challenge.host, |name| &name[..]);
+    let connector = TimeoutConnector {
+        connect_timeout: challenge.timeouts.connect_timeout,
+        server_name: server_name.to_string(),
+        address: challenge.host.to_string(),
+    };
+    let mut client = Client::with_connector(HttpsConnector::with_connector(tls_client.clone(), connector));
+
+    let mut method = try!(request.verb.parse::<Method>().map_err(hyper_to_io_error));
+    let mut url = format!("{}://{}:{}{}",
+                          url_scheme,
+                          server_name,
+                          challenge.port,
+                          request.uri);
+    let mut body = request.body.clone();
+    let mut redirects = Vec::new();
+
+    // Redirects are followed by hand to record each hop
+    client.set_redirect_policy(RedirectPolicy::FollowNone);
+    // The timeout covers all requests and responses like the read timeout of other protocols.
+    let deadline = Instant::now() + challenge.timeouts.timeout;
+
+    loop {
+        // A redirect from HTTPS to HTTP must not report the certificate of the previous hop.
+        tls_client.clear_info();
+        let left = try!(time_left(deadline));
+        client.set_read_timeout(Some(left));
+        client.set_write_timeout(Some(left));
+        debug!("- {} request '{} {}'", url_scheme, method, url);
+        let mut headers = Headers::new();
+        for &(ref name, ref value) in &request.headers {
+            headers.set_raw(name.clone(), vec![value.as_bytes().to_vec()]);
+        }
+        let mut res = {
+            let builder = client.request(method.clone(), &url[..]).headers(headers);
+            let builder = match body {
+                Some(ref body) => builder.body(&body[..]),
+                None => builder,
+            };
+            try!(builder.send().map_err(hyper_to_io_error))
+        };
 
-    let data = challenge.data.as_ref().unwrap();
-    let data_parts: Vec<&str> = data.split_whitespace().collect();
-    let url = format!("{}://{}:{}{}",
-                      url_scheme,
-                      challenge.host,
-                      challenge.port,
-                      data_parts[1]);
-    debug!("- {} request '{}'", url_scheme, url);
+        let response_code = res.status_raw().0;
+        let response_headers: HashMap<String, String> =
+            res.headers.iter().map(|h| (h.name().to_string(), h.value_string())).collect();
+        let location = response_headers.iter()
+                                       .find(|&(name, _)| name.eq_ignore_ascii_case("location"))
+                                       .map(|(_, value)| value.clone());
+
+        let follow = request.follow_redirects && is_redirect(response_code);
+        if let (true, Some(location)) = (follow, location) {
+            if redirects.len() == MAX_REDIRECTS {
+                return Err(Error::new(ErrorKind::Other,
+                                      format!("more than {} redirects", MAX_REDIRECTS)));
+            }
+            debug!("- Following redirect '{}' to '{}'.", response_code, location);
+            url = resolve_location(&url, &location);
+            // Like browsers, switch to GET unless the redirect asks to repeat the request.
+            if response_code == 303 || (method == Method::Post && response_code < 307) {
+                method = Method::Get;
+                body = None;
+            }
+            redirects.push(HttpRedirect {
+                response_code: response_code,
+                location: location,
+            });
+            continue;
+        }
 
-    client.set_redirect_policy(RedirectPolicy::FollowNone);
-    // TODO: use verb instead of hardcoded get and handle hyper Errors
-    let res = client.get(&url).send().unwrap();
-
-    let headers = HashMap::new();
-    let response_data: HttpResponse<String> = HttpResponse {
-        response_code: res.status_raw().0,
-        headers: headers,
-        body: "<not yet implemented>".to_string(),
-    };
+        // One byte more than allowed tells whether the body is longer.
+        let max_size = challenge.read.max_size;
+        let until_close = ReadStrategy {
+            until: ReadUntil::Close,
+            max_size: max_size.saturating_add(1),
+        };
+        let mut body_bytes = try!(read::read_response(&mut HttpBody(&mut res),
+                                                      &until_close,
+                                                      try!(time_left(deadline))));
+        let body_truncated = body_bytes.len() > max_size;
+        body_bytes.truncate(max_size);
+        debug!("- Received result from '{}', '{}' bytes, truncated: {}.",
+               url,
+               body_bytes.len(),
+               body_truncated);
+
+        return Ok(HttpResponse {
+            response_code: response_code,
+            headers: response_headers,
+            body: String::from_utf8_lossy(&body_bytes).into_owned(),
+            redirects: redirects,
+            tls: tls_client.take_info(),
+            body_truncated: body_truncated,
+        });
+    }
+}
+
+/// Connects hyper to every host it asks for, including redirect targets, within a timeout; the
+/// server name is reached at `address`.
+struct TimeoutConnector {
+    connect_timeout: Duration,
+    server_name: String,
+    address: String,
+}
+
+impl NetworkConnector for TimeoutConnector {
+    type Stream = HttpStream;
+
+    fn connect(&self, host: &str, port: u16, _: &str) -> hyper::Result<HttpStream> {
+        // URLs carry host names in lower case
+        let host = if host.eq_ignore_ascii_case(&self.server_name) { &self.address[..] } else { host };
+        Ok(HttpStream(try!(tcp_connect(host, port, self.connect_timeout))))
+    }
+}
+
+/// Reads the body of a response like any other response, so its read timeout can be adjusted.
+struct HttpBody<'a>(&'a mut hyper::client::Response);
+
+impl<'a> Read for HttpBody<'a> {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize, Error> {
+        self.0.read(buf)
+    }
+}
+
+impl<'a> read::Receiver for HttpBody<'a> {
+    fn set_read_timeout(&mut self, timeout: Duration) -> Result<(), Error> {
+        self.0.get_ref().set_read_timeout(Some(timeout))
+    }
+}
 
-    Ok(response_data)
+/// The time left until `deadline`; an error once it has passed.
+fn time_left(deadline: Instant) -> Result<Duration, Error> {
+    let now = Instant::now();
+    if now >= deadline {
+        return Err(Error::new(ErrorKind::TimedOut, "no complete HTTP response within the timeout"));
+    }
+
+    Ok(deadline - now)
+}
+
+fn is_redirect(response_code: u16) -> bool {
+    match response_code {
+        301 | 302 | 303 | 307 | 308 => true,
+        _ => false,
+    }
+}
+
+/// Resolves a `Location` header against the URL of the request that received it.
+fn resolve_location(base: &str, location: &str) -> String {
+    if location.contains("://") {
+        return location.to_string();
+    }
+    let scheme_end = base.find("://").map(|i| i + 3).unwrap_or(0);
+    if location.starts_with("//") {
+        return format!("{}{}", &base[..scheme_end - 1], &location[1..]);
+    }
+    let authority_end = base[scheme_end..].find('/').map(|i| scheme_end + i).unwrap_or(base.len());
+    if location.starts_with('/') {
+        return format!("{}{}", &base[..authority_end], location);
+    }
+    let path = base[authority_end..].split(|c| c == '?' || c == '#').next().unwrap_or("");
+    let dir_end = authority_end + path.rfind('/').map(|i| i + 1).unwrap_or(0);
+    if dir_end == authority_end {
+        format!("{}/{}", &base[..authority_end], location)
+    } else {
+        format!("{}{}", &base[..dir_end], location)
+    }
+}
+
+fn hyper_to_io_error(err: hyper::Error) -> Error {
+    match err {
+        hyper::Error::Io(err) => err,
+        err => Error::new(ErrorKind::Other, err),
+    }
 }
@@ -0,0 +1,147 @@
+use openssl::ssl::SslStream;
+use std::cmp;
+use std::io::{Error, ErrorKind};
+use std::io::prelude::*;
+use std::net::{TcpStream, UdpSocket};
+use std::time::{Duration, Instant};
+
+/// Default cap for the size of a response, in bytes.
+pub const DEFAULT_MAX_SIZE: usize = 64 * 1024;
+
+/// When a response is complete.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ReadUntil {
+    /// Whatever a single read returns, i.e. the first segment or datagram.
+    FirstChunk,
+    /// The response ends with this delimiter.
+    Delimiter(Vec<u8>),
+    /// The response has exactly this many bytes.
+    Bytes(usize),
+    /// Nothing has been received for this long after the first bytes.
+    Idle(Duration),
+    /// The peer closes the connection.
+    Close,
+}
+
+/// How a protocol reads its response.
+///
+/// Reading never takes longer than the read timeout in total; a response that is still
+/// incomplete by then is a timeout. Reading stops early once `max_size` bytes have been received.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ReadStrategy {
+    pub until: ReadUntil,
+    pub max_size: usize,
+}
+
+impl Default for ReadStrategy {
+    fn default() -> ReadStrategy {
+        ReadStrategy {
+            until: ReadUntil::FirstChunk,
+            max_size: DEFAULT_MAX_SIZE,
+        }
+    }
+}
+
+/// Something to read a response from whose read timeout can be adjusted while reading.
+pub trait Receiver: Read {
+    fn set_read_timeout(&mut self, timeout: Duration) -> Result<(), Error>;
+}
+
+impl Receiver for TcpStream {
+    fn set_read_timeout(&mut self, timeout: Duration) -> Result<(), Error> {
+        TcpStream::set_read_timeout(self, Some(timeout))
+    }
+}
+
+impl Receiver for SslStream<TcpStream> {
+    fn set_read_timeout(&mut self, timeout: Duration) -> Result<(), Error> {
+        self.get_ref().set_read_timeout(Some(timeout))
+    }
+}
+
+/// Reads datagrams from a socket as if they were a stream.
+pub struct Datagrams<'a>(pub &'a UdpSocket);
+
+impl<'a> Read for Datagrams<'a> {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize, Error> {
+        self.0.recv(buf)
+    }
+}
+
+impl<'a> Receiver for Datagrams<'a> {
+    fn set_read_timeout(&mut self, timeout: Duration) -> Result<(), Error> {
+        self.0.set_read_timeout(Some(timeout))
+    }
+}
+
+pub fn read_response<R: Receiver>(receiver: &mut R,
+                                  strategy: &ReadStrategy,
+                                  timeout: Duration)
+                                  -> Result<Vec<u8>, Error> {
+    let deadline = Instant::now() + timeout;
+    let mut response = Vec::new();
+    let mut buf = vec![0u8; cmp::min(strategy.max_size, 16 * 1024)];
+    loop {
+        let now = Instant::now();
+        if now >= deadline {
+            return Err(incomplete(&strategy.until, &response));
+        }
+        let mut read_timeout = deadline - now;
+        if let ReadUntil::Idle(idle) = strategy.until {
+            if !response.is_empty() {
+                read_timeout = cmp::min(read_timeout, idle);
+            }
+        }
+        try!(receiver.set_read_timeout(read_timeout));
+
+        let left = match strategy.until {
+            ReadUntil::Bytes(bytes) => cmp::min(bytes, strategy.max_size) - response.len(),
+            _ => strategy.max_size - response.len(),
+        };
+        let len = cmp::min(buf.len(), left);
+        let rx_len = match receiver.read(&mut buf[..len]) {
+            Ok(rx_len) => rx_len,
+            Err(ref err) if super::is_timeout(err) && is_idle(&strategy.until, &response) => {
+                return Ok(response)
+            }
+            Err(ref err) if super::is_timeout(err) => return Err(incomplete(&strategy.until, &response)),
+            Err(err) => return Err(err),
+        };
+        response.extend_from_slice(&buf[..rx_len]);
+
+        let complete = match strategy.until {
+            ReadUntil::FirstChunk => true,
+            ReadUntil::Delimiter(ref delimiter) => {
+                !delimiter.is_empty() && response.windows(delimiter.len()).any(|w| w == &delimiter[..])
+            }
+            ReadUntil::Bytes(bytes) => response.len() >= bytes,
+            ReadUntil::Idle(_) => false,
+            ReadUntil::Close => rx_len == 0,
+        };
+        if complete || response.len() >= strategy.max_size {
+            return Ok(response);
+        }
+        if rx_len == 0 {
+            return Err(Error::new(ErrorKind::UnexpectedEof,
+                                  format!("connection closed after {} bytes", response.len())));
+        }
+    }
+}
+
+fn is_idle(until: &ReadUntil, response: &[u8]) -> bool {
+    match *until {
+        ReadUntil::Idle(_) => !response.is_empty(),
+        _ => false,
+    }
+}
+
+fn incomplete(until: &ReadUntil, response: &[u8]) -> Error {
+    let waiting_for = match *until {
+        ReadUntil::FirstChunk | ReadUntil::Idle(_) => "any data".to_string(),
+        ReadUntil::Delimiter(ref delimiter) => format!("delimiter {:?}", String::from_utf8_lossy(delimiter)),
+        ReadUntil::Bytes(bytes) => format!("{} bytes", bytes),
+        ReadUntil::Close => "the connection to close".to_string(),
+    };
+    Error::new(ErrorKind::TimedOut,
+               format!("received {} bytes while waiting for {}", response.len(), waiting_for))
+}
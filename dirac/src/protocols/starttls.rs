@@ -0,0 +1,204 @@
+use std::fmt;
+use std::io::{Error, ErrorKind};
+use std::io::prelude::*;
+use std::str::FromStr;
+use std::time::{Duration, Instant};
+
+use super::read::{Receiver, DEFAULT_MAX_SIZE};
+
+/// Services whose plaintext protocol can be upgraded to TLS.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum StartTlsService {
+    Smtp,
+    Imap,
+    Pop3,
+    Ftp,
+    Postgres,
+}
+
+impl FromStr for StartTlsService {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<StartTlsService, Error> {
+        match s {
+            "smtp" => Ok(StartTlsService::Smtp),
+            "imap" => Ok(StartTlsService::Imap),
+            "pop3" => Ok(StartTlsService::Pop3),
+            "ftp" => Ok(StartTlsService::Ftp),
+            "postgres" => Ok(StartTlsService::Postgres),
+            _ => Err(Error::new(ErrorKind::InvalidInput, format!("unknown STARTTLS service '{}'", s))),
+        }
+    }
+}
+
+impl fmt::Display for StartTlsService {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match *self {
+            StartTlsService::Smtp => "smtp",
+            StartTlsService::Imap => "imap",
+            StartTlsService::Pop3 => "pop3",
+            StartTlsService::Ftp => "ftp",
+            StartTlsService::Postgres => "postgres",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// PostgreSQL's `SSLRequest` message: length 8 and the request code 80877103.
+const POSTGRES_SSL_REQUEST: [u8; 8] = [0, 0, 0, 8, 0x04, 0xd2, 0x16, 0x2f];
+
+/// Runs the plaintext part of `service` up to the point where the TLS handshake starts and returns
+/// the server's greeting.
+///
+/// Fails if the server does not agree to upgrade the connection, if that takes longer than `timeout`
+/// in total or if the server sends more than `DEFAULT_MAX_SIZE` bytes on the way.
+pub fn preamble<S: Receiver + Write>(stream: &mut S,
+                                     service: StartTlsService,
+                                     timeout: Duration)
+                                     -> Result<String, Error> {
+    let mut stream = Plaintext {
+        stream: stream,
+        deadline: Instant::now() + timeout,
+        received: 0,
+    };
+    let stream = &mut stream;
+    match service {
+        StartTlsService::Smtp => {
+            let banner = try!(read_reply(stream, is_final_numeric_line));
+            try!(stream.write_all(b"EHLO dirac\r\n"));
+            let capabilities = try!(read_reply(stream, is_final_numeric_line));
+            if !capabilities.lines().any(is_starttls_capability) {
+                return Err(refused(service, "does not offer STARTTLS", &capabilities));
+            }
+            try!(command(stream, service, b"STARTTLS\r\n", is_final_numeric_line, "220"));
+            Ok(banner)
+        }
+        StartTlsService::Imap => {
+            let banner = try!(read_line(stream));
+            try!(command(stream, service, b"a001 STARTTLS\r\n", |l| l.starts_with("a001 "), "a001 OK"));
+            Ok(banner)
+        }
+        StartTlsService::Pop3 => {
+            let banner = try!(read_line(stream));
+            try!(command(stream, service, b"STLS\r\n", |_| true, "+OK"));
+            Ok(banner)
+        }
+        StartTlsService::Ftp => {
+            let banner = try!(read_reply(stream, is_final_numeric_line));
+            try!(command(stream, service, b"AUTH TLS\r\n", is_final_numeric_line, "234"));
+            Ok(banner)
+        }
+        StartTlsService::Postgres => {
+            try!(stream.write_all(&POSTGRES_SSL_REQUEST));
+            let answer = [try!(stream.read_byte())];
+            if answer[0] != b'S' {
+                return Err(refused(service, "does not accept SSL", &String::from_utf8_lossy(&answer)));
+            }
+            Ok(String::new())
+        }
+    }
+}
+
+/// The plaintext connection before the upgrade.
+struct Plaintext<'a, S: 'a> {
+    stream: &'a mut S,
+    /// When the whole preamble has to be done.
+    deadline: Instant,
+    /// How many bytes have been received so far.
+    received: usize,
+}
+
+impl<'a, S: Receiver + Write> Plaintext<'a, S> {
+    fn write_all(&mut self, bytes: &[u8]) -> Result<(), Error> {
+        self.stream.write_all(bytes)
+    }
+
+    /// Reads a single byte so nothing beyond the preamble is consumed: it may already belong to the
+    /// TLS handshake.
+    fn read_byte(&mut self) -> Result<u8, Error> {
+        let now = Instant::now();
+        if now >= self.deadline {
+            return Err(timed_out());
+        }
+        if self.received >= DEFAULT_MAX_SIZE {
+            return Err(Error::new(ErrorKind::InvalidData,
+                                  format!("received more than {} bytes during STARTTLS", DEFAULT_MAX_SIZE)));
+        }
+        try!(self.stream.set_read_timeout(self.deadline - now));
+
+        let mut byte = [0u8; 1];
+        match self.stream.read(&mut byte) {
+            Ok(0) => return Err(Error::new(ErrorKind::UnexpectedEof, "connection closed during STARTTLS")),
+            Ok(_) => self.received += 1,
+            Err(ref err) if super::is_timeout(err) => return Err(timed_out()),
+            Err(err) => return Err(err),
+        }
+
+        Ok(byte[0])
+    }
+}
+
+fn timed_out() -> Error {
+    Error::new(ErrorKind::TimedOut, "timed out during STARTTLS")
+}
+
+/// Sends `request` and expects the final line of the reply to start with `expected`.
+fn command<S, F>(stream: &mut Plaintext<S>,
+                 service: StartTlsService,
+                 request: &[u8],
+                 is_final: F,
+                 expected: &str)
+                 -> Result<String, Error>
+    where S: Receiver + Write,
+          F: Fn(&str) -> bool
+{
+    try!(stream.write_all(request));
+    let reply = try!(read_reply(stream, is_final));
+    if reply.lines().last().map(|l| l.starts_with(expected)).unwrap_or(false) {
+        Ok(reply)
+    } else {
+        Err(refused(service, "refused to start TLS", &reply))
+    }
+}
+
+fn refused(service: StartTlsService, reason: &str, reply: &str) -> Error {
+    Error::new(ErrorKind::Other,
+               format!("{} server {}: '{}'", service, reason, reply.trim_end()))
+}
+
+/// Tells whether an `EHLO` reply line like `250-STARTTLS` announces STARTTLS.
+fn is_starttls_capability(line: &str) -> bool {
+    line.get(4..).map(|capability| capability.trim().eq_ignore_ascii_case("STARTTLS")).unwrap_or(false)
+}
+
+/// SMTP and FTP continue multi-line replies with `<code>-` and end them with `<code> `.
+fn is_final_numeric_line(line: &str) -> bool {
+    line.len() < 4 || line.as_bytes()[3] != b'-'
+}
+
+/// Reads lines until `is_final` accepts one.
+fn read_reply<S, F>(stream: &mut Plaintext<S>, is_final: F) -> Result<String, Error>
+    where S: Receiver + Write,
+          F: Fn(&str) -> bool
+{
+    let mut reply = String::new();
+    loop {
+        let line = try!(read_line(stream));
+        reply.push_str(&line);
+        if is_final(&line) {
+            return Ok(reply);
+        }
+    }
+}
+
+/// Reads a single line including its line break.
+fn read_line<S: Receiver + Write>(stream: &mut Plaintext<S>) -> Result<String, Error> {
+    let mut line = Vec::new();
+    loop {
+        let byte = try!(stream.read_byte());
+        line.push(byte);
+        if byte == b'\n' {
+            return Ok(String::from_utf8_lossy(&line).into_owned());
+        }
+    }
+}
@@ -0,0 +1,236 @@
+use hyper;
+use hyper::net::{HttpStream, NetworkStream, SslClient};
+use openssl::asn1::Asn1Time;
+use openssl::nid::Nid;
+use openssl::pkey::Id;
+use openssl::ssl::{SslConnector, SslMethod, SslRef, SslStream, SslVerifyMode};
+use openssl::x509::{X509NameRef, X509Ref, X509VerifyResult};
+use std::fmt;
+use std::io::{self, Error, ErrorKind};
+use std::io::prelude::*;
+use std::net::{Shutdown, SocketAddr};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// What has been negotiated during a TLS handshake.
+#[derive(Debug, Clone)]
+pub struct TlsInfo {
+    /// Protocol version, e.g. `TLSv1.2`.
+    pub version: String,
+    pub cipher: String,
+    /// Certificates sent by the peer, starting with its own.
+    pub chain: Vec<Certificate>,
+    /// Why the chain could not be verified against the system's trusted CAs, if it could not.
+    pub verify_error: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Certificate {
+    pub subject: String,
+    pub issuer: String,
+    /// DNS names and IP addresses of the subject alternative name extension.
+    pub sans: Vec<String>,
+    pub not_before: String,
+    pub not_after: String,
+    /// Whole days left until `not_after`; negative if the certificate has expired.
+    pub days_valid: i64,
+    /// Algorithm and size of the public key, e.g. `RSA 2048`.
+    pub key_type: String,
+}
+
+impl fmt::Display for TlsInfo {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        try!(write!(f, "{} {}", self.version, self.cipher));
+        if let Some(cert) = self.chain.first() {
+            try!(write!(f, ", {} valid for {} days", cert.subject, cert.days_valid));
+        }
+        if let Some(ref err) = self.verify_error {
+            try!(write!(f, ", not trusted: {}", err));
+        }
+        Ok(())
+    }
+}
+
+/// Performs a TLS handshake on `stream`.
+///
+/// The peer's certificate is never rejected: checks want to inspect broken certificates, too.
+/// The outcome of the verification is reported in `TlsInfo::verify_error` instead.
+pub fn handshake<S>(stream: S, server_name: &str) -> Result<SslStream<S>, Error>
+    where S: Read + Write + fmt::Debug
+{
+    let mut builder = try!(SslConnector::builder(SslMethod::tls()).map_err(to_io_error));
+    builder.set_verify(SslVerifyMode::NONE);
+    let connector = builder.build();
+
+    connector.connect(server_name, stream).map_err(|err| Error::new(ErrorKind::Other, err.to_string()))
+}
+
+pub fn tls_info(ssl: &SslRef) -> TlsInfo {
+    let mut chain: Vec<Certificate> = match ssl.peer_cert_chain() {
+        Some(chain) => chain.iter().map(certificate).collect(),
+        None => Vec::new(),
+    };
+    if chain.is_empty() {
+        if let Some(cert) = ssl.peer_certificate() {
+            chain.push(certificate(&cert));
+        }
+    }
+    let verify_result = ssl.verify_result();
+
+    TlsInfo {
+        version: ssl.version_str().to_string(),
+        cipher: ssl.current_cipher().map(|c| c.name().to_string()).unwrap_or_default(),
+        chain: chain,
+        verify_error: if verify_result == X509VerifyResult::OK {
+            None
+        } else {
+            Some(verify_result.error_string().to_string())
+        },
+    }
+}
+
+fn certificate(cert: &X509Ref) -> Certificate {
+    let sans = match cert.subject_alt_names() {
+        Some(names) => {
+            names.iter()
+                 .filter_map(|name| {
+                     name.dnsname()
+                         .map(|dns| dns.to_string())
+                         .or_else(|| name.ipaddress().and_then(ip_address))
+                 })
+                 .collect()
+        }
+        None => Vec::new(),
+    };
+    let days_valid = Asn1Time::days_from_now(0)
+                         .and_then(|now| now.diff(cert.not_after()))
+                         .map(|diff| diff.days as i64)
+                         .unwrap_or(0);
+    let key_type = match cert.public_key() {
+        Ok(key) => {
+            let algorithm = match key.id() {
+                Id::RSA => "RSA".to_string(),
+                Id::DSA => "DSA".to_string(),
+                Id::EC => "EC".to_string(),
+                Id::ED25519 => "Ed25519".to_string(),
+                Id::ED448 => "Ed448".to_string(),
+                id => format!("unknown ({})", id.as_raw()),
+            };
+            format!("{} {}", algorithm, key.bits())
+        }
+        Err(_) => "unknown".to_string(),
+    };
+
+    Certificate {
+        subject: name_to_string(cert.subject_name()),
+        issuer: name_to_string(cert.issuer_name()),
+        sans: sans,
+        not_before: cert.not_before().to_string(),
+        not_after: cert.not_after().to_string(),
+        days_valid: days_valid,
+        key_type: key_type,
+    }
+}
+
+/// Formats a distinguished name like `CN=example.com, O=Example`.
+fn name_to_string(name: &X509NameRef) -> String {
+    let entries: Vec<String> = name.entries()
+                                   .map(|entry| {
+                                       let key = match entry.object().nid() {
+                                           Nid::UNDEF => entry.object().to_string(),
+                                           nid => nid.short_name().unwrap_or("?").to_string(),
+                                       };
+                                       let value = String::from_utf8_lossy(entry.data().as_slice());
+                                       format!("{}={}", key, value)
+                                   })
+                                   .collect();
+    entries.join(", ")
+}
+
+fn ip_address(bytes: &[u8]) -> Option<String> {
+    match bytes.len() {
+        4 => Some(format!("{}.{}.{}.{}", bytes[0], bytes[1], bytes[2], bytes[3])),
+        16 => {
+            let segments: Vec<String> = bytes.chunks(2)
+                                             .map(|s| format!("{:x}", (s[0] as u16) << 8 | s[1] as u16))
+                                             .collect();
+            Some(segments.join(":"))
+        }
+        _ => None,
+    }
+}
+
+fn to_io_error<E: ::std::error::Error + Send + Sync + 'static>(err: E) -> Error {
+    Error::new(ErrorKind::Other, err)
+}
+
+/// Lets hyper speak TLS through `handshake` and remembers the outcome of the latest handshake.
+#[derive(Clone, Default)]
+pub struct TlsClient {
+    info: Arc<Mutex<Option<TlsInfo>>>,
+}
+
+impl TlsClient {
+    pub fn new() -> TlsClient {
+        TlsClient::default()
+    }
+
+    /// Returns what has been negotiated by the latest handshake.
+    pub fn take_info(&self) -> Option<TlsInfo> {
+        self.info.lock().unwrap().take()
+    }
+
+    /// Forgets the latest handshake, e.g. before a request that may not need one.
+    pub fn clear_info(&self) {
+        *self.info.lock().unwrap() = None;
+    }
+}
+
+impl SslClient for TlsClient {
+    type Stream = TlsStream;
+
+    fn wrap_client(&self, stream: HttpStream, host: &str) -> hyper::Result<TlsStream> {
+        let stream = try!(handshake(stream, host).map_err(|err| hyper::Error::Ssl(Box::new(err))));
+        *self.info.lock().unwrap() = Some(tls_info(stream.ssl()));
+
+        Ok(TlsStream(Arc::new(Mutex::new(stream))))
+    }
+}
+
+/// A TLS stream hyper can clone into its connection pool.
+#[derive(Clone)]
+pub struct TlsStream(Arc<Mutex<SslStream<HttpStream>>>);
+
+impl Read for TlsStream {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.0.lock().unwrap().read(buf)
+    }
+}
+
+impl Write for TlsStream {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.lock().unwrap().write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.0.lock().unwrap().flush()
+    }
+}
+
+impl NetworkStream for TlsStream {
+    fn peer_addr(&mut self) -> io::Result<SocketAddr> {
+        self.0.lock().unwrap().get_mut().peer_addr()
+    }
+
+    fn set_read_timeout(&self, dur: Option<Duration>) -> io::Result<()> {
+        self.0.lock().unwrap().get_ref().set_read_timeout(dur)
+    }
+
+    fn set_write_timeout(&self, dur: Option<Duration>) -> io::Result<()> {
+        self.0.lock().unwrap().get_ref().set_write_timeout(dur)
+    }
+
+    fn close(&mut self, how: Shutdown) -> io::Result<()> {
+        self.0.lock().unwrap().get_mut().close(how)
+    }
+}
@@ -4,6 +4,7 @@ use std::io::prelude::*;
 use std::fs::File;
 use rustc_serialize::json::{ToJson, Json};
 
+use super::super::checks::ParamValue;
 use super::super::engine::{CheckResult, CheckSuiteResult, PropertyResult};
 use super::Report;
 
@@ -32,6 +33,20 @@ impl<'a> Report<'a> for JsonReport<'a> {
     }
 }
 
+impl ToJson for ParamValue {
+    fn to_json(&self) -> Json {
+        match *self {
+            ParamValue::Null => Json::Null,
+            ParamValue::Bool(b) => Json::Boolean(b),
+            ParamValue::Int(i) => Json::I64(i),
+            ParamValue::Float(x) => Json::F64(x),
+            ParamValue::String(ref s) => Json::String(s.clone()),
+            ParamValue::List(ref l) => l.to_json(),
+            ParamValue::Map(ref m) => m.to_json(),
+        }
+    }
+}
+
 impl<'a> ToJson for PropertyResult<'a> {
     fn to_json(&self) -> Json {
         let mut d = BTreeMap::new();
@@ -42,10 +57,15 @@ impl<'a> ToJson for PropertyResult<'a> {
         property.insert("params".to_string(), self.property.params.to_json());
         d.insert("property".to_string(), property.to_json());
         let property_result = match &self.result {
+            &Ok(()) if self.skipped => "Skipped".to_string(),
             &Ok(()) => "Success".to_string(),
-            &Err(ref err) => err.to_string(),
+            &Err(ref err) => err.name().to_string(),
         };
         d.insert("property_result".to_string(), property_result.to_json());
+        if let Err(ref err) = self.result {
+            d.insert("reason".to_string(), err.reason().to_json());
+        }
+        d.insert("response".to_string(), self.response.to_json());
         Json::Object(d)
     }
 }
@@ -46,7 +46,11 @@ impl<'a> ToMarkdown for CheckSuiteResult<'a> {
         report.push_str("\n");
         let summary = create_total_summary(self);
         for kv in summary {
-            report.push_str(&format!(" * *{}* Success {}, Failed {}\n", kv.0, (kv.1).0, (kv.1).1));
+            report.push_str(&format!(" * *{}* Success {}, Failed {}, Skipped {}\n",
+                                     kv.0,
+                                     (kv.1).0,
+                                     (kv.1).1,
+                                     (kv.1).2));
         }
         report.push_str("\n");
 
@@ -60,12 +64,23 @@ impl<'a> ToMarkdown for CheckSuiteResult<'a> {
                 report.push_str(&format!("* {} *{}*",
                                          property_result.property.name,
                                          property_result.property.module));
-                let r = if property_result.result.is_ok() {
-                    "Success"
-                } else {
-                    "**Failed**"
-                };
-                report.push_str(&format!(" {}\n", r));
+                match property_result.result {
+                    Ok(()) if property_result.skipped => report.push_str(" Skipped\n"),
+                    Ok(()) => report.push_str(" Success\n"),
+                    Err(ref err) => {
+                        report.push_str(&format!(" **Failed** ({})\n", err.name()));
+                        if let Some(reason) = err.reason() {
+                            report.push_str(&format!("\n  {}\n", reason));
+                        }
+                        if let Some(ref response) = property_result.response {
+                            report.push_str("\n  ```\n");
+                            for line in response.lines() {
+                                report.push_str(&format!("  {}\n", line));
+                            }
+                            report.push_str("  ```\n");
+                        }
+                    }
+                }
                 report.push_str("\n");
             }
         }
@@ -76,13 +91,16 @@ impl<'a> ToMarkdown for CheckSuiteResult<'a> {
     }
 }
 
-fn create_total_summary<'a>(check_suite_result: &'a CheckSuiteResult) -> HashMap<&'a str, (u16, u16)> {
+fn create_total_summary<'a>(check_suite_result: &'a CheckSuiteResult)
+                            -> HashMap<&'a str, (u16, u16, u16)> {
     let mut result = HashMap::new();
 
     for check in &check_suite_result.results {
         for property in &check.results {
-            let mut host_result = result.entry(property.host).or_insert((0, 0));
-            if property.result.is_ok() {
+            let host_result = result.entry(property.host).or_insert((0, 0, 0));
+            if property.skipped {
+                host_result.2 += 1;
+            } else if property.result.is_ok() {
                 host_result.0 += 1;
             } else {
                 host_result.1 += 1;
@@ -99,7 +117,7 @@ fn create_host_summary<'a>(check_suite_result: &'a CheckSuiteResult)
 
     for check in &check_suite_result.results {
         for property in &check.results {
-            let mut host_result = result.entry(property.host).or_insert(Vec::new());
+            let host_result = result.entry(property.host).or_insert(Vec::new());
             host_result.push(property);
         }
     }
@@ -1,4 +1,5 @@
 use dirac::checks::*;
+use std::os::unix::fs::PermissionsExt;
 
 static CHECK_SUITE_YAML: &'static str = r##"
 ---
@@ -79,9 +80,9 @@ pub fn check_suite_yml_checks_test() {
         assert_eq!(property.module, "ssh".to_string());
 
         let prop_params = &property.params;
-        assert_eq!(prop_params["port"], "22".to_string());
-        assert_eq!(prop_params["version"], "2.0".to_string());
-        assert_eq!(prop_params["software"], "OpenSSH.*".to_string());
+        assert_eq!(prop_params["port"], ParamValue::Int(22));
+        assert_eq!(prop_params["version"], ParamValue::Float(2.0));
+        assert_eq!(prop_params["software"], ParamValue::String("OpenSSH.*".to_string()));
         assert_eq!(prop_params.len(), 3);
     }
 
@@ -96,7 +97,7 @@ pub fn check_suite_yml_checks_test() {
         assert_eq!(property.module, "connect_tcp".to_string());
 
         let prop_params = &property.params;
-        assert_eq!(prop_params["port"], "53".to_string());
+        assert_eq!(prop_params["port"], ParamValue::Int(53));
         assert_eq!(prop_params.len(), 1);
     }
 
@@ -111,13 +112,706 @@ pub fn check_suite_yml_checks_test() {
         assert_eq!(property.module, "connect_tcp".to_string());
 
         let prop_params = &property.params;
-        assert_eq!(prop_params["port"], "139".to_string());
+        assert_eq!(prop_params["port"], ParamValue::Int(139));
         assert_eq!(prop_params.len(), 1);
     }
 }
 
+#[test]
+pub fn check_suite_yml_settings_test() {
+    let check_suite = parse_check_suite(CHECK_SUITE_YAML);
+    assert_eq!(check_suite.settings.jobs, None);
+
+    let yaml = format!("{}{}", "---\n- settings:\n    jobs: 8\n", &CHECK_SUITE_YAML[5..]);
+    let check_suite = parse_check_suite(&yaml);
+    assert_eq!(check_suite.settings.jobs, Some(8));
+    assert_eq!(check_suite.inventory.len(), 3);
+    assert_eq!(check_suite.checks.len(), 3);
+}
+
+#[test]
+pub fn check_suite_yml_typed_params_test() {
+    let yaml = r##"
+---
+- hosts: all
+  properties:
+    - name: Check SMTP Proxy
+      smtp: { port: "25", software: ".*Postfix.*", proxy: True, return_code: 220 }
+    - name: Health
+      http:
+        port: 80
+        response_codes: [200, 204]
+        headers:
+          Host: example.com
+        timeout_factor: 1.5
+        body:
+"##;
+    let check_suite = parse_check_suite(yaml);
+    let properties = &check_suite.checks[0].properties;
+
+    let smtp = &properties[0].params;
+    assert_eq!(smtp["proxy"], ParamValue::Bool(true));
+    assert_eq!(smtp["port"].as_port(), Some(25));
+    assert_eq!(smtp["return_code"].as_i64(), Some(220));
+
+    let http = &properties[1].params;
+    assert_eq!(http["response_codes"],
+               ParamValue::List(vec![ParamValue::Int(200), ParamValue::Int(204)]));
+    let headers = http["headers"].as_map().unwrap();
+    assert_eq!(headers["Host"].as_str(), Some("example.com"));
+    assert_eq!(http["timeout_factor"], ParamValue::Float(1.5));
+    assert_eq!(http["body"], ParamValue::Null);
+}
+
+#[test]
+pub fn check_suite_yml_timeouts_test() {
+    let yaml = r##"
+---
+- settings:
+    timeout: 2
+    connect_timeout: 0.5
+
+- inventory:
+    all:
+      - fritz.box
+
+- hosts: all
+  properties:
+    - name: DNS TCP
+      connect_tcp:
+        port: 53
+    - name: Slow HTTP
+      timeout: 10
+      http:
+        port: 80
+"##;
+    let check_suite = parse_check_suite(yaml);
+    assert_eq!(check_suite.settings.timeout_ms, Some(2000));
+    assert_eq!(check_suite.settings.connect_timeout_ms, Some(500));
+
+    let properties = &check_suite.checks[0].properties;
+    assert_eq!(properties[0].timeout_ms, None);
+    assert_eq!(properties[1].timeout_ms, Some(10000));
+    assert_eq!(properties[1].connect_timeout_ms, None);
+    assert_eq!(properties[1].module, "http".to_string());
+    assert_eq!(properties[1].params.len(), 1);
+
+    let err = parse_check_suite_err(&yaml.replace("timeout: 10", "timeout: soon"));
+    assert_eq!(err.reason, "'timeout' must be a positive number of seconds, found a string");
+
+    let err = parse_check_suite_err(&yaml.replace("timeout: 10", "timeout: 0.0004"));
+    assert_eq!(err.reason, "'timeout' must be at least 0.001 seconds, found 0.0004");
+    assert_eq!(err.line, Some(17));
+}
+
+#[test]
+pub fn check_suite_yml_retries_test() {
+    let yaml = r##"
+---
+- settings:
+    retries: 2
+
+- inventory:
+    all:
+      - fritz.box
+
+- hosts: all
+  properties:
+    - name: DNS UDP
+      dns_udp:
+        port: 53
+    - name: NTP
+      retries: 0
+      ntp:
+        port: 123
+"##;
+    let check_suite = parse_check_suite(yaml);
+    assert_eq!(check_suite.settings.retries, Some(2));
+
+    let properties = &check_suite.checks[0].properties;
+    assert_eq!(properties[0].retries, None);
+    assert_eq!(properties[1].retries, Some(0));
+
+    let err = parse_check_suite_err(&yaml.replace("retries: 0", "retries: -1"));
+    assert_eq!(err.reason, "'retries' must be a non-negative integer, found -1");
+}
+
+#[test]
+pub fn check_suite_yml_invalid_yaml_test() {
+    let err = parse_check_suite_err("---\n- inventory:\n    all: [a, b\n");
+    assert!(err.line.is_some());
+    assert!(err.reason.starts_with("invalid YAML"));
+}
+
+#[test]
+pub fn check_suite_yml_property_without_module_test() {
+    let yaml = r##"
+---
+- inventory:
+    all:
+      - fritz.box
+
+- hosts: all
+  properties:
+    - name: Check SMTP
+"##;
+    let err = parse_check_suite_err(yaml);
+    assert_eq!(err.line, Some(9));
+    assert_eq!(err.reason, "property 'Check SMTP' has no module");
+    assert_eq!(err.to_string(), "<string>:9: property 'Check SMTP' has no module");
+}
+
+#[test]
+pub fn check_suite_yml_property_without_name_test() {
+    let yaml = r##"
+---
+- hosts: all
+  properties:
+    - connect_tcp:
+        port: 53
+"##;
+    let err = parse_check_suite_err(yaml);
+    assert_eq!(err.line, Some(5));
+    assert_eq!(err.reason, "property has no name");
+}
+
+#[test]
+pub fn check_suite_yml_params_not_a_hash_test() {
+    let yaml = r##"
+---
+- hosts: all
+  properties:
+    - name: DNS TCP
+      connect_tcp: 53
+"##;
+    let err = parse_check_suite_err(yaml);
+    assert_eq!(err.line, Some(6));
+    assert_eq!(err.reason,
+               "params of module 'connect_tcp' in property 'DNS TCP' must be a hash, found an integer");
+}
+
+#[test]
+pub fn check_suite_yml_hosts_not_a_string_test() {
+    let yaml = r##"
+---
+- hosts: [all]
+  properties: []
+"##;
+    let err = parse_check_suite_err(yaml);
+    assert_eq!(err.line, Some(3));
+    assert_eq!(err.reason, "'hosts' must name an inventory group, found a list");
+}
+
+#[test]
+pub fn check_suite_unknown_inventory_name_test() {
+    let check_suite = parse_check_suite(CHECK_SUITE_YAML);
+    assert!(check_suite.check_inventory_names().is_empty());
+
+    let yaml = CHECK_SUITE_YAML.replace("- hosts: esel", "- hosts: eesel");
+    let check_suite = parse_check_suite(&yaml);
+    let errors = check_suite.check_inventory_names();
+    assert_eq!(errors.len(), 1);
+    assert_eq!(errors[0].reason, "hosts 'eesel' does not name an inventory group");
+    assert_eq!(errors[0].line, Some(26));
+    assert_eq!(errors[0].to_string(), "<string>:26: hosts 'eesel' does not name an inventory group");
+}
+
+static NESTED_INVENTORY_YAML: &'static str = r##"
+---
+- inventory:
+    all:
+      hosts: [ha01]
+      children: [controllers, nodes]
+    controllers: [control01, control02]
+    nodes:
+      children: [storage-nodes, compute-nodes]
+    storage-nodes: [node01, node02, node03]
+    galera-nodes: [node02, node03, node04]
+    compute-nodes: [node03, node04]
+
+- hosts: storage-nodes:&galera-nodes
+  properties: []
+
+- hosts: all:!controllers,!compute-nodes
+  properties: []
+
+- hosts: controllers, compute-nodes
+  properties: []
+
+- hosts: missing:&all
+  properties: []
+"##;
+
+#[test]
+pub fn check_suite_yml_nested_inventory_test() {
+    let check_suite = parse_check_suite(NESTED_INVENTORY_YAML);
+    let inventory = &check_suite.inventory;
+    assert_eq!(inventory.len(), 6);
+    assert_eq!(inventory["nodes"], vec!["node01", "node02", "node03", "node04"]);
+    assert_eq!(inventory["all"],
+               vec!["ha01", "control01", "control02", "node01", "node02", "node03", "node04"]);
+}
+
+#[test]
+pub fn check_suite_yml_host_patterns_test() {
+    let check_suite = parse_check_suite(NESTED_INVENTORY_YAML);
+    let checks = &check_suite.checks;
+    assert_eq!(checks[0].inventory_name, "storage-nodes:&galera-nodes");
+    assert_eq!(checks[0].hosts, vec!["node02", "node03"]);
+    assert_eq!(checks[1].hosts, vec!["ha01", "node01", "node02"]);
+    assert_eq!(checks[2].hosts, vec!["control01", "control02", "node03", "node04"]);
+    assert!(checks[3].hosts.is_empty());
+
+    let errors = check_suite.check_inventory_names();
+    assert_eq!(errors.len(), 1);
+    assert_eq!(errors[0].reason,
+               "group 'missing' in hosts 'missing:&all' does not name an inventory group");
+}
+
+#[test]
+pub fn check_suite_yml_nested_inventory_invalid_test() {
+    let err = parse_check_suite_err(&NESTED_INVENTORY_YAML.replace("[storage-nodes, compute-nodes]",
+                                                                   "[storage-nodes, all]"));
+    assert_eq!(err.reason.split(": ").next(), Some("inventory groups include each other"));
+    assert!(err.reason.contains("all -> nodes -> all"));
+
+    let err = parse_check_suite_err(&NESTED_INVENTORY_YAML.replace("[storage-nodes, compute-nodes]",
+                                                                   "[storage-nodes, computer-nodes]"));
+    assert_eq!(err.line, Some(9));
+    assert_eq!(err.reason,
+               "child 'computer-nodes' of inventory group 'nodes' does not name an inventory group");
+
+    let err = parse_check_suite_err(&NESTED_INVENTORY_YAML.replace("hosts: [ha01]", "host: [ha01]"));
+    assert_eq!(err.line, Some(5));
+    assert_eq!(err.reason,
+               "unknown key 'host' in inventory group 'all'; expected 'hosts', 'children' or 'vars'");
+}
+
+static HOST_PATTERNS_YAML: &'static str = r##"
+---
+- settings:
+    max_hosts_per_pattern: 14
+
+- inventory:
+    nodes:
+      - node[01:10].baremetal
+    web: ["web-{a,b,c}.example", "db[a:c]", "backup[0:20:10]"]
+    net: [10.0.4.0/28, 10.0.5.7/32]
+"##;
+
+#[test]
+pub fn check_suite_yml_host_patterns_expansion_test() {
+    let check_suite = parse_check_suite(HOST_PATTERNS_YAML);
+    let inventory = &check_suite.inventory;
+    assert_eq!(inventory["nodes"].len(), 10);
+    assert_eq!(inventory["nodes"][0], "node01.baremetal");
+    assert_eq!(inventory["nodes"][9], "node10.baremetal");
+    assert_eq!(inventory["web"],
+               vec!["web-a.example", "web-b.example", "web-c.example", "dba", "dbb", "dbc", "backup0",
+                    "backup10", "backup20"]);
+    assert_eq!(inventory["net"].len(), 15);
+    assert_eq!(inventory["net"][0], "10.0.4.1");
+    assert_eq!(inventory["net"][13], "10.0.4.14");
+    assert_eq!(inventory["net"][14], "10.0.5.7");
+}
+
+#[test]
+pub fn check_suite_yml_host_patterns_invalid_test() {
+    let err = parse_check_suite_err(&HOST_PATTERNS_YAML.replace("14", "9"));
+    assert_eq!(err.line, Some(8));
+    assert_eq!(err.reason,
+               "host in inventory group 'nodes': 'node[01:10].baremetal' expands to more than 9 hosts; raise \
+                the setting 'max_hosts_per_pattern' if this is intended");
+
+    let err = parse_check_suite_err(&HOST_PATTERNS_YAML.replace("14", "13"));
+    assert_eq!(err.reason,
+               "host in inventory group 'net': '10.0.4.0/28' expands to more than 13 hosts; raise the \
+                setting 'max_hosts_per_pattern' if this is intended");
+
+    let err = parse_check_suite_err(&HOST_PATTERNS_YAML.replace("[01:10]", "[10:01]"));
+    assert_eq!(err.reason,
+               "host in inventory group 'nodes': 'node[10:01].baremetal' has an invalid range '[10:01]'");
+
+    let err = parse_check_suite_err(&HOST_PATTERNS_YAML.replace("{a,b,c}", "{a,b,c"));
+    assert_eq!(err.reason, "host in inventory group 'web': 'web-{a,b,c.example' has an unclosed '{'");
+
+    let err = parse_check_suite_err(&HOST_PATTERNS_YAML.replace("/28", "/33"));
+    assert_eq!(err.reason, "host in inventory group 'net': '10.0.4.0/33' is not an IPv4 CIDR block");
+
+    let err = parse_check_suite_err(&HOST_PATTERNS_YAML.replace("14", "0"));
+    assert_eq!(err.reason, "setting 'max_hosts_per_pattern' must be a positive integer");
+}
+
+static ANSIBLE_FIXTURES: &'static str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/ansible");
+
+fn ansible_suite(inventory_file: &str) -> CheckSuite {
+    let yaml = format!(r##"
+---
+- inventory:
+    monitoring: [nagios.example.com]
+    webservers: [replaced.example.com]
+
+- inventory_file: {}/{}
+
+- inventory:
+    frontend:
+      children: [webservers, monitoring]
+
+- hosts: datacenter:!dbservers
+  properties: []
+"##,
+                       ANSIBLE_FIXTURES,
+                       inventory_file);
+    parse_check_suite(&yaml)
+}
+
+#[test]
+pub fn check_suite_yml_ansible_inventory_test() {
+    for inventory_file in &["hosts", "hosts.yml"] {
+        let check_suite = ansible_suite(inventory_file);
+        let inventory = &check_suite.inventory;
+        let webservers = vec!["web1.example.com", "web2.example.com", "web3.example.com", "foo.example.com"];
+        assert_eq!(inventory["webservers"], webservers);
+        assert_eq!(inventory["dbservers"], vec!["db-a.example.com", "db-b.example.com"]);
+        assert_eq!(inventory["datacenter"].len(), 6);
+        assert_eq!(inventory["ungrouped"], vec!["mail.example.com"]);
+        assert_eq!(inventory["all"].len(), 7);
+        assert_eq!(inventory["frontend"].len(), 5);
+        assert_eq!(inventory["frontend"][4], "nagios.example.com");
+        assert_eq!(check_suite.checks[0].hosts, webservers);
+
+        let foo = &check_suite.host_vars["foo.example.com"];
+        assert_eq!(foo["ansible_host"], ParamValue::String("192.0.2.10".to_string()));
+        assert_eq!(foo["ansible_port"], ParamValue::Int(2222));
+        assert_eq!(foo["http_port"], ParamValue::Int(80));
+        let db = &check_suite.host_vars["db-b.example.com"];
+        assert_eq!(db["ansible_host"], ParamValue::String("192.0.2.20".to_string()));
+        assert_eq!(db["http_port"], ParamValue::Int(8080));
+        assert_eq!(db["ntp_server"], ParamValue::String("ntp.db.example.com".to_string()));
+        assert!(check_suite.host_vars["mail.example.com"].is_empty());
+    }
+}
+
+#[test]
+pub fn check_suite_yml_ansible_inventory_invalid_test() {
+    let yaml = "---\n- inventory_file: does/not/exist\n";
+    let err = parse_check_suite_err(yaml);
+    assert_eq!(err.line, Some(2));
+    assert!(err.reason.starts_with("inventory file 'does/not/exist': could not read file"));
+
+    let err = parse_check_suite_err("---\n- inventory_file: { path: hosts }\n");
+    assert_eq!(err.reason, "'inventory_file' must be a path or a list of paths, found a hash");
+}
+
+#[test]
+pub fn check_suite_yml_cmdb_inventory_test() {
+    let yaml = format!("---\n- inventory_file: {}/cmdb.json\n- hosts: openstack\n  properties: []\n",
+                       ANSIBLE_FIXTURES);
+    let check_suite = parse_check_suite(&yaml);
+    assert_eq!(check_suite.checks[0].hosts,
+               vec!["compute01.example.com", "compute02.example.com", "control01.example.com"]);
+    let compute = &check_suite.host_vars["compute02.example.com"];
+    assert_eq!(compute["ansible_host"], ParamValue::String("192.0.2.32".to_string()));
+    assert_eq!(compute["http_port"], ParamValue::Int(8775));
+    assert_eq!(compute["ntp_server"], ParamValue::String("ntp.example.com".to_string()));
+    assert_eq!(check_suite.host_vars["compute01.example.com"]["http_port"], ParamValue::Int(8774));
+}
+
+/// Reads a suite whose inventory command prints `output`; unique files keep caches apart and are
+/// removed afterwards.
+fn command_suite(output: &str, cache: &str) -> SuiteResult<CheckSuite> {
+    let nanos = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap().subsec_nanos();
+    let path = std::env::temp_dir().join(format!("dirac-test-{}-{}.json", std::process::id(), nanos));
+    let cache_home = std::env::temp_dir().join(format!("dirac-test-cache-{}-{}", std::process::id(), nanos));
+    std::env::set_var("XDG_CACHE_HOME", &cache_home);
+    let read = |output: &str| {
+        std::fs::write(&path, output).unwrap();
+        let yaml = format!(r##"
+---
+- inventory_command:
+    command: [{}/inventory.sh, {}]
+{}
+- hosts: compute-nodes
+  properties: []
+"##,
+                           ANSIBLE_FIXTURES,
+                           path.display(),
+                           cache);
+        CheckSuite::read_from_string(&yaml)
+    };
+
+    let first = read(output);
+    let second = read(r#"{"compute-nodes": ["changed.example.com"]}"#);
+    std::fs::remove_file(&path).unwrap();
+    if !cache.is_empty() {
+        let cache_files: Vec<_> = std::fs::read_dir(cache_home.join("dirac")).unwrap().collect();
+        assert_eq!(cache_files.len(), 1);
+        let mode = cache_files[0].as_ref().unwrap().metadata().unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+        std::fs::remove_dir_all(&cache_home).unwrap();
+    }
+    assert!(!cache_home.exists());
+    first.and(second)
+}
+
+#[test]
+pub fn check_suite_yml_inventory_command_test() {
+    let output = r#"{"compute-nodes": {"hosts": ["compute01.example.com"]}}"#;
+    let check_suite = command_suite(output, "").unwrap();
+    assert_eq!(check_suite.checks[0].hosts, vec!["changed.example.com"]);
+
+    let check_suite = command_suite(output, "    cache: 60").unwrap();
+    assert_eq!(check_suite.checks[0].hosts, vec!["compute01.example.com"]);
+
+    let yaml = format!("---\n- inventory_command: {}/inventory.sh\n", ANSIBLE_FIXTURES);
+    let err = parse_check_suite_err(&yaml);
+    assert_eq!(err.line, Some(2));
+    assert_eq!(err.reason,
+               format!("inventory command '{0}/inventory.sh --list': failed with exit status: 2: usage: \
+                        {0}/inventory.sh FILE --list",
+                       ANSIBLE_FIXTURES));
+
+    let err = parse_check_suite_err("---\n- inventory_command: does/not/exist\n");
+    assert!(err.reason.starts_with("inventory command 'does/not/exist --list': could not be run: "));
+
+    let err = parse_check_suite_err("---\n- inventory_command: { cache: 60 }\n");
+    assert_eq!(err.reason, "'inventory_command' lacks a 'command'");
+}
+
+#[test]
+pub fn check_suite_yml_host_vars_test() {
+    let yaml = r#"
+---
+- inventory:
+    all:
+      children: [controllers, storage-nodes]
+      vars: { ssh_port: 22, port_offset: 0 }
+    controllers:
+      - control01.baremetal: { address: 10.1.0.1, ssh_port: 2222 }
+      - control02.baremetal
+    storage-nodes:
+      hosts:
+        - node0[5:6].baremetal: { port_offset: 1000 }
+      vars:
+        ssh_port: 2200
+        http: { path: /status }
+
+- hosts: all
+  properties:
+    - name: Status
+      http:
+        port: "{{ host.ssh_port | default(22) }}"
+        url: "https://{{ host.name }}{{host.http.path | default('/')}}"
+        body: "{{ '{{' }} host.name {{ \"}}\" }} is {{ host.name }}"
+      tls: {}
+"#;
+    let check_suite = parse_check_suite(yaml);
+    let vars = &check_suite.host_vars;
+    assert_eq!(vars["control01.baremetal"]["ssh_port"], ParamValue::Int(2222));
+    assert_eq!(vars["control02.baremetal"]["ssh_port"], ParamValue::Int(22));
+    assert_eq!(vars["node05.baremetal"]["ssh_port"], ParamValue::Int(2200));
+    assert_eq!(vars["node06.baremetal"]["port_offset"], ParamValue::Int(1000));
+    assert_eq!(check_suite.address_of("control01.baremetal"), "10.1.0.1");
+    assert_eq!(check_suite.address_of("node05.baremetal"), "node05.baremetal");
+
+    let property = &check_suite.checks[0].properties[0];
+    let control01 = check_suite.property_for(property, "control01.baremetal").unwrap();
+    assert_eq!(control01.params["port"], ParamValue::Int(2222));
+    assert_eq!(control01.params["url"], ParamValue::String("https://control01.baremetal/".to_string()));
+    assert_eq!(control01.params["body"],
+               ParamValue::String("{{ host.name }} is control01.baremetal".to_string()));
+    assert_eq!(control01.tls.unwrap().server_name, Some("control01.baremetal".to_string()));
+    let node05 = check_suite.property_for(property, "node05.baremetal").unwrap();
+    assert_eq!(node05.params["port"], ParamValue::Int(3200));
+    assert_eq!(node05.params["url"], ParamValue::String("https://node05.baremetal/status".to_string()));
+    assert_eq!(node05.tls.unwrap().server_name, None);
+    let unknown = check_suite.property_for(property, "unknown.baremetal").unwrap();
+    assert_eq!(unknown.params["port"], ParamValue::Int(22));
+
+    let check_suite = parse_check_suite(&yaml.replace("port_offset: 1000", "port_offset: 65000"));
+    let err = check_suite.property_for(&check_suite.checks[0].properties[0], "node05.baremetal").unwrap_err();
+    assert_eq!(err,
+               "param 'port': 2200 plus 'port_offset' 65000 of host 'node05.baremetal' is out of range");
+}
+
+#[test]
+pub fn check_suite_yml_host_vars_invalid_test() {
+    let yaml = r#"
+---
+- inventory:
+    nodes: [node01]
+
+- hosts: nodes
+  properties:
+    - name: SSH
+      ssh: { port: "{{ host.ssh_port }}" }
+    - name: Broken
+      ssh: { port: "{{ ssh_port }}" }
+"#;
+    let err = parse_check_suite_err(yaml);
+    assert_eq!(err.line, Some(11));
+    assert_eq!(err.reason,
+               "param 'port' of property 'Broken': invalid template '{{ ssh_port }}': expected a variable \
+                like 'host.port'");
+
+    let check_suite = parse_check_suite(&yaml.replace("{{ ssh_port }}", "22"));
+    let err = check_suite.property_for(&check_suite.checks[0].properties[0], "node01").unwrap_err();
+    assert_eq!(err, "param 'port': host 'node01' has no variable 'ssh_port'");
+
+    let err = parse_check_suite_err("---\n- inventory:\n    nodes:\n      - [node01]\n");
+    assert_eq!(err.line, Some(4));
+    assert_eq!(err.reason,
+               "host in inventory group 'nodes' must be a string or a hash of a host and its variables, \
+                found a list");
+}
+
+#[test]
+pub fn check_suite_yml_tls_test() {
+    let yaml = r##"
+---
+- hosts: all
+  properties:
+    - name: IMAPS Banner
+      tls: true
+      imap: { port: 993 }
+    - name: LDAPS
+      tls: { server_name: ldap.example.com }
+      raw_tcp: { port: 636 }
+    - name: Plain
+      tls: false
+      ssh: { port: 22 }
+"##;
+    let check_suite = parse_check_suite(yaml);
+    let properties = &check_suite.checks[0].properties;
+    assert_eq!(properties[0].tls, Some(TlsOptions::default()));
+    assert_eq!(properties[1].tls,
+               Some(TlsOptions { server_name: Some("ldap.example.com".to_string()) }));
+    assert_eq!(properties[2].tls, None);
+    assert_eq!(properties[2].module, "ssh");
+}
+
+#[test]
+pub fn check_suite_yml_tls_invalid_test() {
+    let yaml = r##"
+---
+- hosts: all
+  properties:
+    - name: IMAPS Banner
+      tls: { sni: imap.example.com }
+      imap: { port: 993 }
+"##;
+    let err = parse_check_suite_err(yaml);
+    assert_eq!(err.line, Some(6));
+    assert_eq!(err.reason, "unknown TLS option 'sni'");
+}
+
+#[test]
+pub fn check_suite_yml_tags_test() {
+    let yaml = r##"
+---
+- hosts: all
+  properties:
+    - name: Check SSH
+      tags: warning
+      ssh: { port: 22 }
+    - name: DNS TCP
+      tags: [slow, dns]
+      connect_tcp: { port: 53 }
+    - name: NetBios TCP Port
+      connect_tcp: { port: 139 }
+"##;
+    let check_suite = parse_check_suite(yaml);
+    let properties = &check_suite.checks[0].properties;
+    assert_eq!(properties[0].tags, vec!["warning".to_string()]);
+    assert_eq!(properties[1].tags, vec!["slow".to_string(), "dns".to_string()]);
+    assert!(properties[2].tags.is_empty());
+
+    let err = parse_check_suite_err(&yaml.replace("[slow, dns]", "[slow, 53]"));
+    assert_eq!(err.line, Some(9));
+    assert_eq!(err.reason, "'tags' must be a string or a list of strings, found an integer");
+}
+
+static FILTER_YAML: &'static str = r##"
+---
+- inventory:
+    all: [node04, node05, node09, node10]
+    galera_nodes: [node05, node10]
+
+- hosts: all
+  properties:
+    - name: Check SSH
+      ssh: { port: 22 }
+    - name: Check HTTP
+      http: { port: 80 }
+"##;
+
+fn selected(filter: &Filter) -> Vec<String> {
+    let check_suite = parse_check_suite(FILTER_YAML);
+    let check = &check_suite.checks[0];
+    let mut selected = Vec::new();
+    for property in &check.properties {
+        for host in &check_suite.inventory["all"] {
+            if filter.selects(&check_suite.inventory, check, property, host) {
+                selected.push(format!("{} {}", property.module, host));
+            }
+        }
+    }
+    selected
+}
+
+#[test]
+pub fn filter_test() {
+    assert_eq!(selected(&Filter::new()).len(), 8);
+
+    let mut filter = Filter::new();
+    filter.include(Selector::Host, "node0[5-9]*").unwrap();
+    assert_eq!(selected(&filter), vec!["ssh node05", "ssh node09", "http node05", "http node09"]);
+    filter.exclude(Selector::Group, "galera_*").unwrap();
+    assert_eq!(selected(&filter), vec!["ssh node09", "http node09"]);
+    filter.include(Selector::Property, "Check SSH").unwrap();
+    assert_eq!(selected(&filter), vec!["ssh node09"]);
+
+    let mut filter = Filter::new();
+    filter.include(Selector::Group, "galera_nodes").unwrap().exclude(Selector::Module, "ssh").unwrap();
+    assert_eq!(selected(&filter), vec!["http node05", "http node10"]);
+
+    let mut filter = Filter::new();
+    filter.include(Selector::Host, "node?4").unwrap().include(Selector::Host, "node1[!1]").unwrap();
+    assert_eq!(selected(&filter), vec!["ssh node04", "ssh node10", "http node04", "http node10"]);
+
+    let err = Filter::new().include(Selector::Host, "node[0-9").unwrap_err();
+    assert_eq!(err, "invalid pattern 'node[0-9': unclosed '['");
+}
+
+#[test]
+pub fn check_suite_apply_filter_test() {
+    let mut check_suite = parse_check_suite(FILTER_YAML);
+    let mut filter = Filter::new();
+    filter.include(Selector::Host, "node0*").unwrap().exclude(Selector::Module, "http").unwrap();
+    check_suite.apply_filter(&filter);
+
+    assert_eq!(check_suite.inventory["all"].len(), 4);
+    let properties = &check_suite.checks[0].properties;
+    assert_eq!(properties.len(), 2);
+    assert_eq!(properties[0].skipped_hosts, vec!["node10".to_string()]);
+    assert_eq!(properties[1].skipped_hosts.len(), 4);
+}
+
+#[test]
+pub fn check_suite_missing_file_test() {
+    let err = CheckSuite::read_from_file("does/not/exist.yml").unwrap_err();
+    assert_eq!(err.filename, Some("does/not/exist.yml".to_string()));
+    assert!(err.reason.starts_with("could not read file"));
+}
+
 fn parse_check_suite(check_suite_yaml: &str) -> CheckSuite {
-    let check_suite_op = CheckSuite::read_from_string(check_suite_yaml);
-    assert!(check_suite_op.is_some());
-    check_suite_op.unwrap()
+    let check_suite_res = CheckSuite::read_from_string(check_suite_yaml);
+    assert!(check_suite_res.is_ok());
+    check_suite_res.unwrap()
+}
+
+fn parse_check_suite_err(check_suite_yaml: &str) -> SuiteError {
+    let check_suite_res = CheckSuite::read_from_string(check_suite_yaml);
+    assert!(check_suite_res.is_err());
+    check_suite_res.unwrap_err()
 }
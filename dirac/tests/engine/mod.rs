@@ -0,0 +1,767 @@
+use dirac::checks::*;
+use dirac::engine::{CheckSuiteResult, Engine, FailurePolicy, Observer, PropertyError, PropertyResult,
+                    Verdict, MAX_RESPONSE_LEN};
+use dirac::modules::{ArgumentError, Args, CheckModule, Response};
+#[cfg(feature = "python")]
+use dirac::modules::Registry;
+use dirac::protocols::{ReadStrategy, ReadUntil, DEFAULT_MAX_SIZE};
+use std::cmp;
+#[cfg(feature = "python")]
+use std::io;
+use std::io::prelude::*;
+use std::net::TcpListener;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+#[derive(Default)]
+struct Recorder {
+    events: Vec<String>,
+}
+
+impl Observer for Recorder {
+    fn suite_started(&mut self, check_suite: &CheckSuite) {
+        self.events.push(format!("suite started: {} checks", check_suite.checks.len()));
+    }
+
+    fn property_started(&mut self, _: &Check, property: &Property, host: &str) {
+        self.events.push(format!("started: {} [{}]", property.name, host));
+    }
+
+    fn check_results_started(&mut self, check: &Check) {
+        self.events.push(format!("check: {}", check.inventory_name));
+    }
+
+    fn property_results_started(&mut self, _: &Check, property: &Property) {
+        self.events.push(format!("property: {}", property.name));
+    }
+
+    fn property_finished(&mut self, check: &Check, result: &PropertyResult) {
+        self.events.push(format!("finished: {} / {} [{}] {}",
+                                 check.inventory_name,
+                                 result.property.name,
+                                 result.host,
+                                 result.result.is_ok()));
+    }
+
+    fn suite_finished(&mut self, check_suite_result: &CheckSuiteResult) {
+        self.events.push(format!("suite finished: {} checks", check_suite_result.results.len()));
+    }
+}
+
+/// Connects to the port, then sleeps for `sleep_ms` or panics if `panic` is set, and records how
+/// many properties have been running at the same time.
+#[derive(Clone, Default)]
+struct Probe {
+    /// The number of properties running and the maximum seen.
+    running: Arc<Mutex<(usize, usize)>>,
+}
+
+impl Probe {
+    fn max_running(&self) -> usize {
+        self.running.lock().unwrap().1
+    }
+}
+
+impl CheckModule for Probe {
+    fn check_args(&self, params: &Params) -> Result<(), ArgumentError> {
+        try!(Args::new(params).port());
+        Ok(())
+    }
+
+    fn protocol(&self, _: &Params) -> String {
+        "connect/tcp".to_string()
+    }
+
+    fn check_response(&self, params: &Params, _: &Response) -> Result<(), String> {
+        if params.get("panic").and_then(|p| p.as_bool()) == Some(true) {
+            panic!("probe panics as requested");
+        }
+        {
+            let mut running = self.running.lock().unwrap();
+            running.0 += 1;
+            running.1 = cmp::max(running.0, running.1);
+        }
+        let sleep_ms = params.get("sleep_ms").and_then(|p| p.as_i64()).unwrap_or(0);
+        thread::sleep(Duration::from_millis(sleep_ms as u64));
+        self.running.lock().unwrap().0 -= 1;
+        Ok(())
+    }
+}
+
+/// Reads whatever the port sends until it closes the connection, as `raw/tcp` or `text/tcp` depending on
+/// `protocol`, and rejects it.
+struct Banner;
+
+impl CheckModule for Banner {
+    fn check_args(&self, params: &Params) -> Result<(), ArgumentError> {
+        try!(Args::new(params).port());
+        Ok(())
+    }
+
+    fn protocol(&self, params: &Params) -> String {
+        params.get("protocol").and_then(|p| p.as_str()).unwrap_or("text/tcp").to_string()
+    }
+
+    fn read_strategy(&self, _: &Params) -> ReadStrategy {
+        ReadStrategy {
+            until: ReadUntil::Close,
+            max_size: DEFAULT_MAX_SIZE,
+        }
+    }
+
+    fn check_response(&self, _: &Params, _: &Response) -> Result<(), String> {
+        Err("banner rejected".to_string())
+    }
+}
+
+/// Starts a server that sends `banner` and closes the connection, and returns its port.
+fn banner_server(banner: Vec<u8>) -> u16 {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+    thread::spawn(move || {
+        let mut stream = listener.accept().unwrap().0;
+        let _ = stream.write_all(&banner);
+    });
+
+    port
+}
+
+/// Returns the response kept for a property that received `banner` via `protocol`.
+fn response_of(protocol: &str, banner: Vec<u8>) -> Option<String> {
+    let yaml = format!(r#"
+- inventory:
+    local: [127.0.0.1]
+
+- hosts: local
+  properties:
+    - name: Banner
+      banner: {{ port: {}, protocol: {} }}
+"#,
+                       banner_server(banner),
+                       protocol);
+    let check_suite = CheckSuite::read_from_string(&yaml).unwrap();
+    let mut engine = Engine::new();
+    engine.register("banner", Banner);
+
+    let mut check_suite_result = engine.run(&check_suite);
+    let result = check_suite_result.results.remove(0).results.remove(0);
+    match result.result {
+        Err(PropertyError::FailedResponseCheck(ref reason)) => assert_eq!(reason, "banner rejected"),
+        ref other => panic!("unexpected result {:?}", other),
+    }
+    result.response
+}
+
+fn closed_port() -> u16 {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    listener.local_addr().unwrap().port()
+}
+
+#[test]
+pub fn run_observed_test() {
+    let open = TcpListener::bind("127.0.0.1:0").unwrap();
+    let yaml = format!(r#"
+- settings:
+    jobs: 3
+
+- inventory:
+    local: [127.0.0.1, localhost]
+    nowhere: []
+
+- hosts: local
+  properties:
+    - name: Open
+      connect_tcp: {{ port: {} }}
+    - name: Closed
+      connect_tcp: {{ port: {} }}
+
+- hosts: nowhere
+  properties:
+    - name: Unused
+      connect_tcp: {{ port: 22 }}
+"#,
+                       open.local_addr().unwrap().port(),
+                       closed_port());
+    let check_suite = CheckSuite::read_from_string(&yaml).unwrap();
+
+    let mut recorder = Recorder::default();
+    let check_suite_result = Engine::new().run_observed(&check_suite, &mut recorder);
+    assert_eq!(check_suite_result.results.len(), 2);
+    assert_eq!(check_suite_result.results[0].results.len(), 4);
+    assert!(check_suite_result.results[1].results.is_empty());
+
+    let events = &recorder.events;
+    assert_eq!(events.len(), 13);
+    assert_eq!(events[0], "suite started: 2 checks");
+    let mut started: Vec<&str> = events.iter()
+                                       .filter(|e| e.starts_with("started: "))
+                                       .map(|e| &e[..])
+                                       .collect();
+    started.sort();
+    assert_eq!(started,
+               vec!["started: Closed [127.0.0.1]",
+                    "started: Closed [localhost]",
+                    "started: Open [127.0.0.1]",
+                    "started: Open [localhost]"]);
+    let reported: Vec<&str> = events.iter()
+                                    .filter(|e| !e.starts_with("started: ") && !e.starts_with("suite "))
+                                    .map(|e| &e[..])
+                                    .collect();
+    assert_eq!(reported,
+               vec!["check: local",
+                    "property: Open",
+                    "finished: local / Open [127.0.0.1] true",
+                    "finished: local / Open [localhost] true",
+                    "property: Closed",
+                    "finished: local / Closed [127.0.0.1] false",
+                    "finished: local / Closed [localhost] false"]);
+    assert_eq!(events[12], "suite finished: 2 checks");
+}
+
+#[test]
+pub fn failure_policy_test() {
+    let yaml = format!(r#"
+- inventory:
+    local: [127.0.0.1]
+
+- hosts: local
+  properties:
+    - name: Closed
+      tags: warning
+      connect_tcp: {{ port: {} }}
+"#,
+                       closed_port());
+    let check_suite = CheckSuite::read_from_string(&yaml).unwrap();
+    let check_suite_result = Engine::new().run(&check_suite);
+    let property_result = &check_suite_result.results[0].results[0];
+    match property_result.result {
+        Err(PropertyError::Closed(..)) => {}
+        ref other => panic!("unexpected result {:?}", other),
+    }
+
+    let policy = FailurePolicy::new();
+    assert!(policy.is_fatal(property_result));
+    assert_eq!(policy.verdict(&check_suite_result), Verdict::Failed);
+
+    let mut policy = FailurePolicy::new();
+    policy.non_fatal_error("Timeout");
+    assert_eq!(policy.verdict(&check_suite_result), Verdict::Failed);
+    policy.non_fatal_error("Closed");
+    assert!(!policy.is_fatal(property_result));
+    assert_eq!(policy.verdict(&check_suite_result), Verdict::Passed);
+
+    let mut policy = FailurePolicy::new();
+    policy.non_fatal_tag("warning");
+    assert_eq!(policy.verdict(&check_suite_result), Verdict::Passed);
+
+    let mut check_suite_result = check_suite_result;
+    check_suite_result.results[0].results[0].result = Err(PropertyError::Unclassified);
+    assert_eq!(policy.verdict(&check_suite_result), Verdict::InternalError);
+}
+
+#[test]
+pub fn run_skipped_test() {
+    let yaml = format!(r#"
+- inventory:
+    local: [127.0.0.1, localhost]
+
+- hosts: local
+  properties:
+    - name: Closed
+      connect_tcp: {{ port: {} }}
+    - name: Skipped
+      connect_tcp: {{ port: 1 }}
+"#,
+                       closed_port());
+    let mut check_suite = CheckSuite::read_from_string(&yaml).unwrap();
+    let mut filter = Filter::new();
+    filter.exclude(Selector::Property, "Skipped").unwrap().exclude(Selector::Host, "localhost").unwrap();
+    check_suite.apply_filter(&filter);
+
+    let mut recorder = Recorder::default();
+    let check_suite_result = Engine::new().run_observed(&check_suite, &mut recorder);
+    let results = &check_suite_result.results[0].results;
+    assert_eq!(results.len(), 4);
+    assert!(!results[0].skipped);
+    assert!(results[0].result.is_err());
+    assert!(results.iter().skip(1).all(|r| r.skipped && r.result.is_ok()));
+    assert_eq!(FailurePolicy::new().verdict(&check_suite_result), Verdict::Failed);
+
+    assert_eq!(recorder.events.iter().filter(|e| e.starts_with("started: ")).count(), 1);
+    assert_eq!(recorder.events.iter().filter(|e| e.starts_with("finished: ")).count(), 4);
+
+    filter.exclude(Selector::Host, "127.0.0.1").unwrap();
+    check_suite.apply_filter(&filter);
+    let check_suite_result = Engine::new().run(&check_suite);
+    assert!(check_suite_result.results[0].results.iter().all(|r| r.skipped));
+    assert_eq!(FailurePolicy::new().verdict(&check_suite_result), Verdict::Passed);
+}
+
+#[test]
+pub fn run_host_vars_test() {
+    let open = TcpListener::bind("127.0.0.1:0").unwrap();
+    let yaml = format!(r#"
+- inventory:
+    nodes:
+      - node05.baremetal: {{ address: 127.0.0.1, service_port: {} }}
+      - node06.baremetal: {{ address: 127.0.0.1 }}
+
+- hosts: nodes
+  properties:
+    - name: Service
+      connect_tcp: {{ port: "{{{{ host.service_port | default({}) }}}}" }}
+"#,
+                       open.local_addr().unwrap().port(),
+                       closed_port());
+    let check_suite = CheckSuite::read_from_string(&yaml).unwrap();
+
+    let check_suite_result = Engine::new().run(&check_suite);
+    let results = &check_suite_result.results[0].results;
+    assert_eq!(results[0].host, "node05.baremetal");
+    assert!(results[0].result.is_ok());
+    assert_eq!(results[1].host, "node06.baremetal");
+    assert!(results[1].result.is_err());
+}
+
+/// Starts an HTTP server answering a single request with the request's head as body.
+fn http_echo_server() -> u16 {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+    thread::spawn(move || {
+        let mut stream = listener.accept().unwrap().0;
+        let mut request = Vec::new();
+        let mut buf = [0u8; 1024];
+        while !request.ends_with(b"\r\n\r\n") {
+            let len = stream.read(&mut buf).unwrap();
+            if len == 0 {
+                return;
+            }
+            request.extend_from_slice(&buf[..len]);
+        }
+        let _ = write!(stream,
+                       "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+                       request.len());
+        let _ = stream.write_all(&request);
+    });
+
+    port
+}
+
+#[test]
+pub fn run_http_with_address_test() {
+    let port = http_echo_server();
+    let yaml = format!(r#"
+- inventory:
+    web:
+      - www.dirac.test: {{ address: 127.0.0.1 }}
+
+- hosts: web
+  properties:
+    - name: Virtual Host
+      http:
+        port: {0}
+        verb: GET
+        uri: /
+        response_code: 200
+        body_contains: "Host: www.dirac.test:{0}"
+"#,
+                       port);
+    let check_suite = CheckSuite::read_from_string(&yaml).unwrap();
+
+    let check_suite_result = Engine::new().run(&check_suite);
+    let result = &check_suite_result.results[0].results[0];
+    assert_eq!(result.host, "www.dirac.test");
+    assert!(result.result.is_ok(), "{:?}", result.result);
+}
+
+/// The Python check modules of this repository.
+#[cfg(feature = "python")]
+static CHECK_MODULES: &'static str = concat!(env!("CARGO_MANIFEST_DIR"), "/../check_modules");
+
+/// Makes the Python check modules importable.
+#[cfg(feature = "python")]
+fn import_check_modules() {
+    use cpython::{ObjectProtocol, Python};
+
+    let gil = Python::acquire_gil();
+    let py = gil.python();
+    let path = py.import("sys").unwrap().get(py, "path").unwrap();
+    if !path.call_method(py, "__contains__", (CHECK_MODULES,), None).unwrap().is_true(py).unwrap() {
+        path.call_method(py, "insert", (0, CHECK_MODULES), None).unwrap();
+    }
+}
+
+/// Registers a Python check module `dirac.<name>` speaking `protocol` whose `challenge` and `read_strategy`
+/// return the Python expressions `challenge` and `read_strategy`.
+#[cfg(feature = "python")]
+fn define_python_module(name: &str, protocol: &str, challenge: &str, read_strategy: &str) {
+    use cpython::Python;
+
+    import_check_modules();
+    let source = format!(r#"
+import sys, types
+from dirac import Module as Base
+
+class Module(Base):
+    _module_protocol = "{protocol}"
+
+    @classmethod
+    def check_args(cls, **kwargs):
+        return True
+
+    def challenge(self):
+        return {challenge}
+
+    def read_strategy(self):
+        return {read_strategy}
+
+    def check_response(self, **kwargs):
+        return True
+
+module = types.ModuleType("dirac.{name}")
+module.Module = Module
+sys.modules[module.__name__] = module
+"#,
+                         name = name,
+                         protocol = protocol,
+                         challenge = challenge,
+                         read_strategy = read_strategy);
+    let gil = Python::acquire_gil();
+    gil.python().run(&source, None, None).unwrap();
+}
+
+/// Runs the Python check module `dirac.<module>` against a closed local port and returns its result.
+#[cfg(feature = "python")]
+fn run_python_module(module: &str) -> Result<(), PropertyError> {
+    let open = TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = open.local_addr().unwrap().port();
+    let yaml = format!(r#"
+- inventory:
+    local: [127.0.0.1]
+
+- hosts: local
+  properties:
+    - name: Python module
+      {}: {{ port: {} }}
+"#,
+                       module,
+                       port);
+    let check_suite = CheckSuite::read_from_string(&yaml).unwrap();
+
+    let mut check_suite_result = Engine::with_registry(Registry::empty()).run(&check_suite);
+    check_suite_result.results.remove(0).results.remove(0).result
+}
+
+/// Asserts that `result` failed because the module returned invalid input, for `reason`.
+#[cfg(feature = "python")]
+fn assert_invalid_input(result: Result<(), PropertyError>, reason: &str) {
+    match result {
+        Err(PropertyError::FailedExecution(io::ErrorKind::InvalidInput, ref message)) => {
+            assert_eq!(message, reason)
+        }
+        other => panic!("unexpected result {:?}", other),
+    }
+}
+
+#[test]
+#[cfg(feature = "python")]
+pub fn run_python_module_with_invalid_dns_query_test() {
+    define_python_module("dns_unknown_type", "dns/udp", r#"{"name": "example.com", "type": "XYZ"}"#, "None");
+    assert_invalid_input(run_python_module("dns_unknown_type"),
+                           "invalid DNS query: unknown type 'XYZ'");
+
+    define_python_module("dns_missing_name", "dns/udp", r#"{"type": "A"}"#, "None");
+    assert_invalid_input(run_python_module("dns_missing_name"),
+                           "invalid DNS query: missing 'name'");
+
+    define_python_module("dns_not_a_dict", "dns/udp", r#""example.com""#, "None");
+    assert_invalid_input(run_python_module("dns_not_a_dict"), "invalid DNS query: not a dict");
+}
+
+#[test]
+#[cfg(feature = "python")]
+pub fn run_python_module_with_invalid_dialog_test() {
+    define_python_module("dialog_not_a_list", "dialog/tcp", r#""HELO""#, "None");
+    assert_invalid_input(run_python_module("dialog_not_a_list"), "invalid dialog: not a list");
+
+    define_python_module("dialog_step_not_a_dict", "dialog/tcp", r#"[{"send": "HELO"}, "QUIT"]"#, "None");
+    assert_invalid_input(run_python_module("dialog_step_not_a_dict"),
+                           "invalid dialog: step 2 is not a dict");
+
+    define_python_module("dialog_send_not_a_string", "dialog/tcp", r#"[{"send": 42}]"#, "None");
+    assert_invalid_input(run_python_module("dialog_send_not_a_string"),
+                           "invalid dialog: step 1 'send' is not a string");
+
+    define_python_module("dialog_zero_timeout", "dialog/tcp", r#"[{"expect": "220", "timeout": 0}]"#, "None");
+    assert_invalid_input(run_python_module("dialog_zero_timeout"),
+                           "invalid dialog: step 1 'timeout' is not a positive number of seconds");
+}
+
+#[test]
+#[cfg(feature = "python")]
+pub fn run_python_module_with_invalid_idle_timeout_test() {
+    let idle_timeouts = vec![("idle_zero", "0"), ("idle_negative", "-1"), ("idle_nan", "float('nan')")];
+    for (name, idle_timeout) in idle_timeouts {
+        define_python_module(name,
+                             "text/tcp",
+                             "None",
+                             &format!(r#"{{"until": "idle", "idle_timeout": {}}}"#, idle_timeout));
+        assert_invalid_input(run_python_module(name),
+                               "invalid read strategy: 'idle_timeout' must be a positive number of seconds");
+    }
+}
+
+#[test]
+#[cfg(feature = "python")]
+pub fn run_python_module_with_invalid_http_request_test() {
+    define_python_module("http_headers_list",
+                         "http/tcp",
+                         r#"{"verb": "GET", "uri": "/", "headers": ["Accept: */*"]}"#,
+                         "None");
+    assert_invalid_input(run_python_module("http_headers_list"),
+                         "invalid HTTP request: 'headers' is not a dict");
+}
+
+/// Registers a Python check module `dirac.<name>` that merely subclasses `dirac.<base>`, as custom modules
+/// built on the reference implementations do.
+#[cfg(feature = "python")]
+fn define_python_subclass(name: &str, base: &str) {
+    use cpython::Python;
+
+    import_check_modules();
+    let source = format!(r#"
+import sys, types
+from dirac.{base} import Module as Base
+
+class Module(Base):
+    pass
+
+module = types.ModuleType("dirac.{name}")
+module.Module = Module
+sys.modules[module.__name__] = module
+"#,
+                         name = name,
+                         base = base);
+    let gil = Python::acquire_gil();
+    gil.python().run(&source, None, None).unwrap();
+}
+
+/// Starts an HTTPS server presenting the test certificate that answers a single request; returns its port.
+#[cfg(feature = "python")]
+fn https_server() -> u16 {
+    use openssl::ssl::{SslAcceptor, SslFiletype, SslMethod};
+
+    let fixtures = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/tls");
+    let mut acceptor = SslAcceptor::mozilla_intermediate(SslMethod::tls()).unwrap();
+    acceptor.set_private_key_file(format!("{}/server.key", fixtures), SslFiletype::PEM).unwrap();
+    acceptor.set_certificate_file(format!("{}/server.pem", fixtures), SslFiletype::PEM).unwrap();
+    let acceptor = acceptor.build();
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+    thread::spawn(move || {
+        let mut stream = acceptor.accept(listener.accept().unwrap().0).unwrap();
+        let mut request = Vec::new();
+        let mut buf = [0u8; 1024];
+        while !request.ends_with(b"\r\n\r\n") {
+            let len = stream.read(&mut buf).unwrap();
+            if len == 0 {
+                return;
+            }
+            request.extend_from_slice(&buf[..len]);
+        }
+        let _ = stream.write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 2\r\nConnection: close\r\n\r\nok");
+    });
+
+    port
+}
+
+/// Starts an SMTP server that greets and waits for the client to hang up; returns its port.
+#[cfg(feature = "python")]
+fn smtp_server() -> u16 {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+    thread::spawn(move || {
+        let mut stream = listener.accept().unwrap().0;
+        stream.write_all(b"220 mail.local ESMTP Postfix\r\n").unwrap();
+        let mut buf = [0u8; 1024];
+        while stream.read(&mut buf).map(|len| len > 0).unwrap_or(false) {}
+    });
+
+    port
+}
+
+/// Starts a DNS server that answers a single query without any records; returns its port.
+#[cfg(feature = "python")]
+fn dns_server() -> u16 {
+    use std::net::UdpSocket;
+
+    let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+    let port = socket.local_addr().unwrap().port();
+    thread::spawn(move || {
+        let mut query = [0u8; 512];
+        let (len, peer) = socket.recv_from(&mut query).unwrap();
+        // Same id and question, QR and RD/RA set, NOERROR and no records
+        let mut reply = vec![query[0], query[1], 0x81, 0x80, query[4], query[5], 0, 0, 0, 0, 0, 0];
+        reply.extend_from_slice(&query[12..len]);
+        socket.send_to(&reply, peer).unwrap();
+    });
+
+    port
+}
+
+#[test]
+#[cfg(feature = "python")]
+pub fn run_python_reference_module_subclasses_test() {
+    for base in &["http", "https", "smtp", "dns"] {
+        define_python_subclass(&format!("custom_{}", base), base);
+    }
+    let yaml = format!(r#"
+- inventory:
+    local: [127.0.0.1]
+
+- hosts: local
+  properties:
+    - name: HTTP
+      custom_http: {{ port: {}, verb: GET, uri: /, response_code: 200 }}
+    - name: HTTPS
+      custom_https: {{ port: {}, verb: GET, uri: /, response_code: 200, body_contains: ok }}
+    - name: SMTP
+      custom_smtp: {{ port: {}, software: ".*Postfix.*", proxy: False, return_code: 220 }}
+    - name: DNS
+      custom_dns: {{ port: {}, name: example.com, rcode: NOERROR }}
+"#,
+                       http_echo_server(),
+                       https_server(),
+                       smtp_server(),
+                       dns_server());
+    let check_suite = CheckSuite::read_from_string(&yaml).unwrap();
+
+    let check_suite_result = Engine::new().run(&check_suite);
+    let results = &check_suite_result.results[0].results;
+    assert_eq!(results.len(), 4);
+    for result in results {
+        assert!(result.result.is_ok(), "{}: {:?}", result.property.name, result.result);
+    }
+}
+
+#[test]
+#[cfg(feature = "python")]
+pub fn run_python_http_module_test() {
+    import_check_modules();
+    let port = http_echo_server();
+    let yaml = format!(r#"
+- inventory:
+    local: [127.0.0.1]
+
+- hosts: local
+  properties:
+    - name: Python HTTP
+      http: {{ port: {}, verb: GET, uri: /, response_code: 200, body_contains: "GET / HTTP/1.1" }}
+"#,
+                       port);
+    let check_suite = CheckSuite::read_from_string(&yaml).unwrap();
+
+    let check_suite_result = Engine::with_registry(Registry::empty()).run(&check_suite);
+    let result = &check_suite_result.results[0].results[0];
+    assert!(result.result.is_ok(), "{:?}", result.result);
+}
+
+#[test]
+pub fn response_excerpt_test() {
+    assert_eq!(response_of("text/tcp", b"220 ready\r\n".to_vec()),
+               Some("220 ready\r\n".to_string()));
+    assert_eq!(response_of("raw/tcp", b"\x00ok\xff".to_vec()),
+               Some("\\x00ok\\xff".to_string()));
+}
+
+#[test]
+pub fn response_excerpt_truncates_at_char_boundary_test() {
+    let at_limit: String = ::std::iter::repeat('\u{e9}').take(MAX_RESPONSE_LEN).collect();
+    assert_eq!(response_of("text/tcp", at_limit.clone().into_bytes()), Some(at_limit.clone()));
+
+    let over_limit = format!("{}\u{e9}", at_limit);
+    assert_eq!(response_of("text/tcp", over_limit.into_bytes()),
+               Some(format!("{}...", at_limit)));
+}
+
+#[test]
+pub fn run_concurrently_test() {
+    let open = TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = open.local_addr().unwrap().port();
+    let yaml = format!(r#"
+- settings:
+    jobs: 4
+
+- inventory:
+    local: [127.0.0.1]
+
+- hosts: local
+  properties:
+    - name: Slowest
+      probe: {{ port: {0}, sleep_ms: 600 }}
+    - name: Slow
+      probe: {{ port: {0}, sleep_ms: 400 }}
+    - name: Fast
+      probe: {{ port: {0}, sleep_ms: 200 }}
+    - name: Fastest
+      probe: {{ port: {0}, sleep_ms: 0 }}
+"#,
+                       port);
+    let check_suite = CheckSuite::read_from_string(&yaml).unwrap();
+    let probe = Probe::default();
+    let mut engine = Engine::new();
+    engine.register("probe", probe.clone());
+
+    let start = Instant::now();
+    let check_suite_result = engine.run(&check_suite);
+    assert!(start.elapsed() < Duration::from_millis(1200));
+    assert!(probe.max_running() > 1);
+
+    let names: Vec<&str> = check_suite_result.results[0]
+                               .results
+                               .iter()
+                               .map(|r| &r.property.name[..])
+                               .collect();
+    assert_eq!(names, vec!["Slowest", "Slow", "Fast", "Fastest"]);
+    assert!(check_suite_result.results[0].results.iter().all(|r| r.result.is_ok()));
+}
+
+#[test]
+pub fn run_panicking_module_test() {
+    let open = TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = open.local_addr().unwrap().port();
+    let yaml = format!(r#"
+- settings:
+    jobs: 2
+
+- inventory:
+    local: [127.0.0.1]
+
+- hosts: local
+  properties:
+    - name: Before
+      probe: {{ port: {0} }}
+    - name: Panics
+      probe: {{ port: {0}, panic: true }}
+    - name: After
+      probe: {{ port: {0} }}
+"#,
+                       port);
+    let check_suite = CheckSuite::read_from_string(&yaml).unwrap();
+    let mut engine = Engine::new();
+    engine.register("probe", Probe::default());
+
+    let mut recorder = Recorder::default();
+    let check_suite_result = engine.run_observed(&check_suite, &mut recorder);
+    let results = &check_suite_result.results[0].results;
+    assert_eq!(results.len(), 3);
+    assert!(results[0].result.is_ok());
+    match results[1].result {
+        Err(PropertyError::Unclassified) => {}
+        ref other => panic!("unexpected result {:?}", other),
+    }
+    assert!(results[2].result.is_ok());
+    assert_eq!(recorder.events.iter().filter(|e| e.starts_with("finished: ")).count(), 3);
+    assert_eq!(FailurePolicy::new().verdict(&check_suite_result), Verdict::InternalError);
+}
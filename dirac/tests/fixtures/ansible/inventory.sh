@@ -0,0 +1,7 @@
+#!/bin/sh
+# A dynamic inventory printing the file given as first argument for --list.
+if [ "$2" != "--list" ]; then
+    echo "usage: $0 FILE --list" >&2
+    exit 2
+fi
+exec cat "$1"
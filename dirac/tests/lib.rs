@@ -1,3 +1,11 @@
+#[cfg(feature = "python")]
+extern crate cpython;
 extern crate dirac;
+extern crate openssl;
+extern crate rustc_serialize;
 
 pub mod checks;
+pub mod engine;
+pub mod modules;
+pub mod protocols;
+pub mod reports;
@@ -0,0 +1,452 @@
+use dirac::checks::*;
+use dirac::engine::{self, Engine, PropertyError};
+use dirac::modules::*;
+use dirac::protocols::*;
+use std::collections::HashMap;
+use std::io::prelude::*;
+use std::net::TcpListener;
+use std::thread;
+
+/// Reads the params of a single property given as `<module>: { ... }`.
+fn params(module_yaml: &str) -> Params {
+    let yaml = format!("- hosts: all\n  properties:\n    - name: Test\n      {}\n", module_yaml);
+    let check_suite = CheckSuite::read_from_string(&yaml).unwrap();
+    check_suite.checks[0].properties[0].params.clone()
+}
+
+fn module(name: &str) -> Box<CheckModule> {
+    builtin(name).unwrap()
+}
+
+fn check_args_err(name: &str, module_yaml: &str) -> String {
+    module(name).check_args(&params(module_yaml)).unwrap_err().to_string()
+}
+
+fn check_text(name: &str, module_yaml: &str, text: &str) -> Result<(), String> {
+    let params = params(module_yaml);
+    assert_eq!(module(name).check_args(&params), Ok(()));
+    module(name).check_response(&params, &Response::Text(text.to_string()))
+}
+
+fn http_response(response_code: u16, headers: &[(&str, &str)], body: &str) -> Response {
+    Response::Http(HttpResponse {
+        response_code: response_code,
+        headers: headers.iter().map(|&(n, v)| (n.to_string(), v.to_string())).collect::<HashMap<_, _>>(),
+        body: body.to_string(),
+        redirects: Vec::new(),
+        tls: None,
+        body_truncated: false,
+    })
+}
+
+fn record(record_type: &str, ttl: u32, data: &str) -> DnsRecord {
+    DnsRecord {
+        name: "example.com.".to_string(),
+        record_type: record_type.to_string(),
+        ttl: ttl,
+        data: data.to_string(),
+    }
+}
+
+fn dns_response(rcode: &str, answers: Vec<DnsRecord>) -> Response {
+    Response::Dns(DnsResponse {
+        rcode: rcode.to_string(),
+        authoritative: false,
+        truncated: false,
+        recursion_available: true,
+        answers: answers,
+        authority: Vec::new(),
+    })
+}
+
+#[test]
+pub fn builtin_modules_test() {
+    let names = ["connect_tcp", "raw_tcp", "ssh", "smtp", "ftp", "http", "https", "mysql", "dns", "dns_udp"];
+    for name in &names {
+        assert!(builtin(name).is_some(), "no built-in module '{}'", name);
+    }
+    assert!(builtin("dialog").is_none());
+    assert!(builtin("imap").is_none());
+}
+
+#[test]
+pub fn ssh_module_test() {
+    let ssh = r#"ssh: { port: 22, version: 2.0, software: "OpenSSH.*" }"#;
+    assert_eq!(check_text("ssh", ssh, "SSH-2.0-OpenSSH_7.4p1 Debian\r\n"), Ok(()));
+    assert_eq!(check_text("ssh", ssh, "SSH-1.99-OpenSSH_7.4\r\n"),
+               Err("Unexpected version '1.99'; expected '2.0'.".to_string()));
+    assert_eq!(check_text("ssh", ssh, "SSH-2.0-dropbear_2016.74\r\n"),
+               Err("Unexpected software version 'dropbear_2016.74'; expected to match against 'OpenSSH.*'."
+                       .to_string()));
+    assert!(check_text("ssh", ssh, "HTTP/1.1 400 Bad Request\r\n").unwrap_err().starts_with("Invalid"));
+    assert_eq!(module("ssh").read_strategy(&params(ssh)).until,
+               ReadUntil::Delimiter(b"\n".to_vec()));
+
+    assert_eq!(check_args_err("ssh", "ssh: { port: 22, version: 2.0 }"),
+               "'software' is missing");
+    assert_eq!(check_args_err("ssh", r#"ssh: { port: 70000, version: 2.0, software: "x" }"#),
+               "'port': '70000' is not a valid port number");
+    assert_eq!(check_args_err("ssh", r#"ssh: { port: 22, version: 2.0, software: "(" }"#),
+               "'software': '(' is not a valid regular expression");
+    assert_eq!(check_args_err("ssh", r#"ssh: { port: 22, version: 2.0, software: "x", banner: "y" }"#),
+               "'banner': 'y' is not a known param");
+}
+
+#[test]
+pub fn smtp_module_test() {
+    let smtp = r#"smtp: { port: 25, software: ".*Postfix.*", proxy: False, return_code: 220 }"#;
+    assert_eq!(check_text("smtp", smtp, "220 mail.example.com ESMTP Postfix\r\n"), Ok(()));
+    assert_eq!(check_text("smtp", smtp, "554 mail.example.com ESMTP Postfix\r\n"),
+               Err("Unexpected result code '554'; expected '220'.".to_string()));
+    assert_eq!(module("smtp").protocol(&params(smtp)), "text/tcp");
+    match module("smtp").challenge(&params(smtp)) {
+        Payload::Text(challenge) => assert_eq!(challenge, "quit\n"),
+        payload => panic!("unexpected payload {:?}", payload),
+    }
+
+    let starttls = r#"smtp: { port: 25, software: ".*", proxy: False, return_code: 220,
+                             starttls: required }"#;
+    assert_eq!(module("smtp").protocol(&params(starttls)), "starttls/smtp");
+    assert_eq!(check_text("smtp", starttls, "220 mail.example.com ESMTP\r\n"),
+               Err("Connection has not been upgraded via STARTTLS.".to_string()));
+
+    let proxy = starttls.replace("proxy: False", "proxy: True");
+    assert_eq!(check_args_err("smtp", &proxy),
+               "'starttls': 'required' cannot be combined with proxy");
+    assert_eq!(check_args_err("smtp", &smtp.replace("220", "700")),
+               "'return_code': '700' is not a valid return code");
+}
+
+#[test]
+pub fn ftp_module_test() {
+    let ftp = "ftp: { port: 21, response_code: 220 }";
+    assert_eq!(check_text("ftp", ftp, "220 (vsFTPd 3.0.3)\r\n"), Ok(()));
+    assert_eq!(check_text("ftp", ftp, "421 Service not available\r\n"),
+               Err("Unexpected response code '421'; expected '220'.".to_string()));
+    assert_eq!(check_args_err("ftp", "ftp: { port: 21 }"), "'response_code' is missing");
+}
+
+#[test]
+pub fn mysql_module_test() {
+    let mysql = params("mysql: { port: 3306 }");
+    let mut handshake = vec![0x0A];
+    handshake.extend(b"5.7.22\0");
+    handshake.extend(&[1, 0, 0, 0]);
+    handshake.extend(b"abcdefgh\0");
+    handshake.extend(&[0xFF, 0xF7]);
+    let mut packet = vec![handshake.len() as u8, 0, 0, 0];
+    packet.extend(handshake);
+    assert_eq!(module("mysql").check_response(&mysql, &Response::Bytes(packet)), Ok(()));
+
+    let ssh = Response::Bytes(b"SSH-2.0-x\r\n".to_vec());
+    let err = module("mysql").check_response(&mysql, &ssh).unwrap_err();
+    assert_eq!(err, "Did not get expected response: 53 53 48 2d 32 2e 30 2d 78 0d 0a");
+}
+
+#[test]
+pub fn http_module_test() {
+    let http = params(r#"http: { port: 80, verb: get, uri: /, response_code: [200, 204], location: /home,
+                                 expected_headers: { Cache-Control: no-cache }, body_regex: "ok|up" }"#);
+    let headers = [("Location", "/home"), ("Cache-Control", "no-cache")];
+    assert_eq!(module("http").check_args(&http), Ok(()));
+    assert_eq!(module("http").check_response(&http, &http_response(204, &headers, "all up")), Ok(()));
+    assert_eq!(module("http").check_response(&http, &http_response(500, &headers, "ok")),
+               Err("Unexpected response code '500'; expected '200' or '204'.".to_string()));
+    assert_eq!(module("http").check_response(&http, &http_response(200, &headers[1..], "ok")),
+               Err("Unexpected location 'None'; expected '/home'.".to_string()));
+    assert_eq!(module("http").check_response(&http, &http_response(200, &headers, "down")),
+               Err("Body does not match 'ok|up'.".to_string()));
+    match module("http").challenge(&http) {
+        Payload::Http(request) => assert_eq!((&request.verb[..], &request.uri[..]), ("GET", "/")),
+        payload => panic!("unexpected payload {:?}", payload),
+    }
+
+    assert_eq!(check_args_err("http", "http: { port: 80, verb: FETCH, uri: /, response_code: 200 }"),
+               "'verb': 'FETCH' is not a valid HTTP verb");
+    assert_eq!(check_args_err("http",
+                              "http: { port: 80, verb: GET, uri: /, response_code: 200, redirect_chain: [/a] }"),
+               "'redirect_chain': '[/a]' requires follow_redirects");
+}
+
+#[test]
+pub fn https_module_test() {
+    let https = params("https: { port: 443, verb: GET, uri: /, response_code: 200, min_days_valid: 30, \
+                        expected_san: www.example.com, min_tls_version: TLSv1.2 }");
+    assert_eq!(module("https").check_args(&https), Ok(()));
+    assert_eq!(module("https").check_response(&https, &http_response(200, &[], "")),
+               Err("No TLS certificate in response.".to_string()));
+
+    let certificate = Certificate {
+        subject: "CN=www.example.com".to_string(),
+        issuer: "CN=Test CA".to_string(),
+        sans: vec!["www.example.com".to_string()],
+        not_before: "Jan  1 00:00:00 2020 GMT".to_string(),
+        not_after: "Jan  1 00:00:00 2030 GMT".to_string(),
+        days_valid: 10,
+        key_type: "RSA 2048".to_string(),
+    };
+    let mut response = http_response(200, &[], "");
+    if let Response::Http(ref mut response) = response {
+        response.tls = Some(TlsInfo {
+            version: "TLSv1.2".to_string(),
+            cipher: "ECDHE-RSA-AES128-GCM-SHA256".to_string(),
+            chain: vec![certificate],
+            verify_error: None,
+        });
+    }
+    assert_eq!(module("https").check_response(&https, &response),
+               Err("Certificate 'CN=www.example.com' expires in 10 days on 'Jan  1 00:00:00 2030 GMT'; \
+                    expected at least 30 days."
+                       .to_string()));
+
+    assert_eq!(check_args_err("https",
+                              "https: { port: 443, verb: GET, uri: /, response_code: 200, min_tls_version: v2 }"),
+               "'min_tls_version': 'v2' is not one of ['SSLv3', 'TLSv1', 'TLSv1.1', 'TLSv1.2', 'TLSv1.3']");
+}
+
+#[test]
+pub fn dns_module_test() {
+    let dns = params(r#"dns: { port: 53, name: example.com, type: txt, transport: tcp, answers: "v=spf1 -all",
+                               max_ttl: 300 }"#);
+    assert_eq!(module("dns").check_args(&dns), Ok(()));
+    assert_eq!(module("dns").protocol(&dns), "dns/tcp");
+    let txt = record("TXT", 300, r#""v=spf1" " -all""#);
+    assert_eq!(module("dns").check_response(&dns, &dns_response("NOERROR", vec![txt.clone()])), Ok(()));
+    let other = dns_response("NOERROR", vec![record("TXT", 300, "\"x\"")]);
+    assert_eq!(module("dns").check_response(&dns, &other),
+               Err("Missing TXT record 'v=spf1 -all' for 'example.com'; found '\"x\"'.".to_string()));
+    assert_eq!(module("dns").check_response(&dns, &dns_response("SERVFAIL", vec![txt])),
+               Err("Unexpected rcode 'SERVFAIL' for 'example.com'; expected 'NOERROR'.".to_string()));
+
+    let a = params("dns: { port: 53, name: www.example.com, answers: [192.0.2.1], min_ttl: 60 }");
+    let short_lived = dns_response("NOERROR", vec![record("A", 30, "192.0.2.1")]);
+    assert_eq!(module("dns").check_response(&a, &short_lived),
+               Err("TTL 30 of A record '192.0.2.1' is less than 60.".to_string()));
+
+    assert_eq!(check_args_err("dns", "dns: { port: 53 }"), "'name' is not a domain name");
+    assert_eq!(check_args_err("dns", "dns: { port: 53, name: example.com, min_ttl: 10, max_ttl: 5 }"),
+               "'min_ttl': '10' is greater than max_ttl");
+}
+
+#[test]
+pub fn dns_udp_module_test() {
+    let probe = params("dns_udp: { port: 53, transport: tcp }");
+    assert_eq!(module("dns_udp").check_args(&probe), Ok(()));
+    assert_eq!(module("dns_udp").protocol(&probe), "dns/udp");
+    match module("dns_udp").challenge(&probe) {
+        Payload::Dns(query) => assert_eq!(query, DnsQuery::new(".", RecordType::Ns)),
+        payload => panic!("unexpected payload {:?}", payload),
+    }
+    // Without a name, any well-formed answer shows that a server is listening
+    assert_eq!(module("dns_udp").check_response(&probe, &dns_response("REFUSED", Vec::new())), Ok(()));
+
+    let named = params("dns_udp: { port: 53, name: example.com }");
+    assert!(module("dns_udp").check_response(&named, &dns_response("REFUSED", Vec::new())).is_err());
+}
+
+#[test]
+pub fn unexpected_response_test() {
+    let ssh = params(r#"ssh: { port: 22, version: 2.0, software: ".*" }"#);
+    assert_eq!(module("ssh").check_response(&ssh, &dns_response("NOERROR", Vec::new())),
+               Err("Unexpected response: received a DNS response.".to_string()));
+}
+
+/// Starts a server that greets every client and keeps the connection open until the client closes
+/// it; returns its port.
+fn greeting_server(greeting: &'static str) -> u16 {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            let mut stream = stream.unwrap();
+            thread::spawn(move || {
+                let _ = stream.write_all(greeting.as_bytes());
+                let _ = stream.read_to_end(&mut Vec::new());
+            });
+        }
+    });
+
+    port
+}
+
+#[test]
+pub fn run_native_modules_test() {
+    let ssh_port = greeting_server("SSH-2.0-OpenSSH_7.4\r\n");
+    let ftp_port = greeting_server("220 Welcome\r\n");
+    let closed_port = {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        listener.local_addr().unwrap().port()
+    };
+    let yaml = format!(r#"
+- inventory:
+    local: [127.0.0.1]
+
+- hosts: local
+  properties:
+    - name: SSH
+      ssh: {{ port: {}, version: 2.0, software: "OpenSSH.*" }}
+    - name: FTP
+      ftp: {{ port: {}, response_code: 230 }}
+    - name: Closed
+      connect_tcp: {{ port: {} }}
+"#,
+                       ssh_port,
+                       ftp_port,
+                       closed_port);
+    let check_suite = CheckSuite::read_from_string(&yaml).unwrap();
+    assert!(engine::validate(&check_suite).is_empty());
+
+    let check_suite_result = engine::run(&check_suite);
+    let results = &check_suite_result.results[0].results;
+    assert!(results[0].result.is_ok());
+    assert_eq!(results[0].response, Some("SSH-2.0-OpenSSH_7.4\r\n".to_string()));
+    match results[1].result {
+        Err(PropertyError::FailedResponseCheck(ref reason)) => {
+            assert_eq!(reason, "Unexpected response code '220'; expected '230'.")
+        }
+        ref other => panic!("unexpected result {:?}", other),
+    }
+    match results[2].result {
+        Err(PropertyError::Closed(..)) => {}
+        ref other => panic!("unexpected result {:?}", other),
+    }
+}
+
+static PYTHON_ONLY_YAML: &'static str = r#"
+- inventory:
+    local: [127.0.0.1]
+
+- hosts: local
+  properties:
+    - name: SSH
+      ssh: { port: 22, version: 2.0, software: "OpenSSH.*" }
+    - name: IMAP
+      imap: { port: 143 }
+"#;
+
+#[test]
+pub fn check_module_support_test() {
+    let check_suite = CheckSuite::read_from_string(PYTHON_ONLY_YAML).unwrap();
+    let errors = engine::check_module_support(&check_suite);
+    if cfg!(feature = "python") {
+        assert!(errors.is_empty());
+    } else {
+        assert_eq!(errors.len(), 1);
+        assert!(errors[0].reason.starts_with("property 'IMAP' [imap]: module 'imap' is not a native module"));
+        assert_eq!(errors[0].line, Some(9));
+    }
+}
+
+#[test]
+#[cfg(not(feature = "python"))]
+pub fn run_without_python_test() {
+    let check_suite = CheckSuite::read_from_string(PYTHON_ONLY_YAML).unwrap();
+    let errors = engine::validate(&check_suite);
+    assert_eq!(errors.len(), 1);
+    assert!(errors[0].reason.contains("without the 'python' feature"));
+    assert_eq!(errors[0].line, Some(9));
+
+    let check_suite_result = engine::run(&check_suite);
+    match check_suite_result.results[0].results[1].result {
+        Err(PropertyError::FailedExecution(_, ref reason)) => assert!(reason.contains("needs Python")),
+        ref other => panic!("unexpected result {:?}", other),
+    }
+}
+
+/// A custom module asking a license server for its status.
+struct License;
+
+impl CheckModule for License {
+    fn check_args(&self, params: &Params) -> Result<(), ArgumentError> {
+        let args = Args::new(params);
+        try!(args.only(&["port", "status"]));
+        try!(args.port());
+        try!(args.required_string("status"));
+        Ok(())
+    }
+
+    fn protocol(&self, _: &Params) -> String {
+        "text/tcp".to_string()
+    }
+
+    fn challenge(&self, _: &Params) -> Payload {
+        Payload::Text("STATUS\n".to_string())
+    }
+
+    fn check_response(&self, params: &Params, response: &Response) -> Result<(), String> {
+        let expected = Args::new(params).required_string("status").unwrap();
+        match response.text().map(|text| text.trim()) {
+            Some(status) if status == expected => Ok(()),
+            status => Err(format!("Unexpected status '{}'.", status.unwrap_or(""))),
+        }
+    }
+}
+
+/// Answers every line with `reply`.
+fn license_server(reply: &'static str) -> u16 {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            let mut stream = stream.unwrap();
+            let mut line = [0u8; 7];
+            if stream.read_exact(&mut line).is_ok() && &line == b"STATUS\n" {
+                let _ = stream.write_all(reply.as_bytes());
+            }
+        }
+    });
+
+    port
+}
+
+#[test]
+pub fn registry_test() {
+    let mut registry = Registry::new();
+    assert_eq!(registry.names(), BUILTIN_MODULES.to_vec());
+    assert!(Registry::empty().names().is_empty());
+
+    registry.register("license", License).register("ssh", License);
+    assert!(registry.contains("license"));
+    let ssh = registry.get("ssh").unwrap();
+    assert_eq!(ssh.protocol(&Params::new()), "text/tcp");
+}
+
+#[test]
+pub fn engine_runs_registered_module_test() {
+    let port = license_server("READY\n");
+    let yaml = format!(r#"
+- inventory:
+    local: [127.0.0.1]
+
+- hosts: local
+  properties:
+    - name: Ready
+      license: {{ port: {0}, status: READY }}
+    - name: Draining
+      license: {{ port: {0}, status: DRAINING }}
+"#,
+                       port);
+    let check_suite = CheckSuite::read_from_string(&yaml).unwrap();
+
+    let mut engine = Engine::new();
+    engine.register("license", License);
+    assert!(engine.validate(&check_suite).is_empty());
+    assert!(engine.check_module_support(&check_suite).is_empty());
+
+    let check_suite_result = engine.run(&check_suite);
+    let results = &check_suite_result.results[0].results;
+    assert!(results[0].result.is_ok());
+    match results[1].result {
+        Err(PropertyError::FailedResponseCheck(ref reason)) => {
+            assert_eq!(reason, "Unexpected status 'READY'.")
+        }
+        ref other => panic!("unexpected result {:?}", other),
+    }
+
+    let invalid = CheckSuite::read_from_string(&yaml.replace("status: READY", "state: READY")).unwrap();
+    let errors = engine.validate(&invalid);
+    assert_eq!(errors.len(), 1);
+    assert_eq!(errors[0].reason,
+               "property 'Ready' [license]: invalid params: 'state': 'READY' is not a known param");
+    assert_eq!(errors[0].line, Some(7));
+}
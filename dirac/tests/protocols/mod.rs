@@ -0,0 +1,965 @@
+use dirac::protocols::*;
+use openssl::ssl::{NameType, SslAcceptor, SslAcceptorBuilder, SslFiletype, SslMethod};
+use openssl::x509::X509;
+use std::fs::File;
+use std::io::BufReader;
+use std::io::prelude::*;
+use std::net::{SocketAddr, TcpListener, TcpStream, UdpSocket};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+static TLS_FIXTURES: &'static str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/tls");
+
+/// Starts a tiny HTTP server and returns its port.
+fn http_server() -> u16 {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            let mut stream = stream.unwrap();
+            // Connections closed without a request are ignored.
+            let request = match read_request(&mut stream) {
+                Some(request) => request,
+                None => continue,
+            };
+            let response = match request.lines().next().unwrap_or("") {
+                "GET /old HTTP/1.1" => "HTTP/1.1 301 Moved Permanently\r\nLocation: /new\r\n".to_string(),
+                "POST /form HTTP/1.1" => "HTTP/1.1 303 See Other\r\nLocation: /new\r\n".to_string(),
+                "GET /new HTTP/1.1" => "HTTP/1.1 200 OK\r\nX-Test: yes\r\n".to_string(),
+                "POST /echo HTTP/1.1" => format!("HTTP/1.1 200 OK\r\nX-Echo: {}\r\n", request.len()),
+                _ => "HTTP/1.1 404 Not Found\r\n".to_string(),
+            };
+            let body = if response.contains("X-Echo") { request.clone() } else { "hello".to_string() };
+            let _ = write!(stream,
+                           "{}Content-Length: {}\r\nConnection: close\r\n\r\n{}",
+                           response,
+                           body.len(),
+                           body);
+        }
+    });
+
+    port
+}
+
+fn tls_acceptor() -> SslAcceptorBuilder {
+    let mut acceptor = SslAcceptor::mozilla_intermediate(SslMethod::tls()).unwrap();
+    acceptor.set_private_key_file(format!("{}/server.key", TLS_FIXTURES), SslFiletype::PEM).unwrap();
+    acceptor.set_certificate_file(format!("{}/server.pem", TLS_FIXTURES), SslFiletype::PEM).unwrap();
+    let mut ca = Vec::new();
+    File::open(format!("{}/ca.pem", TLS_FIXTURES)).unwrap().read_to_end(&mut ca).unwrap();
+    acceptor.add_extra_chain_cert(X509::from_pem(&ca).unwrap()).unwrap();
+    acceptor
+}
+
+/// Starts a tiny HTTPS server presenting a certificate for `localhost` signed by the test CA;
+/// returns its port and the server name the latest client has asked for.
+fn https_server() -> (u16, Arc<Mutex<Option<String>>>) {
+    let server_name = Arc::new(Mutex::new(None));
+    let mut acceptor = tls_acceptor();
+    let sni = server_name.clone();
+    acceptor.set_servername_callback(move |ssl, _| {
+        *sni.lock().unwrap() = ssl.servername(NameType::HOST_NAME).map(|s| s.to_string());
+        Ok(())
+    });
+    let acceptor = Arc::new(acceptor.build());
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            let mut stream = match acceptor.accept(stream.unwrap()) {
+                Ok(stream) => stream,
+                Err(_) => continue,
+            };
+            if read_request(&mut stream).is_some() {
+                let _ = write!(stream, "HTTP/1.1 200 OK\r\nContent-Length: 5\r\nConnection: close\r\n\r\nhello");
+            }
+        }
+    });
+
+    (port, server_name)
+}
+
+/// Starts an HTTPS server that redirects a single request to `location`; returns its port.
+fn https_redirect_server(location: String) -> u16 {
+    let acceptor = tls_acceptor().build();
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+    thread::spawn(move || {
+        let mut stream = acceptor.accept(listener.accept().unwrap().0).unwrap();
+        if read_request(&mut stream).is_some() {
+            let _ = write!(stream,
+                           "HTTP/1.1 302 Found\r\nLocation: {}\r\nContent-Length: 0\r\n\
+                            Connection: close\r\n\r\n",
+                           location);
+        }
+    });
+
+    port
+}
+
+/// Starts a TLS server that greets with a banner and echoes what it receives; returns its port
+/// and the server name the client has asked for.
+fn tls_banner_server() -> (u16, Arc<Mutex<Option<String>>>) {
+    let server_name = Arc::new(Mutex::new(None));
+    let mut acceptor = tls_acceptor();
+    let sni = server_name.clone();
+    acceptor.set_servername_callback(move |ssl, _| {
+        *sni.lock().unwrap() = ssl.servername(NameType::HOST_NAME).map(|s| s.to_string());
+        Ok(())
+    });
+    let acceptor = acceptor.build();
+
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+    thread::spawn(move || {
+        let mut stream = acceptor.accept(listener.accept().unwrap().0).unwrap();
+        let _ = stream.write_all(b"* OK IMAP4 ready\r\n");
+        let mut buf = [0u8; 1024];
+        let len = stream.read(&mut buf).unwrap_or(0);
+        let _ = stream.write_all(&buf[..len]);
+    });
+
+    (port, server_name)
+}
+
+/// Starts a server that runs `preamble` in plaintext, then upgrades to TLS and answers `QUIT`.
+fn starttls_server(preamble: fn(&mut BufReader<TcpStream>, &mut TcpStream) -> bool) -> u16 {
+    let acceptor = tls_acceptor().build();
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+    thread::spawn(move || {
+        let mut stream = listener.accept().unwrap().0;
+        let mut reader = BufReader::new(stream.try_clone().unwrap());
+        if !preamble(&mut reader, &mut stream) {
+            return;
+        }
+        let mut stream = acceptor.accept(stream).unwrap();
+        let mut line = [0u8; 6];
+        let _ = stream.read_exact(&mut line);
+        let _ = stream.write_all(b"221 Bye\r\n");
+    });
+
+    port
+}
+
+fn smtp_preamble(reader: &mut BufReader<TcpStream>, stream: &mut TcpStream) -> bool {
+    let mut line = String::new();
+    stream.write_all(b"220-mail.local ESMTP\r\n220 ready\r\n").unwrap();
+    reader.read_line(&mut line).unwrap();
+    assert_eq!(line, "EHLO dirac\r\n");
+    stream.write_all(b"250-mail.local\r\n250-PIPELINING\r\n250 STARTTLS\r\n").unwrap();
+    line.clear();
+    reader.read_line(&mut line).unwrap();
+    assert_eq!(line, "STARTTLS\r\n");
+    stream.write_all(b"220 Go ahead\r\n").unwrap();
+    true
+}
+
+fn smtp_without_starttls_preamble(reader: &mut BufReader<TcpStream>, stream: &mut TcpStream) -> bool {
+    let mut line = String::new();
+    stream.write_all(b"220 mail.local ESMTP\r\n").unwrap();
+    reader.read_line(&mut line).unwrap();
+    stream.write_all(b"250-mail.local\r\n250 PIPELINING\r\n").unwrap();
+    false
+}
+
+fn postgres_preamble(reader: &mut BufReader<TcpStream>, stream: &mut TcpStream) -> bool {
+    let mut request = [0u8; 8];
+    reader.read_exact(&mut request).unwrap();
+    assert_eq!(request, [0, 0, 0, 8, 0x04, 0xd2, 0x16, 0x2f]);
+    stream.write_all(b"S").unwrap();
+    true
+}
+
+fn smtp_multibyte_capability_preamble(reader: &mut BufReader<TcpStream>, stream: &mut TcpStream) -> bool {
+    let mut line = String::new();
+    stream.write_all(b"220 mail.local ESMTP\r\n").unwrap();
+    reader.read_line(&mut line).unwrap();
+    stream.write_all("250-mail.local\r\n2\u{e9}\u{20ac}\r\n".as_bytes()).unwrap();
+    false
+}
+
+/// Sends a banner without line break one byte at a time until the client gives up.
+fn dripping_banner_preamble(_: &mut BufReader<TcpStream>, stream: &mut TcpStream) -> bool {
+    while stream.write_all(b"2").is_ok() {
+        thread::sleep(Duration::from_millis(100));
+    }
+    false
+}
+
+fn silent_preamble(_: &mut BufReader<TcpStream>, _: &mut TcpStream) -> bool {
+    thread::sleep(Duration::from_millis(1500));
+    false
+}
+
+fn endless_banner_preamble(_: &mut BufReader<TcpStream>, stream: &mut TcpStream) -> bool {
+    let _ = stream.write_all(&[b'2'; 128 * 1024]);
+    false
+}
+
+fn starttls(port: u16, service: StartTlsService, payload: Option<&str>) -> Result<StartTlsResponse, String> {
+    let mut p = StartTlsText::new("localhost", port);
+    p.set_data(StartTlsData {
+        service: service,
+        server_name: None,
+        payload: payload.map(|p| p.to_string()),
+    });
+    p.send_challenge().map(|StartTlsTextResponse(response)| response).map_err(|err| err.to_string())
+}
+
+/// Starts an SMTP-like server that answers `EHLO` with its capabilities in two segments.
+fn smtp_server() -> u16 {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+    thread::spawn(move || {
+        let mut stream = listener.accept().unwrap().0;
+        let mut reader = BufReader::new(stream.try_clone().unwrap());
+        stream.write_all(b"220 mail.local ESMTP\r\n").unwrap();
+        let mut line = String::new();
+        while reader.read_line(&mut line).unwrap_or(0) > 0 {
+            if line.starts_with("EHLO") {
+                stream.write_all(b"250-mail.local\r\n250-PIPELINING\r\n").unwrap();
+                thread::sleep(Duration::from_millis(50));
+                stream.write_all(b"250 STARTTLS\r\n").unwrap();
+            } else if line.starts_with("QUIT") {
+                stream.write_all(b"221 Bye\r\n").unwrap();
+                return;
+            }
+            line.clear();
+        }
+    });
+
+    port
+}
+
+fn step(send: Option<&str>, expect: Option<&str>) -> DialogStep {
+    DialogStep {
+        send: send.map(|s| s.to_string()),
+        expect: expect.map(|s| s.to_string()),
+        timeout: None,
+    }
+}
+
+fn dialog(port: u16, steps: Vec<DialogStep>) -> Result<Transcript, ::std::io::Error> {
+    let mut p = TcpDialog::new("127.0.0.1", port);
+    p.set_data(steps);
+    p.send_challenge().map(|TcpDialogResponse(transcript)| transcript)
+}
+
+/// Starts a server that writes `segments` with a short pause in between and then closes the
+/// connection if `close` is set.
+fn segmented_server(segments: Vec<&'static [u8]>, close: bool) -> u16 {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+    thread::spawn(move || {
+        let mut stream = listener.accept().unwrap().0;
+        for segment in segments {
+            stream.write_all(segment).unwrap();
+            stream.flush().unwrap();
+            thread::sleep(Duration::from_millis(50));
+        }
+        if !close {
+            thread::sleep(Duration::from_secs(2));
+        }
+    });
+
+    port
+}
+
+fn read_text(port: u16, until: ReadUntil, max_size: usize) -> Result<String, ::std::io::Error> {
+    let mut p = TcpText::new("127.0.0.1", port);
+    p.set_timeouts(Timeouts {
+        timeout: Duration::from_millis(500),
+        connect_timeout: Duration::from_secs(1),
+        retries: 0,
+    });
+    p.set_read_strategy(ReadStrategy {
+        until: until,
+        max_size: max_size,
+    });
+    p.send_challenge().map(|TcpTextResponse(response)| response)
+}
+
+/// Starts a UDP server answering every datagram but the first `ignore` ones with `reply` and
+/// returns its port and the addresses it has received datagrams from.
+fn udp_server(ignore: usize, reply: &'static str) -> (u16, Arc<Mutex<Vec<SocketAddr>>>) {
+    let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+    let port = socket.local_addr().unwrap().port();
+    let peers = Arc::new(Mutex::new(Vec::new()));
+    let received = peers.clone();
+    thread::spawn(move || {
+        let mut buf = [0u8; 512];
+        loop {
+            let (_, peer) = socket.recv_from(&mut buf).unwrap();
+            let mut received = received.lock().unwrap();
+            received.push(peer);
+            if received.len() > ignore {
+                socket.send_to(reply.as_bytes(), peer).unwrap();
+            }
+        }
+    });
+
+    (port, peers)
+}
+
+fn udp_text(port: u16, timeout_ms: u64, retries: u32) -> Result<String, ::std::io::Error> {
+    let mut p = UdpText::new("127.0.0.1", port);
+    p.set_data("ping".to_string());
+    p.set_timeouts(Timeouts {
+        timeout: Duration::from_millis(timeout_ms),
+        connect_timeout: Duration::from_secs(1),
+        retries: retries,
+    });
+    p.send_challenge().map(|UdpTextResponse(response)| response)
+}
+
+/// Builds the reply to `query` with `flags` and answers `(type, ttl, rdata)` for the queried name.
+fn dns_reply(query: &[u8], flags: u16, answers: &[(u16, u32, Vec<u8>)]) -> Vec<u8> {
+    let mut reply = query[..2].to_vec();
+    reply.extend_from_slice(&[0x80 | (flags >> 8) as u8, flags as u8]);
+    reply.extend_from_slice(&[0, 1, 0, answers.len() as u8, 0, 0, 0, 0]);
+    reply.extend_from_slice(&query[12..]);
+    for &(record_type, ttl, ref rdata) in answers {
+        // The name points to the question's
+        reply.extend_from_slice(&[0xc0, 12, (record_type >> 8) as u8, record_type as u8, 0, 1]);
+        reply.extend_from_slice(&[(ttl >> 24) as u8, (ttl >> 16) as u8, (ttl >> 8) as u8, ttl as u8]);
+        reply.extend_from_slice(&[(rdata.len() >> 8) as u8, rdata.len() as u8]);
+        reply.extend_from_slice(rdata);
+    }
+    reply
+}
+
+/// Starts a DNS server answering queries over UDP with `answer` and returns its port and the
+/// queries it has received.
+fn dns_udp_server(answer: fn(&[u8]) -> Vec<u8>) -> (u16, Arc<Mutex<Vec<Vec<u8>>>>) {
+    let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+    let port = socket.local_addr().unwrap().port();
+    let queries = Arc::new(Mutex::new(Vec::new()));
+    let received = queries.clone();
+    thread::spawn(move || {
+        let mut buf = [0u8; 512];
+        loop {
+            let (len, peer) = socket.recv_from(&mut buf).unwrap();
+            received.lock().unwrap().push(buf[..len].to_vec());
+            socket.send_to(&answer(&buf[..len]), peer).unwrap();
+        }
+    });
+
+    (port, queries)
+}
+
+/// Starts a DNS server answering a single query over TCP with `answer`.
+fn dns_tcp_server(answer: fn(&[u8]) -> Vec<u8>) -> u16 {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+    thread::spawn(move || {
+        let mut stream = listener.accept().unwrap().0;
+        let mut len = [0u8; 2];
+        stream.read_exact(&mut len).unwrap();
+        let mut query = vec![0u8; (len[0] as usize) << 8 | len[1] as usize];
+        stream.read_exact(&mut query).unwrap();
+        let reply = answer(&query);
+        stream.write_all(&[(reply.len() >> 8) as u8, reply.len() as u8]).unwrap();
+        stream.write_all(&reply).unwrap();
+    });
+
+    port
+}
+
+fn dns_udp(port: u16, query: DnsQuery) -> DnsResponse {
+    let mut p = DnsUdp::new("127.0.0.1", port);
+    p.set_data(query);
+    let DnsUdpResponse(response) = p.send_challenge().unwrap();
+    response
+}
+
+fn read_request<R: Read>(stream: &mut R) -> Option<String> {
+    let mut request = Vec::new();
+    let mut buf = [0u8; 1024];
+    loop {
+        let len = stream.read(&mut buf).unwrap_or(0);
+        if len == 0 {
+            return None;
+        }
+        request.extend_from_slice(&buf[..len]);
+        let text = String::from_utf8_lossy(&request).into_owned();
+        if let Some(end) = text.find("\r\n\r\n") {
+            let content_length = text.lines()
+                                     .find(|l| l.to_lowercase().starts_with("content-length:"))
+                                     .and_then(|l| l[15..].trim().parse::<usize>().ok())
+                                     .unwrap_or(0);
+            if request.len() >= end + 4 + content_length {
+                return Some(text);
+            }
+        }
+    }
+}
+
+fn send(port: u16, request: HttpRequest) -> HttpResponse<String> {
+    let mut p = TcpHttp::new("127.0.0.1", port);
+    p.set_data(request);
+    let TcpHttpTextResponse(response) = p.send_challenge().unwrap();
+    response
+}
+
+#[test]
+fn http_returns_redirect_if_not_following() {
+    let port = http_server();
+    let response = send(port, HttpRequest::new("GET", "/old"));
+
+    assert_eq!(response.response_code, 301);
+    assert_eq!(response.headers.get("Location"), Some(&"/new".to_string()));
+    assert!(response.redirects.is_empty());
+}
+
+#[test]
+fn http_follows_redirects() {
+    let port = http_server();
+    let mut request = HttpRequest::new("GET", "/old");
+    request.follow_redirects = true;
+    let response = send(port, request);
+
+    assert_eq!(response.response_code, 200);
+    assert_eq!(response.body, "hello");
+    assert_eq!(response.headers.get("X-Test"), Some(&"yes".to_string()));
+    assert_eq!(response.redirects,
+               vec![HttpRedirect {
+                        response_code: 301,
+                        location: "/new".to_string(),
+                    }]);
+}
+
+#[test]
+fn http_see_other_switches_to_get() {
+    let port = http_server();
+    let mut request = HttpRequest::new("POST", "/form");
+    request.body = Some("a=b".to_string());
+    request.follow_redirects = true;
+    let response = send(port, request);
+
+    assert_eq!(response.response_code, 200);
+    assert_eq!(response.redirects.len(), 1);
+}
+
+#[test]
+fn http_sends_verb_headers_and_body() {
+    let port = http_server();
+    let mut request = HttpRequest::new("post", "/echo");
+    request.headers.push(("X-Token".to_string(), "secret".to_string()));
+    request.body = Some("ping".to_string());
+    let response = send(port, request);
+
+    assert_eq!(response.response_code, 200);
+    assert!(response.body.starts_with("POST /echo HTTP/1.1"));
+    assert!(response.body.contains("X-Token: secret"));
+    assert!(response.body.ends_with("\r\n\r\nping"));
+}
+
+#[test]
+fn http_truncates_body_at_max_size() {
+    let port = http_server();
+    let mut p = TcpHttp::new("127.0.0.1", port);
+    p.set_data(HttpRequest::new("GET", "/new"));
+    p.set_read_strategy(ReadStrategy {
+        until: ReadUntil::Close,
+        max_size: 3,
+    });
+    let TcpHttpTextResponse(response) = p.send_challenge().unwrap();
+
+    assert_eq!(response.body, "hel");
+    assert!(response.body_truncated);
+
+    let response = send(port, HttpRequest::new("GET", "/new"));
+    assert_eq!(response.body, "hello");
+    assert!(!response.body_truncated);
+}
+
+/// Starts an HTTP server announcing a body of `len` bytes and sending one byte every 100 ms.
+fn http_drip_server(len: usize) -> u16 {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+    thread::spawn(move || {
+        let mut stream = listener.accept().unwrap().0;
+        read_request(&mut stream);
+        let _ = write!(stream, "HTTP/1.1 200 OK\r\nContent-Length: {}\r\n\r\n", len);
+        for _ in 0..len {
+            if stream.write_all(b"x").is_err() {
+                break;
+            }
+            thread::sleep(Duration::from_millis(100));
+        }
+    });
+
+    port
+}
+
+#[test]
+fn http_times_out_on_slow_body() {
+    let port = http_drip_server(100);
+    let mut p = TcpHttp::new("127.0.0.1", port);
+    p.set_data(HttpRequest::new("GET", "/"));
+    p.set_timeouts(Timeouts {
+        timeout: Duration::from_millis(500),
+        connect_timeout: Duration::from_secs(1),
+        retries: 0,
+    });
+
+    let start = Instant::now();
+    let err = p.send_challenge().err().unwrap();
+    assert!(is_timeout(&err));
+    assert!(start.elapsed() < Duration::from_secs(2));
+}
+
+#[test]
+fn http_has_no_tls_info() {
+    let port = http_server();
+    let response = send(port, HttpRequest::new("GET", "/new"));
+
+    assert!(response.tls.is_none());
+}
+
+#[test]
+fn https_inspects_certificate_chain() {
+    let (port, _) = https_server();
+    let mut p = TcpHttps::new("localhost", port);
+    p.set_data(HttpRequest::new("GET", "/"));
+    let TcpHttpsTextResponse(response) = p.send_challenge().unwrap();
+
+    assert_eq!(response.response_code, 200);
+    assert_eq!(response.body, "hello");
+    let tls = response.tls.unwrap();
+    assert!(tls.version.starts_with("TLSv1"));
+    assert!(!tls.cipher.is_empty());
+    // The test CA is not among the trusted system CAs
+    assert!(tls.verify_error.is_some());
+    assert_eq!(tls.chain.len(), 2);
+    let cert = &tls.chain[0];
+    assert_eq!(cert.subject, "CN=localhost, O=Dirac");
+    assert_eq!(cert.issuer, "CN=Dirac Test CA, O=Dirac");
+    assert_eq!(cert.sans, vec!["localhost".to_string(), "127.0.0.1".to_string()]);
+    assert_eq!(cert.key_type, "RSA 2048");
+    assert!(cert.days_valid > 3650);
+    assert!(cert.not_after.contains("2126"));
+    assert_eq!(tls.chain[1].subject, "CN=Dirac Test CA, O=Dirac");
+}
+
+#[test]
+fn https_redirect_to_http_has_no_tls_info() {
+    let http_port = http_server();
+    let port = https_redirect_server(format!("http://localhost:{}/new", http_port));
+    let mut request = HttpRequest::new("GET", "/");
+    request.follow_redirects = true;
+    let mut p = TcpHttps::new("localhost", port);
+    p.set_data(request);
+    let TcpHttpsTextResponse(response) = p.send_challenge().unwrap();
+
+    assert_eq!(response.response_code, 200);
+    assert_eq!(response.redirects.len(), 1);
+    assert!(response.tls.is_none());
+}
+
+#[test]
+fn http_sends_server_name_as_host() {
+    let port = http_server();
+    let mut request = HttpRequest::new("POST", "/echo");
+    request.server_name = Some("www.dirac.test".to_string());
+    let response = send(port, request);
+
+    assert_eq!(response.response_code, 200);
+    assert!(response.body.contains(&format!("Host: www.dirac.test:{}", port)));
+}
+
+#[test]
+fn https_sends_server_name_via_sni() {
+    let (port, server_name) = https_server();
+    let mut p = TcpHttps::new("127.0.0.1", port);
+    let mut request = HttpRequest::new("GET", "/");
+    request.server_name = Some("localhost".to_string());
+    p.set_data(request);
+    let TcpHttpsTextResponse(response) = p.send_challenge().unwrap();
+
+    assert_eq!(response.response_code, 200);
+    assert_eq!(*server_name.lock().unwrap(), Some("localhost".to_string()));
+}
+
+#[test]
+fn tls_text_reads_banner_and_sends_server_name() {
+    let (port, server_name) = tls_banner_server();
+    let mut p = TlsText::new("127.0.0.1", port);
+    p.set_data(TlsData {
+        server_name: Some("localhost".to_string()),
+        payload: None,
+    });
+    let TlsTextResponse(response) = p.send_challenge().unwrap();
+
+    assert_eq!(response.response, "* OK IMAP4 ready\r\n");
+    assert_eq!(response.tls.chain[0].subject, "CN=localhost, O=Dirac");
+    assert_eq!(*server_name.lock().unwrap(), Some("localhost".to_string()));
+}
+
+#[test]
+fn tls_raw_without_server_name_sends_no_sni_for_addresses() {
+    let (port, server_name) = tls_banner_server();
+    let mut p = TlsRaw::new("127.0.0.1", port);
+    p.set_data(TlsData {
+        server_name: None,
+        payload: None,
+    });
+    let TlsRawResponse(response) = p.send_challenge().unwrap();
+
+    assert_eq!(response.response, b"* OK IMAP4 ready\r\n".to_vec());
+    assert_eq!(*server_name.lock().unwrap(), None);
+}
+
+#[test]
+fn starttls_smtp_upgrades_connection() {
+    let port = starttls_server(smtp_preamble);
+    let response = starttls(port, StartTlsService::Smtp, Some("QUIT\r\n")).unwrap();
+
+    assert_eq!(response.banner, "220-mail.local ESMTP\r\n220 ready\r\n");
+    assert_eq!(response.response, "221 Bye\r\n");
+    assert_eq!(response.tls.chain[0].subject, "CN=localhost, O=Dirac");
+}
+
+#[test]
+fn starttls_smtp_fails_if_not_offered() {
+    let port = starttls_server(smtp_without_starttls_preamble);
+    let err = starttls(port, StartTlsService::Smtp, None).err().unwrap();
+
+    assert_eq!(err, "smtp server does not offer STARTTLS: '250-mail.local\r\n250 PIPELINING'");
+}
+
+#[test]
+fn starttls_smtp_accepts_multibyte_capabilities() {
+    let port = starttls_server(smtp_multibyte_capability_preamble);
+    let err = starttls(port, StartTlsService::Smtp, None).err().unwrap();
+
+    assert!(err.starts_with("smtp server does not offer STARTTLS"), "{}", err);
+}
+
+#[test]
+fn starttls_times_out_on_slow_banner() {
+    let port = starttls_server(dripping_banner_preamble);
+    let start = Instant::now();
+    let err = starttls(port, StartTlsService::Smtp, None).err().unwrap();
+
+    assert_eq!(err, "timed out during STARTTLS");
+    assert!(start.elapsed() < Duration::from_millis(1500));
+}
+
+#[test]
+fn starttls_times_out_on_silent_server() {
+    let port = starttls_server(silent_preamble);
+    let start = Instant::now();
+    let err = starttls(port, StartTlsService::Smtp, None).err().unwrap();
+
+    assert_eq!(err, "timed out during STARTTLS");
+    assert!(start.elapsed() < Duration::from_millis(1500));
+}
+
+#[test]
+fn starttls_limits_banner_size() {
+    let port = starttls_server(endless_banner_preamble);
+    let err = starttls(port, StartTlsService::Smtp, None).err().unwrap();
+
+    assert_eq!(err, format!("received more than {} bytes during STARTTLS", DEFAULT_MAX_SIZE));
+}
+
+#[test]
+fn starttls_postgres_upgrades_connection() {
+    let port = starttls_server(postgres_preamble);
+    let response = starttls(port, StartTlsService::Postgres, None).unwrap();
+
+    assert_eq!(response.banner, "");
+    assert_eq!(response.response, "");
+    assert!(response.tls.version.starts_with("TLSv1"));
+}
+
+#[test]
+fn dialog_runs_all_steps() {
+    let port = smtp_server();
+    let Transcript(steps) = dialog(port,
+                                   vec![step(None, Some("^220 ")),
+                                        step(Some("EHLO dirac\r\n"), Some("(?m)^250 .*\r\n")),
+                                        step(Some("QUIT\r\n"), Some("^221"))])
+                                .unwrap();
+
+    assert_eq!(steps.len(), 3);
+    assert!(steps.iter().all(|s| s.matched == Some(true)));
+    assert_eq!(steps[1].received, "250-mail.local\r\n250-PIPELINING\r\n250 STARTTLS\r\n");
+    assert_eq!(steps[2].sent, Some("QUIT\r\n".to_string()));
+}
+
+#[test]
+fn dialog_stops_at_first_mismatch() {
+    let port = smtp_server();
+    let mut mismatch = step(Some("EHLO dirac\r\n"), Some("(?m)^250[- ]AUTH"));
+    mismatch.timeout = Some(Duration::from_millis(200));
+    let transcript = dialog(port,
+                            vec![step(None, Some("^220 ")), mismatch, step(Some("QUIT\r\n"), Some("^221"))])
+                         .unwrap();
+
+    assert_eq!(transcript.0.len(), 2);
+    assert_eq!(transcript.0[1].matched, Some(false));
+    assert_eq!(transcript.to_string(),
+               "< 220 mail.local ESMTP\n> EHLO dirac\n< 250-mail.local\n< 250-PIPELINING\n< 250 STARTTLS\n");
+}
+
+#[test]
+fn dialog_keeps_data_following_a_match() {
+    let port = segmented_server(vec![b"220 ready\r\nlogin: ", b"password: "], false);
+    let Transcript(steps) = dialog(port,
+                                   vec![step(None, Some("^220 ")),
+                                        step(None, Some("^login: $")),
+                                        step(Some("dirac\r\n"), Some("password: "))])
+                                .unwrap();
+
+    assert!(steps.iter().all(|s| s.matched == Some(true)));
+    assert_eq!(steps[0].received, "220 ready\r\n");
+    assert_eq!(steps[1].received, "login: ");
+    assert_eq!(steps[2].received, "password: ");
+}
+
+#[test]
+fn dialog_stops_reading_at_max_size() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+    thread::spawn(move || {
+        let mut stream = listener.accept().unwrap().0;
+        let chunk = [b'x'; 1024];
+        while stream.write_all(&chunk).is_ok() {}
+    });
+    let mut never = step(None, Some("y"));
+    never.timeout = Some(Duration::from_secs(5));
+    let start = Instant::now();
+    let Transcript(steps) = dialog(port, vec![never]).unwrap();
+
+    assert_eq!(steps[0].matched, Some(false));
+    assert_eq!(steps[0].received.len(), DEFAULT_MAX_SIZE);
+    assert!(start.elapsed() < Duration::from_secs(5));
+}
+
+#[test]
+fn dialog_rejects_invalid_regex() {
+    let port = smtp_server();
+    let err = dialog(port, vec![step(None, Some("(220"))]).err().unwrap();
+
+    assert_eq!(err.kind(), ::std::io::ErrorKind::InvalidInput);
+}
+
+#[test]
+fn read_first_chunk_by_default() {
+    let port = segmented_server(vec![b"220-mail.local\r\n", b"220 ready\r\n"], true);
+    let p = TcpText::new("127.0.0.1", port);
+    let TcpTextResponse(response) = p.send_challenge().unwrap();
+
+    assert_eq!(response, "220-mail.local\r\n");
+}
+
+#[test]
+fn read_until_delimiter_across_segments() {
+    let port = segmented_server(vec![b"SSH-2.0-", b"OpenSSH_7.4\r", b"\n"], false);
+    let response = read_text(port, ReadUntil::Delimiter(b"\r\n".to_vec()), DEFAULT_MAX_SIZE).unwrap();
+
+    assert_eq!(response, "SSH-2.0-OpenSSH_7.4\r\n");
+}
+
+#[test]
+fn read_until_bytes() {
+    let port = segmented_server(vec![b"ab", b"cd", b"ef"], false);
+    let response = read_text(port, ReadUntil::Bytes(5), DEFAULT_MAX_SIZE).unwrap();
+
+    assert_eq!(response, "abcde");
+}
+
+#[test]
+fn read_until_idle() {
+    let port = segmented_server(vec![b"220-mail.local\r\n", b"220 ready\r\n"], false);
+    let response = read_text(port, ReadUntil::Idle(Duration::from_millis(200)), DEFAULT_MAX_SIZE).unwrap();
+
+    assert_eq!(response, "220-mail.local\r\n220 ready\r\n");
+}
+
+#[test]
+fn read_until_close() {
+    let port = segmented_server(vec![b"220 ready\r\n", b"221 Bye\r\n"], true);
+    let response = read_text(port, ReadUntil::Close, DEFAULT_MAX_SIZE).unwrap();
+
+    assert_eq!(response, "220 ready\r\n221 Bye\r\n");
+}
+
+#[test]
+fn read_stops_at_max_size() {
+    let port = segmented_server(vec![b"0123", b"4567"], false);
+    let response = read_text(port, ReadUntil::Close, 6).unwrap();
+
+    assert_eq!(response, "012345");
+}
+
+#[test]
+fn read_times_out_without_delimiter() {
+    let port = segmented_server(vec![b"no newline"], false);
+    let err = read_text(port, ReadUntil::Delimiter(b"\n".to_vec()), DEFAULT_MAX_SIZE).err().unwrap();
+
+    assert_eq!(err.kind(), ::std::io::ErrorKind::TimedOut);
+    assert_eq!(err.to_string(), "received 10 bytes while waiting for delimiter \"\\n\"");
+}
+
+#[test]
+fn read_fails_if_closed_before_complete() {
+    let port = segmented_server(vec![b"abc"], true);
+    let err = read_text(port, ReadUntil::Bytes(5), DEFAULT_MAX_SIZE).err().unwrap();
+
+    assert_eq!(err.kind(), ::std::io::ErrorKind::UnexpectedEof);
+}
+
+#[test]
+fn udp_text_sends_from_ephemeral_ports() {
+    let (port, peers) = udp_server(0, "pong");
+    let checks: Vec<_> = (0..2).map(|_| thread::spawn(move || udp_text(port, 500, 0))).collect();
+    for check in checks {
+        assert_eq!(check.join().unwrap().unwrap(), "pong");
+    }
+
+    let peers = peers.lock().unwrap();
+    assert_eq!(peers.len(), 2);
+    assert!(peers[0].port() != peers[1].port());
+}
+
+#[test]
+fn udp_text_ignores_datagrams_from_other_peers() {
+    let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+    let port = socket.local_addr().unwrap().port();
+    thread::spawn(move || {
+        let mut buf = [0u8; 512];
+        let (_, peer) = socket.recv_from(&mut buf).unwrap();
+        let intruder = UdpSocket::bind("127.0.0.1:0").unwrap();
+        intruder.send_to(b"spoofed", peer).unwrap();
+        thread::sleep(Duration::from_millis(50));
+        socket.send_to(b"pong", peer).unwrap();
+    });
+
+    assert_eq!(udp_text(port, 500, 0).unwrap(), "pong");
+}
+
+#[test]
+fn udp_text_retransmits_on_timeout() {
+    let (port, peers) = udp_server(2, "pong");
+
+    assert_eq!(udp_text(port, 50, 2).unwrap(), "pong");
+    assert_eq!(peers.lock().unwrap().len(), 3);
+}
+
+#[test]
+fn udp_text_times_out_after_retries() {
+    let (port, peers) = udp_server(3, "pong");
+    let err = udp_text(port, 50, 1).err().unwrap();
+
+    assert_eq!(err.kind(), ::std::io::ErrorKind::TimedOut);
+    assert_eq!(peers.lock().unwrap().len(), 2);
+}
+
+#[test]
+fn udp_text_reports_closed_port() {
+    let port = UdpSocket::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port();
+    let err = udp_text(port, 500, 0).err().unwrap();
+
+    assert_eq!(err.kind(), ::std::io::ErrorKind::ConnectionRefused);
+    assert_eq!(err.to_string(), "port unreachable");
+}
+
+#[test]
+fn dns_udp_resolves_a_record() {
+    let (port, queries) = dns_udp_server(|query| {
+        dns_reply(query, 0x0500, &[(1, 300, vec![192, 0, 2, 1])])
+    });
+    let response = dns_udp(port, DnsQuery::new("example.com", RecordType::A));
+
+    assert_eq!(response.rcode, "NOERROR");
+    assert!(response.authoritative);
+    assert_eq!(response.answers,
+               vec![DnsRecord {
+                        name: "example.com.".to_string(),
+                        record_type: "A".to_string(),
+                        ttl: 300,
+                        data: "192.0.2.1".to_string(),
+                    }]);
+
+    let query = &queries.lock().unwrap()[0];
+    assert_eq!(query[2] & 0x01, 0x01, "recursion desired");
+    assert_eq!(&query[12..], b"\x07example\x03com\x00\x00\x01\x00\x01");
+}
+
+#[test]
+fn dns_udp_reports_nxdomain() {
+    let (port, _) = dns_udp_server(|query| dns_reply(query, 0x0083, &[]));
+    let mut query = DnsQuery::new("missing.example.com", RecordType::Aaaa);
+    query.recursion_desired = false;
+    let response = dns_udp(port, query);
+
+    assert_eq!(response.rcode, "NXDOMAIN");
+    assert!(!response.authoritative);
+    assert!(response.recursion_available);
+    assert!(response.answers.is_empty());
+}
+
+#[test]
+fn dns_udp_skips_replies_with_another_id() {
+    let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+    let port = socket.local_addr().unwrap().port();
+    thread::spawn(move || {
+        let mut buf = [0u8; 512];
+        let (len, peer) = socket.recv_from(&mut buf).unwrap();
+        let mut stale = dns_reply(&buf[..len], 0x0083, &[]);
+        stale[0] ^= 0xff;
+        socket.send_to(&stale, peer).unwrap();
+        socket.send_to(&dns_reply(&buf[..len], 0x0080, &[]), peer).unwrap();
+    });
+    let response = dns_udp(port, DnsQuery::new("example.com", RecordType::A));
+
+    assert_eq!(response.rcode, "NOERROR");
+}
+
+#[test]
+fn dns_udp_looks_up_reverse_names() {
+    let (port, queries) = dns_udp_server(|query| {
+        dns_reply(query, 0x0100, &[(12, 60, b"\x04host\x07example\x03com\x00".to_vec())])
+    });
+    let response = dns_udp(port, DnsQuery::new("192.0.2.1", RecordType::Ptr));
+
+    assert_eq!(response.answers[0].data, "host.example.com.");
+    assert_eq!(response.answers[0].name, "1.2.0.192.in-addr.arpa.");
+    assert_eq!(&queries.lock().unwrap()[0][12..],
+               b"\x011\x012\x010\x03192\x07in-addr\x04arpa\x00\x00\x0c\x00\x01");
+}
+
+#[test]
+fn dns_udp_decodes_txt_and_soa_records() {
+    let (port, _) = dns_udp_server(|query| {
+        let mut soa = b"\x03ns1\xc0\x0c\x0ahostmaster\xc0\x0c".to_vec();
+        soa.extend_from_slice(&[0, 0, 0, 42, 0, 0, 14, 16, 0, 0, 3, 132, 0, 9, 58, 128, 0, 0, 1, 44]);
+        dns_reply(query, 0x0500, &[(16, 300, b"\x0bv=spf1 -all\x02ok".to_vec()), (6, 3600, soa)])
+    });
+    let response = dns_udp(port, DnsQuery::new("example.com", RecordType::Txt));
+
+    assert_eq!(response.answers[0].data, "\"v=spf1 -all\" \"ok\"");
+    assert_eq!(response.answers[1].record_type, "SOA");
+    assert_eq!(response.answers[1].data,
+               "ns1.example.com. hostmaster.example.com. 42 3600 900 604800 300");
+}
+
+#[test]
+fn dns_tcp_resolves_mx_record() {
+    let port = dns_tcp_server(|query| {
+        dns_reply(query, 0x0580, &[(15, 3600, b"\x00\x0a\x04mail\xc0\x0c".to_vec())])
+    });
+    let mut p = DnsTcp::new("127.0.0.1", port);
+    p.set_data(DnsQuery::new("example.com", RecordType::Mx));
+    let DnsTcpResponse(response) = p.send_challenge().unwrap();
+
+    assert_eq!(response.answers[0].data, "10 mail.example.com.");
+    assert_eq!(response.answers[0].ttl, 3600);
+    assert_eq!(response.to_string(),
+               "NOERROR, authoritative\nexample.com. 3600 IN MX 10 mail.example.com.");
+}
@@ -0,0 +1,59 @@
+use dirac::checks::*;
+use dirac::engine::{CheckResult, CheckSuiteResult, PropertyError, PropertyResult};
+use dirac::reports::Report;
+use dirac::reports::json::JsonReport;
+use dirac::reports::md::MarkdownReport;
+use rustc_serialize::json::Json;
+
+fn check_suite() -> CheckSuite {
+    let yaml = r#"
+- inventory:
+    local: [127.0.0.1]
+
+- hosts: local
+  properties:
+    - name: SMTP banner
+      text_tcp: { port: 25, banner: "^220 " }
+"#;
+    CheckSuite::read_from_string(yaml).unwrap()
+}
+
+/// A result with a single property that rejected the response `220-first\r\n500 second\r\n`.
+fn failed_result(check_suite: &CheckSuite) -> CheckSuiteResult {
+    let check = &check_suite.checks[0];
+    let mut check_result = CheckResult::new(check);
+    check_result.results.push(PropertyResult {
+        host: "127.0.0.1",
+        property: &check.properties[0],
+        result: Err(PropertyError::FailedResponseCheck("banner does not match".to_string())),
+        response: Some("220-first\r\n500 second\r\n".to_string()),
+        skipped: false,
+    });
+    let mut check_suite_result = CheckSuiteResult::new(check_suite);
+    check_suite_result.results.push(check_result);
+
+    check_suite_result
+}
+
+#[test]
+fn json_report_contains_reason_and_response_test() {
+    let check_suite = check_suite();
+    let check_suite_result = failed_result(&check_suite);
+
+    let json = Json::from_str(&JsonReport::new(&check_suite_result, "report.json").as_string()).unwrap();
+    let property_result = &json["check_results"][0]["property_results"][0];
+    assert_eq!(property_result["property_result"].as_string(), Some("FailedResponseCheck"));
+    assert_eq!(property_result["reason"].as_string(), Some("banner does not match"));
+    assert_eq!(property_result["response"].as_string(), Some("220-first\r\n500 second\r\n"));
+}
+
+#[test]
+fn markdown_report_contains_reason_and_response_test() {
+    let check_suite = check_suite();
+    let check_suite_result = failed_result(&check_suite);
+
+    let md = MarkdownReport::new(&check_suite_result, "report.md").as_string();
+    let expected = "* SMTP banner *text_tcp* **Failed** (FailedResponseCheck)\n\n  banner does not match\n\n  \
+                    ```\n  220-first\n  500 second\n  ```\n";
+    assert!(md.contains(expected), "{}", md);
+}